@@ -1,82 +1,7 @@
-use davbjor_chess::{ChessBoard, PieceType, GameResult};
+use davbjor_chess::{ChessBoard, PieceType, GameResult, string_to_square};
 use std::io;
 
 
-/*
-Converts chess notation to a square on the board, on bad input it returns square 64 (non-existant),
-*/
-pub fn string_to_square(s: String) -> usize {
-    let x = s.to_uppercase();
-    return match x.as_str() {
-        "A1"  => 0,
-        "B1"  => 1,
-        "C1"  => 2,
-        "D1"  => 3,
-        "E1"  => 4,
-        "F1"  => 5,
-        "G1"  => 6,
-        "H1"  => 7,
-        "A2"  => 8,
-        "B2"  => 9,
-        "C2"  => 10,
-        "D2"  => 11,
-        "E2"  => 12,
-        "F2"  => 13,
-        "G2"  => 14,
-        "H2"  => 15,
-        "A3"  => 16,
-        "B3"  => 17,
-        "C3"  => 18,
-        "D3"  => 19,
-        "E3"  => 20,
-        "F3"  => 21,
-        "G3"  => 22,
-        "H3"  => 23,
-        "A4"  => 24,
-        "B4"  => 25,
-        "C4"  => 26,
-        "D4"  => 27,
-        "E4"  => 28,
-        "F4"  => 29,
-        "G4"  => 30,
-        "H4"  => 31,
-        "A5"  => 32,
-        "B5"  => 33,
-        "C5"  => 34,
-        "D5"  => 35,
-        "E5"  => 36,
-        "F5"  => 37,
-        "G5"  => 38,
-        "H5"  => 39,
-        "A6"  => 40,
-        "B6"  => 41,
-        "C6"  => 42,
-        "D6"  => 43,
-        "E6"  => 44,
-        "F6"  => 45,
-        "G6"  => 46,
-        "H6"  => 47,
-        "A7"  => 48,
-        "B7"  => 49,
-        "C7"  => 50,
-        "D7"  => 51,
-        "E7"  => 52,
-        "F7"  => 53,
-        "G7"  => 54,
-        "H7"  => 55,
-        "A8"  => 56,
-        "B8"  => 57,
-        "C8"  => 58,
-        "D8"  => 59,
-        "E8"  => 60,
-        "F8"  => 61,
-        "G8"  => 62,
-        "H8"  => 63,
-        _ => 64
-    }
-}
-    
-
 fn main() {
     let mut chess = ChessBoard::new(); 
     //chess.load("k5rr/8/8/8/8/8/7p/7K w ---- - 0 1".to_string());
@@ -84,11 +9,23 @@ fn main() {
     loop {
         //Check the state of the game
         match chess.game_result {
-            GameResult::White => { println!("White has won!"); break; },
-            GameResult::Black => { println!("Black has won!"); break; },
-            GameResult::Draw => { println!("Game is a draw!"); break; },
+            GameResult::White => { println!("White has won!"); },
+            GameResult::Black => { println!("Black has won!"); },
+            GameResult::Draw => { println!("Game is a draw!"); },
             _ => ()
         } 
+        if let Some(termination) = chess.termination {
+            println!("The game ended by {termination}");
+        }
+
+        // Write the finished game as PGN
+        if chess.game_result != GameResult::Ongoing {
+            match chess.game_record().to_pgn() {
+                Ok(pgn) => println!("\n{pgn}"),
+                Err(s) => println!("Error: {s}")
+            }
+            break;
+        }
 
         for i in (0..8).rev() {
             let mut s = "".to_string();
@@ -116,7 +53,8 @@ fn main() {
 
         // Read input n
         let mut line = String::new();
-        io::stdin().read_line(&mut line).expect("failed to readline");
+        // Stop at the end of the input, or when asked to
+        if io::stdin().read_line(&mut line).expect("failed to readline") == 0 || line.trim() == "quit" { break; }
 
         //let line = input.next().unwrap().unwrap();
         let vec: Vec<&str> = line.split(" ").collect();
@@ -129,9 +67,7 @@ fn main() {
             
             if sq1 == 64 || sq2 == 64 { break; }
             match chess.move_piece(sq1, sq2) {
-                Ok(true) => {
-                    ()
-                },
+                Ok(true) => (),
                 Ok(false) => (),
                 Err(s) => println!("Error: {s}")
             }
@@ -140,7 +76,16 @@ fn main() {
             let w1 = vec[0];
             let sq1 = string_to_square(w1.trim().to_string());
 
-            if sq1 == 64 { break; }
+            // Not a square, try to read it as a move in algebraic notation (e.g. Nf3)
+            if sq1 == 64 {
+                match chess.parse_san(w1.trim()) {
+                    Ok(m) => {
+                        if let Err(s) = chess.make_move(m) { println!("Error: {s}"); }
+                    },
+                    Err(s) => println!("Error: {s}")
+                }
+                continue;
+            }
             let moves = chess.get_moves_list(sq1);
             for m in moves {
                 println!("{m}");
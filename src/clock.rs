@@ -0,0 +1,330 @@
+//! Chess clock for timed games
+//!
+//! The clock never reads the system time, every method is given the current time as a Duration since any fixed point (e.g. the start of the game), so the same timestamps always give the same result
+//!
+//! A time control is a list of stages, every stage gives time for a number of moves (or the rest of the game), and adds time to the clock of a player for every move (Fischer increment, Bronstein delay or simple delay)
+//!
+//! # Examples
+//!
+//! ```
+//! use std::time::Duration;
+//! use davbjor_chess::clock::{Clock, TimeControl};
+//!
+//! // 3 minutes and 2 seconds for every move
+//! let mut clock = Clock::new(TimeControl::fischer(Duration::from_secs(180), Duration::from_secs(2)));
+//! clock.start(true, Duration::ZERO);
+//!
+//! // White moves after 10 seconds, black after 5 more
+//! assert_eq!(clock.press(Duration::from_secs(10)), None);
+//! assert_eq!(clock.press(Duration::from_secs(15)), None);
+//! assert_eq!(clock.remaining(true, Duration::from_secs(15)), Duration::from_secs(172));
+//! assert_eq!(clock.remaining(false, Duration::from_secs(15)), Duration::from_secs(177));
+//! ```
+
+use std::fmt;
+use std::time::Duration;
+
+/// Time added to the clock of a player for every move
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Increment {
+    /// No time is added
+    None,
+    /// The time is added after every move
+    Fischer(Duration),
+    /// The time used for the move is added back after it, but never more than the delay
+    Bronstein(Duration),
+    /// The clock waits for the delay before it starts counting down on every move
+    Delay(Duration),
+}
+
+/// One period of a time control
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Stage {
+    /// Moves to play in the stage, or None for the rest of the game
+    pub moves: Option<u32>,
+    /// Time added to the clock when the stage starts
+    pub time: Duration,
+    /// Time added for every move played in the stage
+    pub increment: Increment,
+}
+
+/// Reason a time control can't be created
+#[derive(Debug, PartialEq, Clone)]
+pub enum TimeControlError {
+    /// The time control has no stages
+    NoStages,
+}
+
+impl fmt::Display for TimeControlError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            TimeControlError::NoStages => write!(f, "A time control needs at least one stage"),
+        }
+    }
+}
+
+impl std::error::Error for TimeControlError {}
+
+/// Time control of a game, the stages are played in order
+///
+/// When every move of the last stage has been played, the last stage starts again
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TimeControl {
+    stages: Vec<Stage>,
+}
+
+impl TimeControl {
+    /// Time control of one or more stages
+    ///
+    /// Returns Err(TimeControlError::NoStages) if there are no stages
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use davbjor_chess::clock::{TimeControl, TimeControlError, Stage, Increment};
+    ///
+    /// // 40 moves in 90 minutes, then 30 minutes for the rest of the game, with 30 seconds for every move
+    /// let increment = Increment::Fischer(Duration::from_secs(30));
+    /// let control = TimeControl::new(vec![
+    ///     Stage { moves: Some(40), time: Duration::from_secs(90 * 60), increment },
+    ///     Stage { moves: None, time: Duration::from_secs(30 * 60), increment },
+    /// ]).unwrap();
+    /// assert_eq!(control.stages().len(), 2);
+    ///
+    /// assert_eq!(TimeControl::new(vec![]), Err(TimeControlError::NoStages));
+    /// ```
+    ///
+    pub fn new(stages: Vec<Stage>) -> Result<TimeControl, TimeControlError> {
+        if stages.is_empty() {
+            return Err(TimeControlError::NoStages);
+        }
+        Ok(TimeControl { stages })
+    }
+
+    /// The same time for the whole game, and nothing added for the moves
+    pub fn sudden_death(time: Duration) -> TimeControl {
+        TimeControl::single(time, Increment::None)
+    }
+
+    /// Time for the whole game, and an increment added after every move
+    pub fn fischer(time: Duration, increment: Duration) -> TimeControl {
+        TimeControl::single(time, Increment::Fischer(increment))
+    }
+
+    /// Time for the whole game, the time used for every move is added back up to the delay
+    pub fn bronstein(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single(time, Increment::Bronstein(delay))
+    }
+
+    /// Time for the whole game, the clock waits for the delay on every move before counting down
+    pub fn simple_delay(time: Duration, delay: Duration) -> TimeControl {
+        TimeControl::single(time, Increment::Delay(delay))
+    }
+
+    /// Stages of the time control, in order
+    pub fn stages(&self) -> &[Stage] {
+        &self.stages
+    }
+
+    fn single(time: Duration, increment: Increment) -> TimeControl {
+        TimeControl { stages: vec![Stage { moves: None, time, increment }] }
+    }
+}
+
+/*
+Time left, and the progress through the stages of one player
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Player {
+    remaining: Duration,
+    stage: usize,
+    // Moves played in the current stage
+    moves: u32,
+}
+
+/*
+Everything of a clock that changes during the game (not the time control), copied without allocating so it can be stored for every move
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) struct ClockState {
+    players: [Player; 2],
+    white_to_move: bool,
+    started: Option<Duration>,
+    last_update: Duration,
+    flag: Option<bool>,
+}
+
+/// Clocks of both players, only the clock of the player to move is counting down
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Clock {
+    control: TimeControl,
+    // Indexed by color, black = 0 and white = 1
+    players: [Player; 2],
+    white_to_move: bool,
+    // Time the current move started, None when the clock is stopped
+    started: Option<Duration>,
+    // Latest time given to the clock
+    last_update: Duration,
+    // Player that ran out of time (white if true)
+    flag: Option<bool>,
+}
+
+impl Clock {
+    /// Creates a stopped clock, both players have the time of the first stage
+    pub fn new(control: TimeControl) -> Clock {
+        let player = Player { remaining: control.stages[0].time, stage: 0, moves: 0 };
+        Clock {
+            control,
+            players: [player; 2],
+            white_to_move: true,
+            started: None,
+            last_update: Duration::ZERO,
+            flag: None,
+        }
+    }
+
+    /// Starts counting down the time of a player (white if true), does nothing if the clock is running or a player has run out of time
+    pub fn start(&mut self, white: bool, now: Duration) {
+        if self.started.is_some() || self.flag.is_some() { return; }
+        self.last_update = self.last_update.max(now);
+        self.white_to_move = white;
+        self.started = Some(self.last_update);
+    }
+
+    /// Stops the clock, the time of the player to move is used up to now but nothing is added for the move
+    pub fn stop(&mut self, now: Duration) {
+        if self.update(now).is_some() { return; }
+        let used = self.used(self.last_update);
+        let player = &mut self.players[self.white_to_move as usize];
+        player.remaining = player.remaining.saturating_sub(used);
+        self.started = None;
+    }
+
+    /// Checks if the player to move has run out of time, and stops the clock if so
+    ///
+    /// Times earlier than the latest time given to the clock count as the latest time
+    ///
+    /// Returns Some(white) if a player has run out of time (white if true), or None if both players have time left
+    pub fn update(&mut self, now: Duration) -> Option<bool> {
+        self.last_update = self.last_update.max(now);
+        if self.flag.is_some() || self.started.is_none() { return self.flag; }
+
+        let used = self.used(self.last_update);
+        let player = &mut self.players[self.white_to_move as usize];
+        if used >= player.remaining {
+            player.remaining = Duration::ZERO;
+            self.started = None;
+            self.flag = Some(self.white_to_move);
+        }
+        self.flag
+    }
+
+    /// The player to move has made a move, their time is used up to now and the time of the other player starts counting down
+    ///
+    /// The increment of the stage is added, and the time of the next stage if this move ended a stage
+    ///
+    /// Does nothing if the clock is stopped
+    ///
+    /// Returns Some(white) if a player ran out of time before the move (white if true), or None if the move was made in time
+    pub fn press(&mut self, now: Duration) -> Option<bool> {
+        if self.update(now).is_some() { return self.flag; }
+        let started = self.started?;
+
+        let now = self.last_update;
+        let elapsed = now.saturating_sub(started);
+        let used = self.used(now);
+        let stages = &self.control.stages;
+        let player = &mut self.players[self.white_to_move as usize];
+
+        let stage = stages[player.stage];
+        player.remaining -= used;
+        player.remaining += match stage.increment {
+            Increment::Fischer(increment) => increment,
+            Increment::Bronstein(delay) => elapsed.min(delay),
+            Increment::None | Increment::Delay(_) => Duration::ZERO
+        };
+
+        // The last stage starts again when it is finished
+        player.moves += 1;
+        if stage.moves.is_some_and(|moves| player.moves >= moves) {
+            player.stage = (player.stage + 1).min(stages.len() - 1);
+            player.moves = 0;
+            player.remaining += stages[player.stage].time;
+        }
+
+        self.white_to_move = !self.white_to_move;
+        self.started = Some(now);
+        None
+    }
+
+    /// Time left for a player (white if true) at a time, not counting a delay that hasn't been used yet
+    pub fn remaining(&self, white: bool, now: Duration) -> Duration {
+        let remaining = self.players[white as usize].remaining;
+        if white != self.white_to_move { return remaining; }
+        remaining.saturating_sub(self.used(now.max(self.last_update)))
+    }
+
+    /// Player whos clock is counting down, or would be if the clock was running (white if true)
+    pub fn white_to_move(&self) -> bool {
+        self.white_to_move
+    }
+
+    /// Checks if the time of a player is counting down
+    pub fn is_running(&self) -> bool {
+        self.started.is_some()
+    }
+
+    /// Player that has run out of time (white if true), or None if both players have time left
+    pub fn flag(&self) -> Option<bool> {
+        self.flag
+    }
+
+    /// Latest time given to the clock
+    pub fn last_update(&self) -> Duration {
+        self.last_update
+    }
+
+    /// Time control the clock was created with
+    pub fn time_control(&self) -> &TimeControl {
+        &self.control
+    }
+
+    /*
+    Copy of the state of the clock, to be restored with restore
+    */
+    pub(crate) fn state(&self) -> ClockState {
+        ClockState {
+            players: self.players,
+            white_to_move: self.white_to_move,
+            started: self.started,
+            last_update: self.last_update,
+            flag: self.flag,
+        }
+    }
+
+    /*
+    Sets the clock back to a state, the stages are kept within the time control in case the state was from another one
+    */
+    pub(crate) fn restore(&mut self, state: ClockState) {
+        let last_stage = self.control.stages.len() - 1;
+        self.players = state.players.map(|player| Player { stage: player.stage.min(last_stage), ..player });
+        self.white_to_move = state.white_to_move;
+        self.started = state.started;
+        self.last_update = state.last_update;
+        self.flag = state.flag;
+    }
+
+    /*
+    Time taken from the clock of the player to move since the move started, a simple delay is not taken from the clock
+    */
+    fn used(&self, now: Duration) -> Duration {
+        let Some(started) = self.started else { return Duration::ZERO; };
+        let elapsed = now.saturating_sub(started);
+        let stage = self.control.stages[self.players[self.white_to_move as usize].stage];
+        match stage.increment {
+            Increment::Delay(delay) => elapsed.saturating_sub(delay),
+            _ => elapsed
+        }
+    }
+}
@@ -2,7 +2,7 @@ pub mod patterns {
 
 
 type BitBoard = u64;
-use crate::lookup::tables::{MASK_RANK, CLEAR_FILE};
+use crate::lookup::tables::{MASK_RANK, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, bishop_attacks, rook_attacks};
 
 
 /*
@@ -31,11 +31,22 @@ pub fn bit_count (bit: BitBoard) -> usize {
     bit.count_ones() as usize
 }
 
+/*
+Loops through the squares of every piece on a bitboard
+*/
+pub fn squares(mut pieces: BitBoard) -> impl Iterator<Item = usize> {
+    std::iter::from_fn(move || {
+        if pieces == 0 { return None; }
+        let square = pieces.trailing_zeros() as usize;
+        pieces &= pieces - 1;
+        Some(square)
+    })
+}
+
 
 /*
 Compute King Valid Moves (Incomplete)
-Moving king to surrounding spots, masking with own pieces
-Using Clear file to avoid clipping edge
+Looking up the squares surrounding each king, masking with own pieces
 
 Spots:
 1 2 3
@@ -46,36 +57,24 @@ TODO:
 Need check and checkmate validation later
 */
 pub fn compute_king_attacks(king: BitBoard, own_pieces: BitBoard) -> BitBoard {
-    let king_clip_h = king & CLEAR_FILE[7];
-    let king_clip_a = king & CLEAR_FILE[0];
-
-    let spot_1 = king_clip_a << 7;
-    let spot_2 = king << 8;
-    let spot_3 = king_clip_h << 9;
-    let spot_4 = king_clip_h << 1;
+    let mut king_moves: BitBoard = 0;
 
-    let spot_5 = king_clip_h >> 7;
-    let spot_6 = king >> 8;
-    let spot_7 = king_clip_a >> 9;
-    let spot_8 = king_clip_a >> 1;
-
-    let king_moves = spot_1 | spot_2 | spot_3 | spot_4 | spot_5 | spot_6 | spot_7 | spot_8;
+    // Loop through every king on the board, removing the lowest one each time
+    let mut kings = king;
+    while kings != 0 {
+        king_moves |= KING_ATTACKS[kings.trailing_zeros() as usize];
+        kings &= kings - 1;
+    }
 
     /* Remove if own pieces block */
-    let king_valid = king_moves & !own_pieces;
 
-    /*
-    Needs check testing
-    */
-
-    king_valid
+    king_moves & !own_pieces
 }
 
 
 /*
 Compute Knights valid moves (Incomplete)
-Moving knights to surrounding spots, masking with own pieces
-Using Clear file to avoid clipping edge
+Looking up the spots each knight jumps to, masking with own pieces
 
 Spots:
  2 3
@@ -88,33 +87,16 @@ TODO:
 Need check (pin) validation later
 */
 pub fn compute_knight_attacks(knight: BitBoard, own_pieces: BitBoard) -> BitBoard {
-    let clip_1 = knight & CLEAR_FILE[0] & CLEAR_FILE[1];
-    let clip_2 = knight & CLEAR_FILE[0];
-    
-    let clip_3 = knight & CLEAR_FILE[7];
-    let clip_4 = knight & CLEAR_FILE[7] & CLEAR_FILE[6];
-
-    let clip_5 = knight & CLEAR_FILE[7] & CLEAR_FILE[6];
-    let clip_6 = knight & CLEAR_FILE[7];
-    let clip_7 = knight & CLEAR_FILE[0];
-    let clip_8 = knight & CLEAR_FILE[0] & CLEAR_FILE[1];
-    
-    
-    let spot_1 = clip_1 << 6;
-    let spot_2 = clip_2 << 15;
-    let spot_3 = clip_3 << 17;
-    let spot_4 = clip_4 << 10;
-
-    let spot_5 = clip_5 >> 6;
-    let spot_6 = clip_6 >> 15;
-    let spot_7 = clip_7 >> 17;
-    let spot_8 = clip_8 >> 10;
+    let mut knight_moves: BitBoard = 0;
 
-    let knight_moves = spot_1 | spot_2 | spot_3 | spot_4 | spot_5 | spot_6 | spot_7 | spot_8;
+    let mut knights = knight;
+    while knights != 0 {
+        knight_moves |= KNIGHT_ATTACKS[knights.trailing_zeros() as usize];
+        knights &= knights - 1;
+    }
 
-    let knight_valid = knight_moves & !own_pieces;
 
-    knight_valid
+    knight_moves & !own_pieces
 }
 
 
@@ -124,13 +106,16 @@ Compute the attacking squares masked by the opposing pieces
   P
 */
 pub fn compute_white_pawn_attacks(white_pawn: BitBoard, black_pieces: BitBoard) -> BitBoard {
-    // Attack spot 3, unless on file A and only if enemy piece is there
-    let spot_3 = ((white_pawn & CLEAR_FILE[0]) << 7) & black_pieces;
+    let mut pawn_attacks: BitBoard = 0;
 
-    // Attack spot 4, unless on file H and only if enemy piece is there
-    let spot_4 = ((white_pawn & CLEAR_FILE[7]) << 9) & black_pieces;
+    let mut pawns = white_pawn;
+    while pawns != 0 {
+        pawn_attacks |= PAWN_ATTACKS[0][pawns.trailing_zeros() as usize];
+        pawns &= pawns - 1;
+    }
 
-    return spot_3 | spot_4;
+    // Only if enemy piece is there
+    pawn_attacks & black_pieces
 }
 
 /*
@@ -154,9 +139,8 @@ pub fn compute_white_pawn_moves(white_pawn: BitBoard, all_pieces: BitBoard, blac
 
     let pawn_attacks = compute_white_pawn_attacks(white_pawn, black_pieces);
 
-    let white_pawn_moves = spot_1 | spot_2 | pawn_attacks;
 
-    white_pawn_moves
+    spot_1 | spot_2 | pawn_attacks
 }
 
 /*
@@ -166,13 +150,16 @@ Compute the attacking squares masked by the opposing pieces
 
 */
 pub fn compute_black_pawn_attacks (black_pawn: BitBoard, white_pieces: BitBoard) -> BitBoard {
-    // Attack spot 3, unless on file A and only if enemy piece is there
-    let spot_3 = ((black_pawn & CLEAR_FILE[0]) >> 9) & white_pieces;
+    let mut pawn_attacks: BitBoard = 0;
 
-    // Attack spot 4, unless on file H and only if enemy piece is there
-    let spot_4 = ((black_pawn & CLEAR_FILE[7]) >> 7) & white_pieces;
+    let mut pawns = black_pawn;
+    while pawns != 0 {
+        pawn_attacks |= PAWN_ATTACKS[1][pawns.trailing_zeros() as usize];
+        pawns &= pawns - 1;
+    }
 
-    return spot_3 | spot_4;
+    // Only if enemy piece is there
+    pawn_attacks & white_pieces
 }
 
 
@@ -197,16 +184,32 @@ pub fn compute_black_pawn_moves(black_pawn: BitBoard, all_pieces: BitBoard, whit
 
     let pawn_attacks = compute_black_pawn_attacks(black_pawn, white_pieces);
 
-    let black_pawn_moves = spot_1 | spot_2 | pawn_attacks;
 
-    black_pawn_moves
+    spot_1 | spot_2 | pawn_attacks
 }
 
 /*
-Compute the targets the bishop could possibly have, (targets could be of own color)
-
+Compute the targets the bishop could possibly have, masked by own pieces (enemy pieces in the path can be captured)
+Uses the magic bitboard tables
 */
 pub fn compute_bishop_attacks(bishop: BitBoard, all_pieces: BitBoard, enemy_pieces: BitBoard) -> BitBoard {
+    bishop_attacks(bit_scan(bishop), all_pieces) & (!all_pieces | enemy_pieces)
+}
+
+/*
+Compute the targets a rook could attack, masked by own pieces (enemy pieces in the path can be captured)
+Uses the magic bitboard tables
+*/
+pub fn compute_rook_attacks(rook: BitBoard, all_pieces: BitBoard, enemy_pieces: BitBoard) -> BitBoard {
+    rook_attacks(bit_scan(rook), all_pieces) & (!all_pieces | enemy_pieces)
+}
+
+/*
+Compute the targets the bishop could possibly have by walking each ray
+Kept to test the magic bitboard tables against
+*/
+#[cfg(test)]
+pub fn compute_bishop_attacks_by_rays(bishop: BitBoard, all_pieces: BitBoard, enemy_pieces: BitBoard) -> BitBoard {
     let mut attacks: BitBoard = 0;
 
     let square = bit_scan(bishop);
@@ -263,10 +266,11 @@ pub fn compute_bishop_attacks(bishop: BitBoard, all_pieces: BitBoard, enemy_piec
 }
 
 /*
-Compute the targets a rook could attack, (could be target of own color)
-
+Compute the targets a rook could attack by walking each ray
+Kept to test the magic bitboard tables against
 */
-pub fn compute_rook_attacks(rook: BitBoard, all_pieces: BitBoard, enemy_pieces: BitBoard) -> BitBoard {
+#[cfg(test)]
+pub fn compute_rook_attacks_by_rays(rook: BitBoard, all_pieces: BitBoard, enemy_pieces: BitBoard) -> BitBoard {
     let mut attacks: BitBoard = 0;
     let square = bit_scan(rook);
     let tr = square / 8;
@@ -0,0 +1,509 @@
+//! A computer opponent, searching the moves of a position with alpha-beta search
+//!
+//! # Examples
+//!
+//! ```
+//! use davbjor_chess::ChessBoard;
+//! use davbjor_chess::engine::{Engine, SearchLimits, Score};
+//!
+//! let mut chess = ChessBoard::new();
+//! chess.load("6k1/5ppp/8/8/8/8/5PPP/3R2K1 w - - 0 1".to_string());
+//!
+//! let mut engine = Engine::new();
+//! let result = engine.search(&chess, SearchLimits { depth: Some(3), ..Default::default() });
+//!
+//! assert_eq!(result.best_move.unwrap().to_uci(), "d1d8");
+//! assert_eq!(result.score, Score::Mate(1));
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use crate::{ChessBoard, Move};
+use crate::eval;
+
+pub mod uci;
+mod picker;
+mod tt;
+use picker::{Heuristics, MovePicker};
+use tt::{Bound, TranspositionTable};
+
+/*
+Score of a checkmate, a mate in more plies scores a little less so the fastest mate is preferred
+*/
+const MATE: i32 = 30_000;
+const MAX_PLY: usize = 128;
+const INFINITY: i32 = MATE + 1;
+
+/// Size of the transposition table of a new engine, in megabytes
+pub const DEFAULT_HASH_SIZE: usize = 16;
+
+/// Limits for how long the engine searches, the search stops when the first limit is reached
+///
+/// Without any limits the search goes on until it is stopped with the stop flag (see Engine::stop_flag)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct SearchLimits {
+    /// Deepest number of plies to search
+    pub depth: Option<usize>,
+    /// Most positions to visit
+    pub nodes: Option<u64>,
+    /// Longest time to search
+    pub time: Option<Duration>,
+}
+
+/// Score of a position for the player whos turn it is
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Score {
+    /// Advantage in centipawns (100 is about one pawn)
+    Centipawns(i32),
+    /// Mate in a number of moves, negative if the player whos turn it is gets mated
+    Mate(i32),
+}
+
+impl Score {
+    fn from_value(value: i32) -> Score {
+        if value >= MATE - MAX_PLY as i32 {
+            return Score::Mate((MATE - value + 1) / 2);
+        }
+        if value <= -MATE + MAX_PLY as i32 {
+            return Score::Mate(-(MATE + value) / 2);
+        }
+        Score::Centipawns(value)
+    }
+}
+
+impl fmt::Display for Score {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Score::Centipawns(cp) => write!(f, "cp {}", cp),
+            Score::Mate(moves) => write!(f, "mate {}", moves)
+        }
+    }
+}
+
+/// Result of a search
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SearchResult {
+    /// Best move found (None if the player whos turn it is has no legal moves)
+    pub best_move: Option<Move>,
+    /// Score of the position after the best move, for the player whos turn it is
+    pub score: Score,
+    /// Principal variation, the expected line of play starting with the best move
+    pub pv: Vec<Move>,
+    /// Deepest number of plies searched completely
+    pub depth: usize,
+    /// Number of positions visited
+    pub nodes: u64,
+    /// Used part of the transposition table in permille
+    pub hashfull: usize,
+}
+
+/// Searches positions for the best move, with negamax alpha-beta search and iterative deepening
+///
+/// Searched positions are stored in a transposition table, which is kept between searches.
+/// The engine searches a copy of the game, the ChessBoard passed to it is not changed
+pub struct Engine {
+    stop: Arc<AtomicBool>,
+    // While set, the time limit is not used and the time of the search doesn't start
+    ponder: Arc<AtomicBool>,
+    limits: SearchLimits,
+    start: Instant,
+    nodes: u64,
+    stopped: bool,
+    // The first iteration is always finished, so there is a move to return
+    can_stop: bool,
+    // Hashes of the positions of the game and the current line, to find repetitions
+    hashes: Vec<u64>,
+    tt: TranspositionTable,
+    heuristics: Heuristics,
+}
+
+impl Default for Engine {
+    fn default() -> Self {
+        Engine::new()
+    }
+}
+
+impl Engine {
+    pub fn new() -> Self {
+        Engine {
+            stop: Arc::new(AtomicBool::new(false)),
+            ponder: Arc::new(AtomicBool::new(false)),
+            limits: SearchLimits::default(),
+            start: Instant::now(),
+            nodes: 0,
+            stopped: false,
+            can_stop: false,
+            hashes: Vec::new(),
+            tt: TranspositionTable::new(DEFAULT_HASH_SIZE),
+            heuristics: Heuristics::new(),
+        }
+    }
+
+    /// Changes the size of the transposition table to about mb megabytes, every stored position is removed
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::ChessBoard;
+    /// use davbjor_chess::engine::{Engine, SearchLimits};
+    ///
+    /// let mut engine = Engine::new();
+    /// engine.set_hash_size(1);
+    ///
+    /// let result = engine.search(&ChessBoard::new(), SearchLimits { depth: Some(4), ..Default::default() });
+    /// assert!(result.hashfull > 0);
+    /// ```
+    ///
+    pub fn set_hash_size(&mut self, mb: usize) {
+        self.tt = TranspositionTable::new(mb);
+    }
+
+    /// Removes every position stored in the transposition table, and the move ordering learned from earlier searches (e.g. when a new game starts)
+    pub fn clear_hash(&mut self) {
+        self.tt.clear();
+        self.heuristics.clear();
+    }
+
+    /// Returns the flag that stops the search when set to true, so a search can be stopped from another thread
+    ///
+    /// The flag is reset when a new search starts
+    pub fn stop_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.stop)
+    }
+
+    /// Returns the flag that makes the search ignore the time limit while it is true, so the engine can think on the time of the opponent (pondering)
+    ///
+    /// The time of the search starts counting when the flag is set to false (e.g. when the opponent makes the expected move), the flag is not reset when a new search starts
+    pub fn ponder_flag(&self) -> Arc<AtomicBool> {
+        Arc::clone(&self.ponder)
+    }
+
+    /// Searches the position for the best move until one of the limits is reached
+    ///
+    /// The search goes one ply deeper for every iteration, and the result of the last complete iteration is returned.
+    /// At least one iteration is always completed, so a legal move is returned if there is one
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use davbjor_chess::ChessBoard;
+    /// use davbjor_chess::engine::{Engine, SearchLimits};
+    ///
+    /// let chess = ChessBoard::new();
+    /// let mut engine = Engine::new();
+    ///
+    /// let result = engine.search(&chess, SearchLimits { time: Some(Duration::from_millis(50)), ..Default::default() });
+    /// assert!(chess.legal_moves().contains(&result.best_move.unwrap()));
+    /// assert_eq!(result.pv[0], result.best_move.unwrap());
+    /// ```
+    ///
+    pub fn search(&mut self, chess: &ChessBoard, limits: SearchLimits) -> SearchResult {
+        self.search_with_info(chess, limits, |_| ())
+    }
+
+    /// Searches the position like Engine::search, and calls info with the result of every complete iteration
+    ///
+    /// Useful to show the progress of a long search (e.g. the info lines of the UCI-protocol)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::ChessBoard;
+    /// use davbjor_chess::engine::{Engine, SearchLimits};
+    ///
+    /// let chess = ChessBoard::new();
+    /// let mut engine = Engine::new();
+    ///
+    /// let mut depths = vec![];
+    /// let result = engine.search_with_info(&chess, SearchLimits { depth: Some(3), ..Default::default() }, |info| depths.push(info.depth));
+    ///
+    /// assert_eq!(depths, vec![1, 2, 3]);
+    /// assert_eq!(result.depth, 3);
+    /// ```
+    ///
+    pub fn search_with_info<F: FnMut(&SearchResult)>(&mut self, chess: &ChessBoard, limits: SearchLimits, mut info: F) -> SearchResult {
+        self.stop.store(false, Ordering::Relaxed);
+        self.limits = limits;
+        self.start = Instant::now();
+        self.nodes = 0;
+        self.stopped = false;
+        self.can_stop = false;
+        self.tt.new_search();
+        self.heuristics.new_search();
+
+        let mut chess = chess.clone();
+        self.hashes = chess.positions.clone();
+        if self.hashes.last() != Some(&chess.hash()) {
+            self.hashes.push(chess.hash());
+        }
+
+        let mut result = SearchResult {
+            best_move: None,
+            score: Score::Centipawns(0),
+            pv: Vec::new(),
+            depth: 0,
+            nodes: 0,
+            hashfull: 0,
+        };
+
+        let max_depth = limits.depth.unwrap_or(MAX_PLY).clamp(1, MAX_PLY);
+        for depth in 1..=max_depth {
+            let mut pv: Vec<Move> = Vec::new();
+            let value = self.negamax(&mut chess, depth, 0, -INFINITY, INFINITY, &result.pv, &mut pv);
+
+            // An unfinished iteration is thrown away
+            if self.stopped { break; }
+            self.extend_pv(&mut chess, &mut pv);
+
+            result.best_move = pv.first().copied();
+            result.score = Score::from_value(value);
+            result.pv = pv;
+            result.depth = depth;
+            result.nodes = self.nodes;
+            result.hashfull = self.tt.hashfull();
+            self.can_stop = true;
+            info(&result);
+
+            // No need to search deeper when a forced mate is found, a shorter mate would have been found earlier
+            if let Score::Mate(_) = result.score { break; }
+        }
+
+        result.nodes = self.nodes;
+        result
+    }
+
+    /*
+    Negamax alpha-beta search, returns the value of the position for the player whos turn it is
+    The principal variation of the last iteration is searched first, then the best move stored in the transposition table
+    */
+    #[allow(clippy::too_many_arguments)]
+    fn negamax(&mut self, chess: &mut ChessBoard, depth: usize, ply: usize, mut alpha: i32, beta: i32, previous_pv: &[Move], pv: &mut Vec<Move>) -> i32 {
+        pv.clear();
+        if depth == 0 { return self.quiescence(chess, ply, alpha, beta, pv); }
+        if self.should_stop() { return 0; }
+        self.nodes += 1;
+
+        if ply > 0 && (chess.halfmove_clock >= 100 || self.is_repetition(chess) || chess.is_insufficient_material()) {
+            return 0;
+        }
+
+        // A result from searching at least as deep can be used, except at the root where the move is needed
+        let entry = self.tt.probe(chess.hash(), ply);
+        if let Some(entry) = entry {
+            if ply > 0 && entry.depth as usize >= depth {
+                match entry.bound {
+                    Bound::Exact => return entry.score,
+                    Bound::Lower if entry.score >= beta => return entry.score,
+                    Bound::Upper if entry.score <= alpha => return entry.score,
+                    _ => ()
+                }
+            }
+        }
+        let tt_move = entry.and_then(|entry| entry.best_move);
+
+        if ply >= MAX_PLY {
+            return evaluate(chess);
+        }
+
+        // Search the move of the last principal variation first (or the move from the table), then captures, killers and quiet moves
+        let pv_move = previous_pv.get(ply).copied();
+        let previous = chess.history.last().map(|record| record.m);
+        let mut picker = MovePicker::new(pv_move.or(tt_move), self.heuristics.killers(ply), self.heuristics.countermove(previous));
+
+        let original_alpha = alpha;
+        let mut best = -INFINITY;
+        let mut best_move = None;
+        let mut child_pv: Vec<Move> = Vec::new();
+        let mut quiets_tried: Vec<Move> = Vec::new();
+        while let Some(m) = picker.next(chess, &self.heuristics) {
+            // Only follow the last principal variation while the line is the same
+            let next_pv: &[Move] = if Some(m) == pv_move { previous_pv } else { &[] };
+
+            chess.make_move_unchecked(m);
+            self.hashes.push(chess.hash());
+            let value = -self.negamax(chess, depth - 1, ply + 1, -beta, -alpha, next_pv, &mut child_pv);
+            self.hashes.pop();
+            chess.unmake_move();
+
+            if self.stopped { return 0; }
+
+            if value > best {
+                best = value;
+                best_move = Some(m);
+            }
+            if value > alpha {
+                alpha = value;
+                pv.clear();
+                pv.push(m);
+                pv.extend_from_slice(&child_pv);
+            }
+
+            let quiet = !m.capture && !m.is_promotion();
+            if alpha >= beta {
+                // Remember the quiet move causing the cutoff, to try it early in other positions
+                if quiet {
+                    self.heuristics.update(chess.whites_turn, m, ply, depth, previous, &quiets_tried);
+                }
+                break;
+            }
+            if quiet { quiets_tried.push(m); }
+        }
+
+        // No legal moves
+        if best_move.is_none() {
+            return if chess.side_to_move_in_check() { -MATE + ply as i32 } else { 0 };
+        }
+
+        let bound = if best >= beta {
+            Bound::Lower
+        } else if best > original_alpha {
+            Bound::Exact
+        } else {
+            Bound::Upper
+        };
+        // No move was better than alpha, so none of them is known to be the best
+        let best_move = if bound == Bound::Upper { None } else { best_move };
+        self.tt.store(chess.hash(), best_move, best, depth, bound, ply);
+
+        best
+    }
+
+    /*
+    Quiescence search, only captures and promotions are searched until the position is quiet, so exchanges are not cut off in the middle
+    The player whos turn it is can stand pat (take the static evaluation) instead of capturing, except when in check where every move is searched
+    Only the captures are generated when not in check, so a stalemate is not found here
+    */
+    fn quiescence(&mut self, chess: &mut ChessBoard, ply: usize, mut alpha: i32, beta: i32, pv: &mut Vec<Move>) -> i32 {
+        pv.clear();
+        if self.should_stop() { return 0; }
+        self.nodes += 1;
+
+        if chess.halfmove_clock >= 100 || self.is_repetition(chess) || chess.is_insufficient_material() {
+            return 0;
+        }
+
+        let in_check = chess.side_to_move_in_check();
+        let moves = if in_check { chess.legal_moves() } else { chess.legal_captures() };
+        if in_check && moves.is_empty() {
+            return -MATE + ply as i32;
+        }
+        if ply >= MAX_PLY {
+            return evaluate(chess);
+        }
+
+        let mut best = -INFINITY;
+        if !in_check {
+            best = evaluate(chess);
+            if best >= beta { return best; }
+            alpha = alpha.max(best);
+        }
+
+        // Captures losing material are skipped, the best exchanges are searched first
+        let mut moves: Vec<(Move, i32)> = moves.into_iter()
+            .map(|m| (m, chess.see(m)))
+            .filter(|(_, see)| in_check || *see >= 0)
+            .collect();
+        moves.sort_by_key(|(_, see)| -see);
+
+        let mut child_pv: Vec<Move> = Vec::new();
+        for (m, _) in moves {
+            chess.make_move_unchecked(m);
+            self.hashes.push(chess.hash());
+            let value = -self.quiescence(chess, ply + 1, -beta, -alpha, &mut child_pv);
+            self.hashes.pop();
+            chess.unmake_move();
+
+            if self.stopped { return 0; }
+
+            best = best.max(value);
+            if value > alpha {
+                alpha = value;
+                pv.clear();
+                pv.push(m);
+                pv.extend_from_slice(&child_pv);
+            }
+            if alpha >= beta { break; }
+        }
+
+        best
+    }
+
+    /*
+    The principal variation ends where a position was found in the transposition table, it is continued with the best moves stored in the table
+    */
+    fn extend_pv(&self, chess: &mut ChessBoard, pv: &mut Vec<Move>) {
+        let mut seen: Vec<u64> = vec![chess.hash()];
+        for m in pv.iter() {
+            chess.make_move_unchecked(*m);
+            seen.push(chess.hash());
+        }
+
+        while pv.len() < MAX_PLY {
+            let best_move = self.tt.probe(chess.hash(), pv.len()).and_then(|entry| entry.best_move);
+            match best_move {
+                Some(m) if chess.legal_moves().contains(&m) => {
+                    chess.make_move_unchecked(m);
+                    pv.push(m);
+
+                    // Stop at a repetition, the line would go on forever
+                    if seen.contains(&chess.hash()) { break; }
+                    seen.push(chess.hash());
+                },
+                _ => break
+            }
+        }
+
+        for _ in 0..pv.len() {
+            chess.unmake_move();
+        }
+    }
+
+    /*
+    Checks if the search should stop, the clock is only read every 1024 positions
+    */
+    fn should_stop(&mut self) -> bool {
+        if self.stopped { return true; }
+        if !self.can_stop { return false; }
+
+        if self.stop.load(Ordering::Relaxed) {
+            self.stopped = true;
+        }
+        if self.limits.nodes.is_some_and(|nodes| self.nodes >= nodes) {
+            self.stopped = true;
+        }
+        if self.nodes.is_multiple_of(1024) {
+            // The time only counts from when pondering ends
+            if self.ponder.load(Ordering::Relaxed) {
+                self.start = Instant::now();
+            } else if self.limits.time.is_some_and(|time| self.start.elapsed() >= time) {
+                self.stopped = true;
+            }
+        }
+
+        self.stopped
+    }
+
+    /*
+    Checks if the position has been seen before, in the game or in the current line
+    Only positions since the last capture or pawn move can be the same, a repetition in the line is scored as a draw
+    */
+    fn is_repetition(&self, chess: &ChessBoard) -> bool {
+        let current = chess.hash();
+        self.hashes.iter().rev()
+            .skip(1)
+            .take(chess.halfmove_clock as usize)
+            .any(|hash| *hash == current)
+    }
+}
+
+/*
+Value of the position for the player whos turn it is
+*/
+fn evaluate(chess: &ChessBoard) -> i32 {
+    let value = eval::evaluate(chess);
+    if chess.whites_turn { value } else { -value }
+}
@@ -0,0 +1,253 @@
+use crate::{ChessBoard, Move, PieceType};
+use crate::lookup::tables::PIECE;
+
+use super::MAX_PLY;
+
+/*
+History scores are kept between -MAX_HISTORY and MAX_HISTORY, a bonus shrinks as the score gets close to the limit
+*/
+const MAX_HISTORY: i32 = 16_384;
+
+/*
+Stages of the move picker, in the order they are tried
+*/
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Stage {
+    HashMove,
+    GenerateCaptures,
+    WinningCaptures,
+    Killers,
+    Countermove,
+    GenerateQuiets,
+    Quiets,
+    LosingCaptures,
+    Done,
+}
+
+/// Tables of quiet moves that caused beta cutoffs earlier in the search, used to order the quiet moves of other positions
+pub struct Heuristics {
+    // Two quiet moves per ply that caused a cutoff in a sibling position
+    killers: [[Option<Move>; 2]; MAX_PLY + 1],
+    // Score of every quiet move by color, from square and to square
+    history: Vec<[[i32; 64]; 64]>,
+    // Quiet move that refuted a move, by the from square and to square of the move
+    countermoves: Vec<[Option<Move>; 64]>,
+}
+
+impl Heuristics {
+    pub fn new() -> Self {
+        Heuristics {
+            killers: [[None; 2]; MAX_PLY + 1],
+            history: vec![[[0; 64]; 64]; 2],
+            countermoves: vec![[None; 64]; 64],
+        }
+    }
+
+    /// Prepares for a new search, the killers are removed and the history scores are halved so newer results count more
+    pub fn new_search(&mut self) {
+        self.killers = [[None; 2]; MAX_PLY + 1];
+        for row in self.history.iter_mut().flatten() {
+            for score in row.iter_mut() {
+                *score /= 2;
+            }
+        }
+    }
+
+    /// Removes everything learned from earlier searches
+    pub fn clear(&mut self) {
+        *self = Heuristics::new();
+    }
+
+    /// Killer moves stored for a ply
+    pub fn killers(&self, ply: usize) -> [Option<Move>; 2] {
+        self.killers[ply.min(MAX_PLY)]
+    }
+
+    /// Countermove stored as a reply to the previous move
+    pub fn countermove(&self, previous: Option<Move>) -> Option<Move> {
+        previous.and_then(|p| self.countermoves[p.from.min(63)][p.to.min(63)])
+    }
+
+    fn history(&self, white: bool, m: Move) -> i32 {
+        self.history[white as usize][m.from.min(63)][m.to.min(63)]
+    }
+
+    /// Updates the tables after a quiet move caused a beta cutoff, the quiet moves searched before it get a lower history score
+    pub fn update(&mut self, white: bool, m: Move, ply: usize, depth: usize, previous: Option<Move>, tried: &[Move]) {
+        let killers = &mut self.killers[ply.min(MAX_PLY)];
+        if killers[0] != Some(m) {
+            killers[1] = killers[0];
+            killers[0] = Some(m);
+        }
+
+        if let Some(p) = previous {
+            self.countermoves[p.from.min(63)][p.to.min(63)] = Some(m);
+        }
+
+        let bonus = (depth * depth).min(MAX_HISTORY as usize) as i32;
+        self.add_history(white, m, bonus);
+        for quiet in tried {
+            self.add_history(white, *quiet, -bonus);
+        }
+    }
+
+    fn add_history(&mut self, white: bool, m: Move, bonus: i32) {
+        let score = &mut self.history[white as usize][m.from.min(63)][m.to.min(63)];
+        *score += bonus - *score * bonus.abs() / MAX_HISTORY;
+    }
+}
+
+/// Gives the legal moves of a position one at a time, the moves most likely to be best first
+///
+/// Moves are generated in stages (captures before quiet moves), so a beta cutoff early in the list skips generating the rest
+pub struct MovePicker {
+    stage: Stage,
+    hash_move: Option<Move>,
+    killers: [Option<Move>; 2],
+    countermove: Option<Move>,
+    // Moves already given before the stage they would be generated in
+    given: Vec<Move>,
+    moves: Vec<(Move, i32)>,
+    losing_captures: Vec<(Move, i32)>,
+    killer_index: usize,
+}
+
+impl MovePicker {
+    pub fn new(hash_move: Option<Move>, killers: [Option<Move>; 2], countermove: Option<Move>) -> Self {
+        MovePicker {
+            stage: Stage::HashMove,
+            hash_move,
+            killers,
+            countermove,
+            given: Vec::with_capacity(4),
+            moves: Vec::new(),
+            losing_captures: Vec::new(),
+            killer_index: 0,
+        }
+    }
+
+    /// Next move to search, None when every legal move has been given
+    pub fn next(&mut self, chess: &ChessBoard, heuristics: &Heuristics) -> Option<Move> {
+        loop {
+            match self.stage {
+                Stage::HashMove => {
+                    self.stage = Stage::GenerateCaptures;
+                    if let Some(m) = self.hash_move.filter(|m| is_legal(chess, *m)) {
+                        self.given.push(m);
+                        return Some(m);
+                    }
+                },
+                Stage::GenerateCaptures => {
+                    // Captures losing material and underpromotions are searched after the quiet moves
+                    for m in chess.legal_captures() {
+                        if self.given.contains(&m) { continue; }
+                        let see = chess.see(m);
+                        let underpromotion = m.promotion.is_some_and(|p| !matches!(p, PieceType::WhiteQueen | PieceType::BlackQueen));
+                        if see < 0 || underpromotion {
+                            self.losing_captures.push((m, see));
+                        } else {
+                            self.moves.push((m, mvv_lva(chess, m)));
+                        }
+                    }
+                    self.stage = Stage::WinningCaptures;
+                },
+                Stage::WinningCaptures => {
+                    if let Some(m) = pick_best(&mut self.moves) { return Some(m); }
+                    self.stage = Stage::Killers;
+                },
+                Stage::Killers => {
+                    let Some(killer) = self.killers.get(self.killer_index).copied() else {
+                        self.stage = Stage::Countermove;
+                        continue;
+                    };
+                    self.killer_index += 1;
+                    if let Some(m) = killer.filter(|m| !self.given.contains(m) && is_quiet_legal(chess, *m)) {
+                        self.given.push(m);
+                        return Some(m);
+                    }
+                },
+                Stage::Countermove => {
+                    self.stage = Stage::GenerateQuiets;
+                    if let Some(m) = self.countermove.filter(|m| !self.given.contains(m) && is_quiet_legal(chess, *m)) {
+                        self.given.push(m);
+                        return Some(m);
+                    }
+                },
+                Stage::GenerateQuiets => {
+                    let white = chess.whites_turn;
+                    self.moves = chess.legal_quiet_moves().into_iter()
+                        .filter(|m| !self.given.contains(m))
+                        .map(|m| (m, heuristics.history(white, m)))
+                        .collect();
+                    self.stage = Stage::Quiets;
+                },
+                Stage::Quiets => {
+                    if let Some(m) = pick_best(&mut self.moves) { return Some(m); }
+                    self.stage = Stage::LosingCaptures;
+                },
+                Stage::LosingCaptures => {
+                    if let Some(m) = pick_best(&mut self.losing_captures) { return Some(m); }
+                    self.stage = Stage::Done;
+                },
+                Stage::Done => return None
+            }
+        }
+    }
+}
+
+/*
+Removes and returns the move with the highest score, only the moves that are searched get sorted
+*/
+fn pick_best(moves: &mut Vec<(Move, i32)>) -> Option<Move> {
+    let best = moves.iter().enumerate().max_by_key(|(_, (_, score))| *score).map(|(i, _)| i)?;
+    Some(moves.swap_remove(best).0)
+}
+
+/*
+Most valuable victim, least valuable attacker, a queen promotion counts as capturing a queen
+*/
+fn mvv_lva(chess: &ChessBoard, m: Move) -> i32 {
+    let victim = if m.en_passant { 1 } else { kind(chess.piece_at(m.to)) };
+    let promotion = m.promotion.map(kind).unwrap_or(0);
+    (victim + promotion) * 8 - kind(chess.piece_at(m.from))
+}
+
+fn kind(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::WhitePawn | PieceType::BlackPawn => 1,
+        PieceType::WhiteKnight | PieceType::BlackKnight => 2,
+        PieceType::WhiteBishop | PieceType::BlackBishop => 3,
+        PieceType::WhiteRook | PieceType::BlackRook => 4,
+        PieceType::WhiteQueen | PieceType::BlackQueen => 5,
+        PieceType::WhiteKing | PieceType::BlackKing => 6,
+        PieceType::Empty => 0
+    }
+}
+
+/*
+Checks a move stored from another position (hash move), it has to be legal and have the right promotion piece
+*/
+fn is_legal(chess: &ChessBoard, m: Move) -> bool {
+    if m.from > 63 || m.to > 63 { return false; }
+    let piece_type = chess.piece_at(m.from);
+    if piece_type == PieceType::Empty || piece_type.is_white() != chess.whites_turn { return false; }
+    if chess.get_moves(m.from) & PIECE[m.to] == 0 { return false; }
+
+    let is_promotion = piece_type.is_pawn() && (m.to / 8 == 7 || m.to / 8 == 0);
+    match m.promotion {
+        Some(promotion) => is_promotion && promotion.is_white() == chess.whites_turn && !promotion.is_pawn() && !promotion.is_king() && promotion != PieceType::Empty,
+        None => !is_promotion
+    }
+}
+
+/*
+Checks a quiet move stored from another position (killer move, countermove), it has to be legal and still be quiet
+*/
+fn is_quiet_legal(chess: &ChessBoard, m: Move) -> bool {
+    if m.from > 63 || m.to > 63 || m.promotion.is_some() || chess.piece_at(m.to) != PieceType::Empty { return false; }
+
+    // A pawn moving diagonally to an empty square captures en passant
+    if chess.piece_at(m.from).is_pawn() && m.from % 8 != m.to % 8 { return false; }
+
+    is_legal(chess, m)
+}
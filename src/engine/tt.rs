@@ -0,0 +1,126 @@
+use crate::Move;
+
+use super::{MATE, MAX_PLY};
+
+/*
+Number of entries sharing an index, a new entry replaces the least useful of them
+*/
+const BUCKET_SIZE: usize = 2;
+
+/// What the score of an entry says about the real value of the position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Bound {
+    /// The score is the value of the position
+    Exact,
+    /// The value is at least the score (the search failed high)
+    Lower,
+    /// The value is at most the score (the search failed low)
+    Upper,
+}
+
+/// Result of searching a position, stored under the hash of the position
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Entry {
+    pub key: u64,
+    pub best_move: Option<Move>,
+    pub score: i32,
+    pub depth: u8,
+    pub bound: Bound,
+    pub age: u8,
+}
+
+type Bucket = [Option<Entry>; BUCKET_SIZE];
+
+/// Table of searched positions with a fixed size, so positions reached by different move orders are only searched once
+pub struct TranspositionTable {
+    buckets: Vec<Bucket>,
+    // Increased for every search, entries from earlier searches are replaced first
+    age: u8,
+}
+
+impl TranspositionTable {
+    /// Creates a table using about mb megabytes of memory (at least one bucket)
+    pub fn new(mb: usize) -> Self {
+        let count = (mb * 1024 * 1024 / std::mem::size_of::<Bucket>()).max(1);
+        TranspositionTable {
+            buckets: vec![[None; BUCKET_SIZE]; count],
+            age: 0,
+        }
+    }
+
+    /// Removes every entry
+    pub fn clear(&mut self) {
+        self.buckets.fill([None; BUCKET_SIZE]);
+        self.age = 0;
+    }
+
+    /// Marks the start of a new search, older entries are replaced before entries of the current search
+    pub fn new_search(&mut self) {
+        self.age = self.age.wrapping_add(1);
+    }
+
+    /// Finds the entry of a position, with the mate score adjusted to the ply it is found at
+    pub fn probe(&self, key: u64, ply: usize) -> Option<Entry> {
+        let mut entry = self.buckets[self.index(key)].iter().flatten().find(|entry| entry.key == key).copied()?;
+        entry.score = score_from_table(entry.score, ply);
+        Some(entry)
+    }
+
+    /// Stores the result of searching a position at a ply
+    ///
+    /// An entry of the same position is replaced (keeping its best move if the new result has none), otherwise the entry from the oldest search with the least depth is replaced
+    pub fn store(&mut self, key: u64, best_move: Option<Move>, score: i32, depth: usize, bound: Bound, ply: usize) {
+        let age = self.age;
+        let index = self.index(key);
+        let bucket = &mut self.buckets[index];
+
+        let slot = match bucket.iter().position(|entry| entry.is_some_and(|entry| entry.key == key)) {
+            Some(slot) => slot,
+            None => (0..BUCKET_SIZE).min_by_key(|slot| match bucket[*slot] {
+                None => (0, 0),
+                Some(entry) => ((entry.age == age) as u8 + 1, entry.depth)
+            }).unwrap_or(0)
+        };
+
+        let best_move = best_move.or(bucket[slot].filter(|entry| entry.key == key).and_then(|entry| entry.best_move));
+        bucket[slot] = Some(Entry {
+            key,
+            best_move,
+            score: score_to_table(score, ply),
+            depth: depth.min(u8::MAX as usize) as u8,
+            bound,
+            age,
+        });
+    }
+
+    /// Used part of the table in permille, from a sample of the first entries (as the hashfull info of the UCI-protocol)
+    pub fn hashfull(&self) -> usize {
+        let sample = self.buckets.iter().take(1000 / BUCKET_SIZE).flatten();
+        let (used, total) = sample.fold((0, 0), |(used, total), entry| {
+            (used + entry.is_some_and(|entry| entry.age == self.age) as usize, total + 1)
+        });
+        used * 1000 / total.max(1)
+    }
+
+    /*
+    Maps the hash to a bucket, by the high bits of multiplying it with the number of buckets
+    */
+    fn index(&self, key: u64) -> usize {
+        ((key as u128 * self.buckets.len() as u128) >> 64) as usize
+    }
+}
+
+/*
+Mate scores are stored as the distance from the position, and not from the root of the search
+*/
+fn score_to_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 { return score + ply as i32; }
+    if score <= -MATE + MAX_PLY as i32 { return score - ply as i32; }
+    score
+}
+
+fn score_from_table(score: i32, ply: usize) -> i32 {
+    if score >= MATE - MAX_PLY as i32 { return score - ply as i32; }
+    if score <= -MATE + MAX_PLY as i32 { return score + ply as i32; }
+    score
+}
@@ -0,0 +1,401 @@
+//! The engine speaking the Universal Chess Interface (UCI), so it can be played from a chess GUI
+//!
+//! Protocol: https://www.wbec-ridderkerk.nl/html/UCIProtocol.html
+//!
+//! Commands are given one line at a time, and every line for the GUI is passed to an output function (e.g. printing it to stdout)
+//!
+//! # Examples
+//!
+//! ```
+//! use std::sync::{Arc, Mutex};
+//! use davbjor_chess::engine::uci::Uci;
+//!
+//! let output = Arc::new(Mutex::new(Vec::new()));
+//! let lines = Arc::clone(&output);
+//! let mut uci = Uci::new(move |line: &str| lines.lock().unwrap().push(line.to_string()));
+//!
+//! uci.command("position startpos moves e2e4 e7e5");
+//! uci.command("go depth 2");
+//! assert!(!uci.command("quit"));
+//! uci.stop_search();
+//!
+//! assert!(output.lock().unwrap().last().unwrap().starts_with("bestmove "));
+//! ```
+
+use std::fmt;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
+use std::thread::{self, JoinHandle};
+use std::time::{Duration, Instant};
+
+use crate::{ChessBoard, FenError, MoveError};
+use super::{Engine, SearchLimits, SearchResult, DEFAULT_HASH_SIZE};
+
+const NAME: &str = "davbjor-chess";
+const AUTHOR: &str = "davbjor";
+
+/*
+Moves left in the game to plan for, when the GUI doesn't send movestogo
+*/
+const DEFAULT_MOVES_TO_GO: u32 = 30;
+const DEFAULT_MOVE_OVERHEAD: u64 = 50;
+const MAX_MOVE_OVERHEAD: u64 = 5000;
+const MAX_HASH_SIZE: usize = 4096;
+
+/// Reason a command from the GUI could not be used, it is sent back to the GUI as an info string
+#[derive(Debug, PartialEq, Clone)]
+pub enum UciError {
+    /// The command is not part of the protocol
+    UnknownCommand(String),
+    /// A position command without startpos or fen
+    MissingPosition,
+    /// The FEN of a position command could not be read
+    InvalidFen(FenError),
+    /// One of the moves of a position command is not legal, or could not be read
+    InvalidMove(MoveError),
+    /// A setoption command for an option the engine doesn't have
+    UnknownOption(String),
+    /// A setoption command with a value the option can't have
+    InvalidOptionValue { name: String, value: String },
+}
+
+impl fmt::Display for UciError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            UciError::UnknownCommand(command) => write!(f, "Unknown command {}", command),
+            UciError::MissingPosition => write!(f, "Invalid position: Expected startpos or fen"),
+            UciError::InvalidFen(error) => write!(f, "Invalid position: {}", error),
+            UciError::InvalidMove(error) => write!(f, "Invalid moves: {}", error),
+            UciError::UnknownOption(name) => write!(f, "Unknown option {}", name),
+            UciError::InvalidOptionValue { name, value } => write!(f, "Invalid value for {}: {}", name, value),
+        }
+    }
+}
+
+impl std::error::Error for UciError {}
+
+/// Reads the arguments of a position command: [startpos | fen <fen>] [moves <move1> ... <moveN>]
+///
+/// Returns Ok(chess) with the position after the moves
+///
+/// Returns Err(e) if the position can't be set up or a move can't be made, and gives the reason e (UciError)
+///
+/// # Examples
+///
+/// ```
+/// use davbjor_chess::engine::uci::{parse_position, UciError};
+///
+/// let chess = parse_position(&["startpos", "moves", "e2e4", "e7e5"]).unwrap();
+/// assert_eq!(chess.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+///
+/// assert_eq!(parse_position(&["moves", "e2e4"]).err(), Some(UciError::MissingPosition));
+/// ```
+///
+pub fn parse_position(args: &[&str]) -> Result<ChessBoard, UciError> {
+    let moves_index = args.iter().position(|arg| *arg == "moves").unwrap_or(args.len());
+    let (setup, moves) = args.split_at(moves_index);
+
+    let mut chess = match setup.split_first() {
+        Some((&"startpos", _)) => ChessBoard::new(),
+        Some((&"fen", fen)) => ChessBoard::from_fen(&fen.join(" ")).map_err(UciError::InvalidFen)?,
+        _ => return Err(UciError::MissingPosition)
+    };
+
+    if let Some((_, moves)) = moves.split_first() {
+        chess.apply_uci_moves(moves).map_err(UciError::InvalidMove)?;
+    }
+
+    Ok(chess)
+}
+
+/// The arguments of a go command, the times are given in milliseconds
+///
+/// go [depth <plies>] [nodes <n>] [movetime <ms>] [wtime <ms>] [btime <ms>] [winc <ms>] [binc <ms>] [movestogo <n>] [infinite] [ponder]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct GoOptions {
+    /// Deepest number of plies to search
+    pub depth: Option<usize>,
+    /// Most positions to visit
+    pub nodes: Option<u64>,
+    /// Exact time to search
+    pub movetime: Option<Duration>,
+    /// Time left on the clock of white
+    pub wtime: Option<Duration>,
+    /// Time left on the clock of black
+    pub btime: Option<Duration>,
+    /// Increment of white for every move
+    pub winc: Duration,
+    /// Increment of black for every move
+    pub binc: Duration,
+    /// Moves to play until the next time control
+    pub movestogo: Option<u32>,
+    /// Search until the GUI sends stop
+    pub infinite: bool,
+    /// Search on the time of the opponent, until the GUI sends ponderhit or stop
+    pub ponder: bool,
+}
+
+impl GoOptions {
+    /// Reads the arguments of a go command, unknown arguments and values that are not numbers are left out
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use davbjor_chess::engine::uci::GoOptions;
+    ///
+    /// let go = GoOptions::parse(&["wtime", "60000", "btime", "50000", "winc", "1000", "ponder"]);
+    /// assert_eq!(go.wtime, Some(Duration::from_secs(60)));
+    /// assert_eq!(go.winc, Duration::from_secs(1));
+    /// assert_eq!(go.binc, Duration::ZERO);
+    /// assert!(go.ponder);
+    /// ```
+    ///
+    pub fn parse(args: &[&str]) -> GoOptions {
+        let mut go = GoOptions::default();
+        let millis = |ms: i64| Duration::from_millis(ms.max(0) as u64);
+
+        let mut i = 0;
+        while i < args.len() {
+            let value = args.get(i + 1).and_then(|value| value.parse::<i64>().ok());
+            match args[i] {
+                "infinite" => go.infinite = true,
+                "ponder" => go.ponder = true,
+                "depth" => go.depth = value.map(|depth| depth.max(1) as usize),
+                "nodes" => go.nodes = value.map(|nodes| nodes.max(1) as u64),
+                "movetime" => go.movetime = value.map(millis),
+                "wtime" => go.wtime = value.map(millis),
+                "btime" => go.btime = value.map(millis),
+                "winc" => go.winc = value.map(millis).unwrap_or_default(),
+                "binc" => go.binc = value.map(millis).unwrap_or_default(),
+                "movestogo" => go.movestogo = value.map(|moves| moves.clamp(1, u32::MAX as i64) as u32),
+                _ => ()
+            }
+            // The flags have no value
+            i += if matches!(args[i], "infinite" | "ponder") { 1 } else { 2 };
+        }
+
+        go
+    }
+
+    /// Limits of the search for the player to move (white if true)
+    ///
+    /// Without a movetime, the search uses a part of the time left on the clock and most of the increment,
+    /// the move overhead is kept for the GUI to receive the move. An infinite search has no limits
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use std::time::Duration;
+    /// use davbjor_chess::engine::uci::GoOptions;
+    ///
+    /// let go = GoOptions::parse(&["wtime", "30050", "winc", "400", "movestogo", "10"]);
+    /// let limits = go.limits(true, Duration::from_millis(50));
+    /// assert_eq!(limits.time, Some(Duration::from_millis(3300)));
+    /// ```
+    ///
+    pub fn limits(&self, white: bool, move_overhead: Duration) -> SearchLimits {
+        if self.infinite { return SearchLimits::default(); }
+
+        let mut limits = SearchLimits { depth: self.depth, nodes: self.nodes, time: self.movetime };
+        let (time, increment) = if white { (self.wtime, self.winc) } else { (self.btime, self.binc) };
+
+        if let (Some(time), None) = (time, limits.time) {
+            let available = time.saturating_sub(move_overhead);
+            let budget = available / self.movestogo.unwrap_or(DEFAULT_MOVES_TO_GO) + increment * 3 / 4;
+            limits.time = Some(budget.min(available).max(Duration::from_millis(1)));
+        }
+
+        limits
+    }
+}
+
+/// State of the engine between the commands of the GUI, the search runs on its own thread so the GUI can send stop
+pub struct Uci {
+    chess: ChessBoard,
+    // The engine is moved to the search thread while searching, and given back when the search ends
+    engine: Option<Engine>,
+    search: Option<JoinHandle<Engine>>,
+    stop: Arc<AtomicBool>,
+    ponder: Arc<AtomicBool>,
+    // Set by stop or ponderhit, an infinite or pondering search waits for it before sending bestmove
+    finish: Arc<AtomicBool>,
+    // Time kept for the GUI to receive the move
+    move_overhead: Duration,
+    output: Arc<dyn Fn(&str) + Send + Sync>,
+}
+
+impl Uci {
+    /// Creates the engine in the start position, every line for the GUI is passed to output
+    pub fn new<F: Fn(&str) + Send + Sync + 'static>(output: F) -> Uci {
+        let engine = Engine::new();
+        Uci {
+            chess: ChessBoard::new(),
+            stop: engine.stop_flag(),
+            ponder: engine.ponder_flag(),
+            engine: Some(engine),
+            search: None,
+            finish: Arc::new(AtomicBool::new(false)),
+            move_overhead: Duration::from_millis(DEFAULT_MOVE_OVERHEAD),
+            output: Arc::new(output),
+        }
+    }
+
+    /// Handles one line from the GUI, a command that can't be used is answered with an info string
+    ///
+    /// Returns false if the GUI sent quit, the running search should then be stopped with uci.stop_search()
+    pub fn command(&mut self, line: &str) -> bool {
+        let words: Vec<&str> = line.split_whitespace().collect();
+        let Some((command, args)) = words.split_first() else { return true; };
+
+        let result = match *command {
+            "uci" => {
+                self.send(&format!("id name {}", NAME));
+                self.send(&format!("id author {}", AUTHOR));
+                self.send(&format!("option name Hash type spin default {} min 1 max {}", DEFAULT_HASH_SIZE, MAX_HASH_SIZE));
+                self.send(&format!("option name Move Overhead type spin default {} min 0 max {}", DEFAULT_MOVE_OVERHEAD, MAX_MOVE_OVERHEAD));
+                self.send("uciok");
+                Ok(())
+            },
+            "isready" => {
+                self.send("readyok");
+                Ok(())
+            },
+            "ucinewgame" => {
+                self.stop_search();
+                self.chess = ChessBoard::new();
+                if let Some(engine) = self.engine.as_mut() { engine.clear_hash(); }
+                Ok(())
+            },
+            "position" => {
+                self.stop_search();
+                parse_position(args).map(|chess| self.chess = chess)
+            },
+            "go" => {
+                self.stop_search();
+                self.go(GoOptions::parse(args));
+                Ok(())
+            },
+            "stop" => {
+                self.stop_search();
+                Ok(())
+            },
+            "ponderhit" => {
+                self.ponderhit();
+                Ok(())
+            },
+            "setoption" => {
+                self.stop_search();
+                self.set_option(args)
+            },
+            "quit" => return false,
+            _ => Err(UciError::UnknownCommand(command.to_string()))
+        };
+
+        if let Err(e) = result {
+            self.send(&format!("info string {}", e));
+        }
+        true
+    }
+
+    /// Position set by the last position command (or the start position)
+    pub fn chess(&self) -> &ChessBoard {
+        &self.chess
+    }
+
+    /// Time kept for the GUI to receive the move, set by the Move Overhead option
+    pub fn move_overhead(&self) -> Duration {
+        self.move_overhead
+    }
+
+    /// Stops a running search and waits for it to send bestmove
+    pub fn stop_search(&mut self) {
+        if let Some(search) = self.search.take() {
+            // The flag is set until the search ends, as a search that has not started yet resets it
+            self.finish.store(true, Ordering::Relaxed);
+            self.ponder.store(false, Ordering::Relaxed);
+            while !search.is_finished() {
+                self.stop.store(true, Ordering::Relaxed);
+                thread::sleep(Duration::from_millis(1));
+            }
+            let engine = search.join().unwrap_or_default();
+            self.stop = engine.stop_flag();
+            self.ponder = engine.ponder_flag();
+            self.engine = Some(engine);
+        }
+    }
+
+    /*
+    Starts searching the position on another thread, bestmove is sent when the search ends
+    */
+    fn go(&mut self, go: GoOptions) {
+        let limits = go.limits(self.chess.whites_turn, self.move_overhead);
+
+        let Some(mut engine) = self.engine.take() else { return; };
+        let chess = self.chess.clone();
+        let output = Arc::clone(&self.output);
+        let finish = Arc::clone(&self.finish);
+        finish.store(!go.infinite && !go.ponder, Ordering::Relaxed);
+        self.ponder.store(go.ponder, Ordering::Relaxed);
+
+        self.search = Some(thread::spawn(move || {
+            let start = Instant::now();
+            let result = engine.search_with_info(&chess, limits, |info| output(&info_line(info, start.elapsed())));
+
+            // The GUI expects no bestmove for an infinite or pondering search until it sends stop or ponderhit
+            while !finish.load(Ordering::Relaxed) {
+                thread::sleep(Duration::from_millis(1));
+            }
+
+            match result.best_move {
+                Some(m) => output(&format!("bestmove {}", m.to_uci())),
+                None => output("bestmove 0000")
+            }
+            engine
+        }));
+    }
+
+    /*
+    The opponent made the move the engine was pondering on, the search goes on with the time limits of the go command
+    */
+    fn ponderhit(&mut self) {
+        if self.search.is_none() { return; }
+        self.ponder.store(false, Ordering::Relaxed);
+        self.finish.store(true, Ordering::Relaxed);
+    }
+
+    /*
+    setoption name <id> [value <x>]
+    */
+    fn set_option(&mut self, args: &[&str]) -> Result<(), UciError> {
+        let value_index = args.iter().position(|arg| *arg == "value").unwrap_or(args.len());
+        let name = args[..value_index].iter().skip_while(|arg| **arg == "name").copied().collect::<Vec<&str>>().join(" ");
+        let value = args.get(value_index + 1..).unwrap_or(&[]).join(" ");
+        let invalid = || UciError::InvalidOptionValue { name: name.clone(), value: value.clone() };
+
+        match name.to_lowercase().as_str() {
+            "hash" => match (value.parse::<usize>(), self.engine.as_mut()) {
+                (Ok(mb), Some(engine)) => engine.set_hash_size(mb.clamp(1, MAX_HASH_SIZE)),
+                _ => return Err(invalid())
+            },
+            "move overhead" => match value.parse::<u64>() {
+                Ok(ms) => self.move_overhead = Duration::from_millis(ms.min(MAX_MOVE_OVERHEAD)),
+                Err(_) => return Err(invalid())
+            },
+            _ => return Err(UciError::UnknownOption(name))
+        }
+
+        Ok(())
+    }
+
+    fn send(&self, message: &str) {
+        (self.output)(message);
+    }
+}
+
+fn info_line(info: &SearchResult, elapsed: Duration) -> String {
+    let ms = elapsed.as_millis() as u64;
+    let nps = info.nodes * 1000 / ms.max(1);
+    let pv: Vec<String> = info.pv.iter().map(|m| m.to_uci()).collect();
+    format!("info depth {} score {} nodes {} nps {} hashfull {} time {} pv {}", info.depth, info.score, info.nodes, nps, info.hashfull, ms, pv.join(" "))
+}
@@ -0,0 +1,473 @@
+//! Static evaluation of positions, in centipawns from white's point of view
+//!
+//! Every term is scored for both middlegame and endgame, and blended by how much material is left on the board (tapered evaluation)
+//!
+//! # Examples
+//!
+//! ```
+//! use davbjor_chess::ChessBoard;
+//! use davbjor_chess::eval::{evaluate, breakdown};
+//!
+//! let mut chess = ChessBoard::new();
+//! assert_eq!(evaluate(&chess), 0);
+//!
+//! // White is a rook up
+//! chess.load("1nbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQk - 0 1".to_string());
+//! let evaluation = breakdown(&chess);
+//! assert!(evaluation.material.score() > 400);
+//! assert_eq!(evaluation.total, evaluate(&chess));
+//! ```
+
+use std::fmt;
+
+use crate::ChessBoard;
+use crate::lookup::tables::{MASK_FILE, PIECE, KING_ATTACKS, PAWN_ATTACKS};
+use crate::compute::patterns::{squares, compute_knight_attacks, compute_bishop_attacks, compute_rook_attacks};
+
+type BitBoard = u64;
+
+/*
+Values of the pieces (pawn, knight, bishop, rook, queen, king) in the middlegame and endgame
+*/
+static MIDDLEGAME_VALUES: [i32; 6] = [82, 337, 365, 477, 1025, 0];
+static ENDGAME_VALUES: [i32; 6] = [94, 281, 297, 512, 936, 0];
+
+/*
+Phase of the game the pieces count for, 24 with every piece on the board (middlegame) and 0 with only pawns and kings (endgame)
+*/
+static PHASE_VALUES: [i32; 6] = [0, 1, 1, 2, 4, 0];
+const MAX_PHASE: i32 = 24;
+
+/*
+Piece-square tables, as seen from white with rank 8 at the top (index with square ^ 56 for white, and square for black)
+*/
+static MIDDLEGAME_TABLES: [[i32; 64]; 6] = [
+    // Pawn
+    [
+         0,  0,  0,  0,  0,  0,  0,  0,
+        50, 50, 50, 50, 50, 50, 50, 50,
+        10, 10, 20, 30, 30, 20, 10, 10,
+         5,  5, 10, 25, 25, 10,  5,  5,
+         0,  0,  0, 20, 20,  0,  0,  0,
+         5, -5,-10,  0,  0,-10, -5,  5,
+         5, 10, 10,-20,-20, 10, 10,  5,
+         0,  0,  0,  0,  0,  0,  0,  0,
+    ],
+    // Knight
+    [
+        -50,-40,-30,-30,-30,-30,-40,-50,
+        -40,-20,  0,  0,  0,  0,-20,-40,
+        -30,  0, 10, 15, 15, 10,  0,-30,
+        -30,  5, 15, 20, 20, 15,  5,-30,
+        -30,  0, 15, 20, 20, 15,  0,-30,
+        -30,  5, 10, 15, 15, 10,  5,-30,
+        -40,-20,  0,  5,  5,  0,-20,-40,
+        -50,-40,-30,-30,-30,-30,-40,-50,
+    ],
+    // Bishop
+    [
+        -20,-10,-10,-10,-10,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5, 10, 10,  5,  0,-10,
+        -10,  5,  5, 10, 10,  5,  5,-10,
+        -10,  0, 10, 10, 10, 10,  0,-10,
+        -10, 10, 10, 10, 10, 10, 10,-10,
+        -10,  5,  0,  0,  0,  0,  5,-10,
+        -20,-10,-10,-10,-10,-10,-10,-20,
+    ],
+    // Rook
+    [
+          0,  0,  0,  0,  0,  0,  0,  0,
+          5, 10, 10, 10, 10, 10, 10,  5,
+         -5,  0,  0,  0,  0,  0,  0, -5,
+         -5,  0,  0,  0,  0,  0,  0, -5,
+         -5,  0,  0,  0,  0,  0,  0, -5,
+         -5,  0,  0,  0,  0,  0,  0, -5,
+         -5,  0,  0,  0,  0,  0,  0, -5,
+          0,  0,  0,  5,  5,  0,  0,  0,
+    ],
+    // Queen
+    [
+        -20,-10,-10, -5, -5,-10,-10,-20,
+        -10,  0,  0,  0,  0,  0,  0,-10,
+        -10,  0,  5,  5,  5,  5,  0,-10,
+         -5,  0,  5,  5,  5,  5,  0, -5,
+          0,  0,  5,  5,  5,  5,  0, -5,
+        -10,  5,  5,  5,  5,  5,  0,-10,
+        -10,  0,  5,  0,  0,  0,  0,-10,
+        -20,-10,-10, -5, -5,-10,-10,-20,
+    ],
+    // King, stay safe behind the pawns
+    [
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -30,-40,-40,-50,-50,-40,-40,-30,
+        -20,-30,-30,-40,-40,-30,-30,-20,
+        -10,-20,-20,-20,-20,-20,-20,-10,
+         20, 20,  0,  0,  0,  0, 20, 20,
+         20, 30, 10,  0,  0, 10, 30, 20,
+    ],
+];
+
+static ENDGAME_TABLES: [[i32; 64]; 6] = [
+    // Pawn, closer to promotion
+    [
+         0,  0,  0,  0,  0,  0,  0,  0,
+        80, 80, 80, 80, 80, 80, 80, 80,
+        50, 50, 50, 50, 50, 50, 50, 50,
+        30, 30, 30, 30, 30, 30, 30, 30,
+        15, 15, 15, 15, 15, 15, 15, 15,
+         5,  5,  5,  5,  5,  5,  5,  5,
+         0,  0,  0,  0,  0,  0,  0,  0,
+         0,  0,  0,  0,  0,  0,  0,  0,
+    ],
+    MIDDLEGAME_TABLES[1],
+    MIDDLEGAME_TABLES[2],
+    MIDDLEGAME_TABLES[3],
+    MIDDLEGAME_TABLES[4],
+    // King, move to the center
+    [
+        -50,-40,-30,-20,-20,-30,-40,-50,
+        -30,-20,-10,  0,  0,-10,-20,-30,
+        -30,-10, 20, 30, 30, 20,-10,-30,
+        -30,-10, 30, 40, 40, 30,-10,-30,
+        -30,-10, 30, 40, 40, 30,-10,-30,
+        -30,-10, 20, 30, 30, 20,-10,-30,
+        -30,-30,  0,  0,  0,  0,-30,-30,
+        -50,-30,-30,-30,-30,-30,-30,-50,
+    ],
+];
+
+/*
+Mobility, score per square a piece (knight, bishop, rook, queen) can move to, counted from the usual number of squares
+*/
+static MOBILITY_WEIGHTS: [(i32, i32); 4] = [(4, 4), (5, 5), (2, 4), (1, 2)];
+static MOBILITY_BASE: [i32; 4] = [4, 6, 7, 13];
+
+/*
+Pawn structure
+*/
+static DOUBLED_PAWN: (i32, i32) = (-10, -20);
+static ISOLATED_PAWN: (i32, i32) = (-10, -15);
+static PASSED_PAWN: [(i32, i32); 8] = [(0, 0), (5, 10), (10, 20), (15, 35), (25, 60), (40, 90), (60, 130), (0, 0)];
+
+/*
+King safety (only counted in the middlegame)
+*/
+static PAWN_SHIELD: [i32; 2] = [12, 6];
+static OPEN_FILE_NEAR_KING: i32 = -15;
+static KING_ZONE_ATTACK_WEIGHTS: [i32; 4] = [2, 2, 3, 5];
+static KING_ZONE_ATTACK: i32 = -6;
+
+static BISHOP_PAIR: (i32, i32) = (30, 50);
+
+/// Score of one part of the evaluation for both sides (in centipawns)
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Term {
+    /// Points for white
+    pub white: i32,
+    /// Points for black
+    pub black: i32,
+}
+
+impl Term {
+    /// Score of the term from white's point of view (white - black)
+    pub fn score(&self) -> i32 {
+        self.white - self.black
+    }
+}
+
+/// Evaluation of a position split into its parts, every term already blended between middlegame and endgame
+///
+/// The total is the sum of the scores of every term, from white's point of view
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Evaluation {
+    /// Value of the pieces on the board
+    pub material: Term,
+    /// Value of the squares the pieces stand on
+    pub piece_squares: Term,
+    /// Number of squares the knights, bishops, rooks and queens can move to
+    pub mobility: Term,
+    /// Doubled, isolated and passed pawns
+    pub pawn_structure: Term,
+    /// Pawns in front of the king, open files next to it, and enemy pieces attacking the squares around it
+    pub king_safety: Term,
+    /// Bonus for having both bishops
+    pub bishop_pair: Term,
+    /// Phase of the game, from 24 (every piece on the board) to 0 (only pawns and kings)
+    pub phase: i32,
+    /// Score of the position from white's point of view
+    pub total: i32,
+}
+
+impl fmt::Display for Evaluation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        writeln!(f, "{:<16}{:>8}{:>8}{:>8}", "Term", "White", "Black", "Score")?;
+        for (name, term) in [
+            ("Material", self.material),
+            ("Piece squares", self.piece_squares),
+            ("Mobility", self.mobility),
+            ("Pawn structure", self.pawn_structure),
+            ("King safety", self.king_safety),
+            ("Bishop pair", self.bishop_pair),
+        ] {
+            writeln!(f, "{:<16}{:>8}{:>8}{:>8}", name, term.white, term.black, term.score())?;
+        }
+        write!(f, "{:<16}{:>8}{:>8}{:>8}", "Total", "", "", self.total)
+    }
+}
+
+/*
+Middlegame and endgame points of a term for one side
+*/
+#[derive(Clone, Copy, Default)]
+struct Points {
+    middlegame: i32,
+    endgame: i32,
+}
+
+impl Points {
+    fn add(&mut self, (middlegame, endgame): (i32, i32)) {
+        self.middlegame += middlegame;
+        self.endgame += endgame;
+    }
+
+    fn taper(&self, phase: i32) -> i32 {
+        (self.middlegame * phase + self.endgame * (MAX_PHASE - phase)) / MAX_PHASE
+    }
+}
+
+/*
+Pieces of one side, in the order of the evaluation tables
+*/
+struct Side {
+    pieces: [BitBoard; 6],
+    all: BitBoard,
+    white: bool,
+}
+
+/// Evaluates a position in centipawns, positive if white is better and negative if black is better
+///
+/// Only the pieces on the board are looked at, not whos turn it is or if the game has ended
+pub fn evaluate(chess: &ChessBoard) -> i32 {
+    breakdown(chess).total
+}
+
+/// Evaluates a position like evaluate, and returns the score of every term for both sides
+///
+/// # Examples
+///
+/// ```
+/// use davbjor_chess::ChessBoard;
+/// use davbjor_chess::eval::breakdown;
+///
+/// let mut chess = ChessBoard::new();
+///
+/// // White has both bishops, black has a passed pawn on d4
+/// chess.load("4k3/2n5/8/8/3p4/8/5BB1/4K3 w - - 0 1".to_string());
+/// let evaluation = breakdown(&chess);
+///
+/// assert!(evaluation.bishop_pair.white > 0);
+/// assert_eq!(evaluation.bishop_pair.black, 0);
+/// assert!(evaluation.pawn_structure.black > 0);
+/// println!("{}", evaluation);
+/// ```
+///
+pub fn breakdown(chess: &ChessBoard) -> Evaluation {
+    let white = Side {
+        pieces: [chess.white_pawns, chess.white_knights, chess.white_bishops, chess.white_rooks, chess.white_queens, chess.white_kings],
+        all: chess.white_pieces,
+        white: true,
+    };
+    let black = Side {
+        pieces: [chess.black_pawns, chess.black_knights, chess.black_bishops, chess.black_rooks, chess.black_queens, chess.black_kings],
+        all: chess.black_pieces,
+        white: false,
+    };
+
+    let mut phase = 0;
+    for (kind, value) in PHASE_VALUES.iter().enumerate() {
+        phase += value * (white.pieces[kind].count_ones() + black.pieces[kind].count_ones()) as i32;
+    }
+    let phase = phase.min(MAX_PHASE);
+
+    let term = |score: fn(&Side, &Side) -> Points| Term {
+        white: score(&white, &black).taper(phase),
+        black: score(&black, &white).taper(phase),
+    };
+
+    let mut evaluation = Evaluation {
+        material: term(material),
+        piece_squares: term(piece_squares),
+        mobility: term(mobility),
+        pawn_structure: term(pawn_structure),
+        king_safety: term(king_safety),
+        bishop_pair: term(bishop_pair),
+        phase,
+        total: 0,
+    };
+
+    evaluation.total = evaluation.material.score()
+        + evaluation.piece_squares.score()
+        + evaluation.mobility.score()
+        + evaluation.pawn_structure.score()
+        + evaluation.king_safety.score()
+        + evaluation.bishop_pair.score();
+
+    evaluation
+}
+
+/*
+Rank of a square as seen from the side (0 is the own back rank)
+*/
+fn relative_rank(square: usize, white: bool) -> usize {
+    if white { square / 8 } else { 7 - square / 8 }
+}
+
+fn material(side: &Side, _enemy: &Side) -> Points {
+    let mut points = Points::default();
+    for kind in 0..6 {
+        let count = side.pieces[kind].count_ones() as i32;
+        points.add((MIDDLEGAME_VALUES[kind] * count, ENDGAME_VALUES[kind] * count));
+    }
+    points
+}
+
+fn piece_squares(side: &Side, _enemy: &Side) -> Points {
+    let mut points = Points::default();
+    for kind in 0..6 {
+        for square in squares(side.pieces[kind]) {
+            let index = if side.white { square ^ 56 } else { square };
+            points.add((MIDDLEGAME_TABLES[kind][index], ENDGAME_TABLES[kind][index]));
+        }
+    }
+    points
+}
+
+fn mobility(side: &Side, enemy: &Side) -> Points {
+    let mut points = Points::default();
+    let all_pieces = side.all | enemy.all;
+
+    // Squares attacked by enemy pawns are not counted
+    let enemy_color = if enemy.white { 0 } else { 1 };
+    let mut enemy_pawn_attacks: BitBoard = 0;
+    for square in squares(enemy.pieces[0]) {
+        enemy_pawn_attacks |= PAWN_ATTACKS[enemy_color][square];
+    }
+
+    for kind in 1..5 {
+        for square in squares(side.pieces[kind]) {
+            let piece = PIECE[square];
+            let moves = match kind {
+                1 => compute_knight_attacks(piece, side.all),
+                2 => compute_bishop_attacks(piece, all_pieces, enemy.all),
+                3 => compute_rook_attacks(piece, all_pieces, enemy.all),
+                _ => compute_bishop_attacks(piece, all_pieces, enemy.all) | compute_rook_attacks(piece, all_pieces, enemy.all)
+            };
+            let count = (moves & !enemy_pawn_attacks).count_ones() as i32 - MOBILITY_BASE[kind - 1];
+            let (middlegame, endgame) = MOBILITY_WEIGHTS[kind - 1];
+            points.add((middlegame * count, endgame * count));
+        }
+    }
+    points
+}
+
+fn pawn_structure(side: &Side, enemy: &Side) -> Points {
+    let mut points = Points::default();
+    let pawns = side.pieces[0];
+
+    for file in 0..8 {
+        let on_file = (pawns & MASK_FILE[file]).count_ones() as i32;
+        if on_file == 0 { continue; }
+
+        // Every pawn after the first on a file is doubled
+        if on_file > 1 {
+            points.add((DOUBLED_PAWN.0 * (on_file - 1), DOUBLED_PAWN.1 * (on_file - 1)));
+        }
+
+        // No own pawns on the files next to it
+        let mut neighbours: BitBoard = 0;
+        if file > 0 { neighbours |= MASK_FILE[file - 1]; }
+        if file < 7 { neighbours |= MASK_FILE[file + 1]; }
+        if pawns & neighbours == 0 {
+            points.add((ISOLATED_PAWN.0 * on_file, ISOLATED_PAWN.1 * on_file));
+        }
+    }
+
+    // No enemy pawns in front of it on the same or the files next to it
+    for square in squares(pawns) {
+        let file = square % 8;
+        let mut files = MASK_FILE[file];
+        if file > 0 { files |= MASK_FILE[file - 1]; }
+        if file < 7 { files |= MASK_FILE[file + 1]; }
+
+        let in_front = if side.white {
+            files & (!0 as BitBoard).checked_shl((square / 8 + 1) as u32 * 8).unwrap_or(0)
+        } else {
+            files & ((1 as BitBoard) << (square / 8 * 8)).wrapping_sub(1)
+        };
+
+        if enemy.pieces[0] & in_front == 0 {
+            points.add(PASSED_PAWN[relative_rank(square, side.white)]);
+        }
+    }
+
+    points
+}
+
+fn king_safety(side: &Side, enemy: &Side) -> Points {
+    let mut points = Points::default();
+    let king_square = match squares(side.pieces[5]).next() {
+        Some(square) => square,
+        None => return points
+    };
+    let file = king_square % 8;
+    let rank = relative_rank(king_square, side.white);
+    let pawns = side.pieces[0];
+
+    // Pawns in front of a king on the first two ranks, and files with no pawns next to it
+    if rank <= 1 {
+        let (first, last) = (file.saturating_sub(1), (file + 1).min(7));
+        for (f, file_mask) in MASK_FILE.iter().enumerate().take(last + 1).skip(first) {
+            for (distance, bonus) in PAWN_SHIELD.iter().enumerate() {
+                let shield_rank = rank + distance + 1;
+                let shield_square = if side.white { shield_rank * 8 + f } else { (7 - shield_rank) * 8 + f };
+                if pawns & PIECE[shield_square] != 0 {
+                    points.add((*bonus, 0));
+                }
+            }
+            if pawns & file_mask == 0 {
+                points.add((OPEN_FILE_NEAR_KING, 0));
+            }
+        }
+    }
+
+    // Enemy pieces attacking the squares around the king
+    let zone = KING_ATTACKS[king_square] | PIECE[king_square];
+    let all_pieces = side.all | enemy.all;
+    let mut attack_units = 0;
+    for kind in 1..5 {
+        for square in squares(enemy.pieces[kind]) {
+            let piece = PIECE[square];
+            let attacks = match kind {
+                1 => compute_knight_attacks(piece, 0),
+                2 => compute_bishop_attacks(piece, all_pieces, all_pieces),
+                3 => compute_rook_attacks(piece, all_pieces, all_pieces),
+                _ => compute_bishop_attacks(piece, all_pieces, all_pieces) | compute_rook_attacks(piece, all_pieces, all_pieces)
+            };
+            attack_units += KING_ZONE_ATTACK_WEIGHTS[kind - 1] * (attacks & zone).count_ones() as i32;
+        }
+    }
+    points.add((KING_ZONE_ATTACK * attack_units, 0));
+
+    points
+}
+
+fn bishop_pair(side: &Side, _enemy: &Side) -> Points {
+    let mut points = Points::default();
+    if side.pieces[2].count_ones() >= 2 {
+        points.add(BISHOP_PAIR);
+    }
+    points
+}
@@ -1,11 +1,22 @@
 mod lookup;
 mod compute;
-
-#[warn(missing_docs)]
-#[allow(unused_imports)]
-use crate::lookup::tables::{MASK_RANK, CLEAR_RANK, MASK_FILE, CLEAR_FILE, PIECE, SQUARE, string_to_square};
+mod moves;
+mod san;
+mod perft;
+mod see;
+pub mod pgn;
+pub mod eval;
+pub mod engine;
+pub mod clock;
+
+pub use crate::moves::Move;
+pub use crate::lookup::tables::{string_to_square, square_to_string};
+
+use crate::lookup::tables::{MASK_RANK, PIECE, Square, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, BETWEEN, LINE, ZOBRIST, DARK_SQUARES, bishop_attacks, rook_attacks};
 use crate::compute::patterns::{
     bit_count,
+    bit_scan,
+    squares,
     compute_king_attacks, 
     compute_knight_attacks, 
     compute_white_pawn_attacks,
@@ -48,8 +59,8 @@ Some parts of this code is really ugly :(
 *
 * FEN data
 * D     Import FEN
-* -         Detect if FEN is allowed as a chess-game
-* -     Export FEN
+* D         Detect if FEN is allowed as a chess-game
+* D     Export FEN
 * D     Player turn,
 * D     En passant target square (If a pawn moves 2 places -> store the square behind it)
 * D     Castling rights (king-side, queen-side, black, white)
@@ -77,6 +88,11 @@ Some parts of this code is really ugly :(
 */
 
 
+use std::fmt;
+use std::time::Duration;
+
+use crate::clock::{Clock, ClockState, TimeControl};
+
 type BitBoard = u64;
 
 
@@ -99,7 +115,7 @@ type BitBoard = u64;
 /// }
 /// 
 /// ```
-#[derive(Debug, PartialEq, Copy, Clone)]
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
 pub enum PieceType {
     WhitePawn,
     WhiteKnight,
@@ -137,15 +153,7 @@ impl PieceType {
     /// ```
     /// 
     pub fn is_white(&self) -> bool {
-        return match self {
-            PieceType::WhitePawn => true,
-            PieceType::WhiteKnight => true,
-            PieceType::WhiteBishop => true,
-            PieceType::WhiteRook => true,
-            PieceType::WhiteQueen => true,
-            PieceType::WhiteKing => true,
-            _ => false
-        }
+        matches!(self, PieceType::WhitePawn | PieceType::WhiteKnight | PieceType::WhiteBishop | PieceType::WhiteRook | PieceType::WhiteQueen | PieceType::WhiteKing)
     }
     /// Checks if the piece is a king
     /// 
@@ -167,11 +175,7 @@ impl PieceType {
     /// ```
     /// 
     pub fn is_king(&self) -> bool {
-        return match self {
-            PieceType::WhiteKing => true,
-            PieceType::BlackKing => true,
-            _ => false
-        }
+        matches!(self, PieceType::WhiteKing | PieceType::BlackKing)
     }
     /// Checks if the piece is a pawn
     /// 
@@ -193,11 +197,7 @@ impl PieceType {
     /// ```
     /// 
     pub fn is_pawn(&self) -> bool {
-        return match self {
-            PieceType::WhitePawn => true,
-            PieceType::BlackPawn => true,
-            _ => false
-        }
+        matches!(self, PieceType::WhitePawn | PieceType::BlackPawn)
     }
 }
 
@@ -231,6 +231,344 @@ pub enum GameResult {
     Black
 }
 
+/// Enum Termination contains the reason a game ended, stored in chess.termination next to chess.game_result
+/// 
+/// # Examples
+/// 
+/// ```
+/// use davbjor_chess::{ChessBoard, GameResult, Termination};
+/// 
+/// let mut chess = ChessBoard::new();
+/// assert_eq!(chess.termination, None);
+/// 
+/// // Fool's mate
+/// chess.apply_uci_moves(&["f2f3", "e7e5", "g2g4", "d8h4"]).unwrap();
+/// assert_eq!(chess.game_result, GameResult::Black);
+/// assert_eq!(chess.termination, Some(Termination::Checkmate));
+/// println!("Black won by {}", chess.termination.unwrap());
+/// ```
+#[derive(Debug, PartialEq, Eq, Copy, Clone)]
+pub enum Termination {
+    /// The player whos turn it is is checkmated
+    Checkmate,
+    /// The player whos turn it is has no legal moves, but is not in check
+    Stalemate,
+    /// The same position has appeared three times
+    ThreefoldRepetition,
+    /// The same position has appeared five times
+    FivefoldRepetition,
+    /// 50 moves by each player without a capture or pawn move
+    FiftyMoveRule,
+    /// 75 moves by each player without a capture or pawn move
+    SeventyFiveMoveRule,
+    /// Neither player has enough pieces left to checkmate
+    InsufficientMaterial,
+    /// A player surrendered
+    Resignation,
+    /// A player ran out of time
+    Timeout,
+    /// Both players agreed to a draw
+    Agreement,
+    /// The game was left unfinished
+    Abandoned
+}
+
+impl fmt::Display for Termination {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let s = match self {
+            Termination::Checkmate => "checkmate",
+            Termination::Stalemate => "stalemate",
+            Termination::ThreefoldRepetition => "threefold repetition",
+            Termination::FivefoldRepetition => "fivefold repetition",
+            Termination::FiftyMoveRule => "fifty-move rule",
+            Termination::SeventyFiveMoveRule => "seventy-five-move rule",
+            Termination::InsufficientMaterial => "insufficient material",
+            Termination::Resignation => "resignation",
+            Termination::Timeout => "timeout",
+            Termination::Agreement => "agreement",
+            Termination::Abandoned => "abandoned"
+        };
+        write!(f, "{}", s)
+    }
+}
+
+/// Enum DrawPolicy chooses how draws by repetition and by the number of moves without a capture or pawn move are handled
+/// 
+/// DrawPolicy::Fide (the default) follows the FIDE rules: a player can claim a draw (chess.claim_draw()) at threefold repetition or after 50 moves,
+/// and the game ends automatically at fivefold repetition or after 75 moves
+/// 
+/// DrawPolicy::AutoDraw ends the game automatically at threefold repetition or after 50 moves (like most online servers)
+/// 
+/// DrawPolicy::None never ends the game automatically, a draw can still be claimed
+/// 
+/// # Examples
+/// 
+/// ```
+/// use davbjor_chess::{ChessBoard, DrawPolicy, GameResult, Termination};
+/// 
+/// let mut chess = ChessBoard::new();
+/// 
+/// // Move the knights out and back twice
+/// chess.apply_uci_moves(&["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]).unwrap();
+/// 
+/// // The position has appeared three times, the draw has to be claimed
+/// assert_eq!(chess.game_result, GameResult::Ongoing);
+/// assert!(chess.can_claim_draw());
+/// 
+/// // Ends automatically with DrawPolicy::AutoDraw
+/// let mut chess = ChessBoard::new();
+/// chess.draw_policy = DrawPolicy::AutoDraw;
+/// chess.apply_uci_moves(&["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]).unwrap();
+/// assert_eq!(chess.termination, Some(Termination::ThreefoldRepetition));
+/// ```
+#[derive(Debug, PartialEq, Eq, Copy, Clone, Default)]
+pub enum DrawPolicy {
+    #[default]
+    Fide,
+    AutoDraw,
+    None
+}
+
+/// Enum FenField names the six fields of a FEN-string
+#[derive(Debug, PartialEq, Copy, Clone)]
+pub enum FenField {
+    Placement,
+    PlayerTurn,
+    CastlingRights,
+    EnPassant,
+    HalfmoveClock,
+    FullmoveCount
+}
+
+/// Enum FenError contains the reasons a FEN-string can be rejected by ChessBoard::from_fen
+/// 
+/// Every error stores the index (counted in characters from the start of the FEN-string) where the problem was found
+/// 
+/// # Examples
+/// 
+/// ```
+/// use davbjor_chess::{ChessBoard, FenError, FenField};
+/// 
+/// match ChessBoard::from_fen("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR x KQkq - 0 1") {
+///     Ok(_) => (),
+///     Err(e) => {
+///         assert_eq!(e, FenError::InvalidPlayerTurn { index: 44 });
+///         assert_eq!(e.field(), Some(FenField::PlayerTurn));
+///         println!("Error: {e}");
+///     }
+/// }
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub enum FenError {
+    /// The FEN-string ended before the field was found
+    MissingField { field: FenField, index: usize },
+    /// The FEN-string has more than six fields
+    TooManyFields { index: usize },
+    /// The piece placement does not contain exactly 8 ranks
+    WrongRankCount { found: usize, index: usize },
+    /// A rank of the piece placement does not contain exactly 8 files
+    WrongFileCount { found: usize, index: usize },
+    /// A character in the piece placement is neither a piece letter or a digit 1-8
+    InvalidPiece { character: char, index: usize },
+    /// The player turn is not 'w' or 'b'
+    InvalidPlayerTurn { index: usize },
+    /// The castling rights contain something else than '-' or the letters K, Q, k, q (each at most once)
+    InvalidCastlingRights { character: char, index: usize },
+    /// The en passant square is not '-' or a square on rank 3 or 6
+    InvalidEnPassant { index: usize },
+    /// The halfmove clock is not a non-negative number
+    InvalidHalfmoveClock { index: usize },
+    /// The fullmove count is not a positive number
+    InvalidFullmoveCount { index: usize },
+    /// The FEN-string could be read but the position breaks the rules of chess (only given by ChessBoard::from_fen_legal)
+    IllegalPosition(Vec<PositionError>),
+}
+
+impl FenError {
+    /// Gives the field of the FEN-string that was wrong
+    /// 
+    /// Returns None if every field was right but there is more text after the last field
+    pub fn field(&self) -> Option<FenField> {
+        match self {
+            FenError::MissingField { field, .. } => Some(*field),
+            FenError::TooManyFields { .. } => None,
+            FenError::WrongRankCount { .. } => Some(FenField::Placement),
+            FenError::WrongFileCount { .. } => Some(FenField::Placement),
+            FenError::InvalidPiece { .. } => Some(FenField::Placement),
+            FenError::InvalidPlayerTurn { .. } => Some(FenField::PlayerTurn),
+            FenError::InvalidCastlingRights { .. } => Some(FenField::CastlingRights),
+            FenError::InvalidEnPassant { .. } => Some(FenField::EnPassant),
+            FenError::InvalidHalfmoveClock { .. } => Some(FenField::HalfmoveClock),
+            FenError::InvalidFullmoveCount { .. } => Some(FenField::FullmoveCount),
+            FenError::IllegalPosition(_) => Some(FenField::Placement),
+        }
+    }
+
+    /// Gives the index of the character (counted from the start of the FEN-string) where the error was found
+    pub fn index(&self) -> usize {
+        match self {
+            FenError::MissingField { index, .. } => *index,
+            FenError::TooManyFields { index } => *index,
+            FenError::WrongRankCount { index, .. } => *index,
+            FenError::WrongFileCount { index, .. } => *index,
+            FenError::InvalidPiece { index, .. } => *index,
+            FenError::InvalidPlayerTurn { index } => *index,
+            FenError::InvalidCastlingRights { index, .. } => *index,
+            FenError::InvalidEnPassant { index } => *index,
+            FenError::InvalidHalfmoveClock { index } => *index,
+            FenError::InvalidFullmoveCount { index } => *index,
+            FenError::IllegalPosition(_) => 0,
+        }
+    }
+}
+
+impl fmt::Display for FenError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            FenError::MissingField { field, index } => write!(f, "Missing field {:?} at character {}", field, index),
+            FenError::TooManyFields { index } => write!(f, "Too many fields, unexpected field at character {}", index),
+            FenError::WrongRankCount { found, index } => write!(f, "Expected 8 ranks but found {} at character {}", found, index),
+            FenError::WrongFileCount { found, index } => write!(f, "Expected 8 files in rank but found {} at character {}", found, index),
+            FenError::InvalidPiece { character, index } => write!(f, "Invalid piece '{}' at character {}", character, index),
+            FenError::InvalidPlayerTurn { index } => write!(f, "Invalid player turn at character {}", index),
+            FenError::InvalidCastlingRights { character, index } => write!(f, "Invalid castling rights '{}' at character {}", character, index),
+            FenError::InvalidEnPassant { index } => write!(f, "Invalid en passant square at character {}", index),
+            FenError::InvalidHalfmoveClock { index } => write!(f, "Invalid halfmove clock at character {}", index),
+            FenError::InvalidFullmoveCount { index } => write!(f, "Invalid fullmove count at character {}", index),
+            FenError::IllegalPosition(errors) => {
+                write!(f, "Illegal position:")?;
+                for e in errors {
+                    write!(f, " {};", e)?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl std::error::Error for FenError {}
+
+/// Enum PositionError contains the ways a position can break the rules of chess
+/// 
+/// Returned as a list by ChessBoard::validate
+#[derive(Debug, PartialEq, Clone)]
+pub enum PositionError {
+    /// White has no king
+    MissingWhiteKing,
+    /// Black has no king
+    MissingBlackKing,
+    /// White has more than one king
+    TooManyWhiteKings,
+    /// Black has more than one king
+    TooManyBlackKings,
+    /// White has more than 8 pawns
+    TooManyWhitePawns,
+    /// Black has more than 8 pawns
+    TooManyBlackPawns,
+    /// White has more than 16 pieces
+    TooManyWhitePieces,
+    /// Black has more than 16 pieces
+    TooManyBlackPieces,
+    /// A pawn is on the first or last rank (square 0-63)
+    PawnOnBackRank { square: usize },
+    /// The player who is not in turn is in check (the king could be captured)
+    OpponentInCheck,
+    /// The en passant square is not behind a pawn that just moved two squares (square 0-63)
+    InvalidEnPassant { square: usize },
+}
+
+impl fmt::Display for PositionError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PositionError::MissingWhiteKing => write!(f, "White has no king"),
+            PositionError::MissingBlackKing => write!(f, "Black has no king"),
+            PositionError::TooManyWhiteKings => write!(f, "White has more than one king"),
+            PositionError::TooManyBlackKings => write!(f, "Black has more than one king"),
+            PositionError::TooManyWhitePawns => write!(f, "White has more than 8 pawns"),
+            PositionError::TooManyBlackPawns => write!(f, "Black has more than 8 pawns"),
+            PositionError::TooManyWhitePieces => write!(f, "White has more than 16 pieces"),
+            PositionError::TooManyBlackPieces => write!(f, "Black has more than 16 pieces"),
+            PositionError::PawnOnBackRank { square } => write!(f, "Pawn on back rank at {}", square_to_string(*square)),
+            PositionError::OpponentInCheck => write!(f, "The player not in turn is in check"),
+            PositionError::InvalidEnPassant { square } => write!(f, "No pawn can be captured en passant at {}", square_to_string(*square)),
+        }
+    }
+}
+
+impl std::error::Error for PositionError {}
+
+/// Enum MoveError contains the reasons a move can't be made or read
+/// 
+/// Returned by chess.move_piece, chess.handle_promotion, chess.make_move, chess.parse_uci, chess.parse_san and the other methods making moves
+/// 
+/// # Examples
+/// 
+/// ```
+/// use davbjor_chess::{ChessBoard, MoveError};
+/// 
+/// let mut chess = ChessBoard::new();
+/// 
+/// // E2 -> E5
+/// match chess.move_piece(12, 36) {
+///     Ok(_) => (),
+///     Err(e) => {
+///         assert_eq!(e, MoveError::IllegalDestination { from: 12, to: 36 });
+///         println!("Error: {e}");
+///     }
+/// }
+/// 
+/// // E7 -> E5 on whites turn
+/// assert_eq!(chess.move_piece(52, 36), Err(MoveError::WrongSideToMove { square: 52 }));
+/// ```
+#[derive(Debug, PartialEq, Clone)]
+pub enum MoveError {
+    /// The game has finished, no more moves can be made
+    GameOver,
+    /// A square is not on the board (squares are 0-63)
+    SquareOutOfRange { square: usize },
+    /// There is no piece on the square to move from
+    NoPieceOnSquare { square: usize },
+    /// The piece on the square belongs to the player not in turn
+    WrongSideToMove { square: usize },
+    /// The piece on the square has no legal moves
+    NoLegalMoves { square: usize },
+    /// The piece can't legally move to the square
+    IllegalDestination { from: usize, to: usize },
+    /// A pawn moving to the last rank needs a piece to promote to
+    PromotionRequired { from: usize, to: usize },
+    /// The piece can't be promoted to (a king, a pawn, the wrong color, or the move is not a promotion)
+    InvalidPromotionPiece { piece: PieceType },
+    /// The text of a move (UCI or SAN) could not be read
+    InvalidNotation(String),
+    /// No legal move matches the text of a move
+    NoMatchingMove(String),
+    /// More than one legal move matches the text of a move
+    AmbiguousMove(String),
+    /// The position has not appeared three times, and there have been less than 50 moves without a capture or pawn move
+    NoDrawToClaim,
+}
+
+impl fmt::Display for MoveError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MoveError::GameOver => write!(f, "Game is finished"),
+            MoveError::SquareOutOfRange { square } => write!(f, "Square {} is not on the board", square),
+            MoveError::NoPieceOnSquare { square } => write!(f, "No piece at {}", square_to_string(*square)),
+            MoveError::WrongSideToMove { square } => write!(f, "Piece at {} can't move on the other player's turn", square_to_string(*square)),
+            MoveError::NoLegalMoves { square } => write!(f, "Piece at {} can't move at all", square_to_string(*square)),
+            MoveError::IllegalDestination { from, to } => write!(f, "Piece at {} can't move to {}", square_to_string(*from), square_to_string(*to)),
+            MoveError::PromotionRequired { from, to } => write!(f, "Move {}{} needs a piece to promote to", square_to_string(*from), square_to_string(*to)),
+            MoveError::InvalidPromotionPiece { piece } => write!(f, "Can't promote to {:?}", piece),
+            MoveError::InvalidNotation(text) => write!(f, "Can't read the move {}", text),
+            MoveError::NoMatchingMove(text) => write!(f, "{} is not a legal move", text),
+            MoveError::AmbiguousMove(text) => write!(f, "{} is ambiguous", text),
+            MoveError::NoDrawToClaim => write!(f, "No draw to claim, the position has not appeared three times and there have been less than 50 moves without a capture or pawn move"),
+        }
+    }
+}
+
+impl std::error::Error for MoveError {}
+
 /// Contains the chessgame and can be altered by it's methods
 /// 
 /// Stores a chessboard, indexed from down-left -> right -> up
@@ -268,6 +606,12 @@ pub struct ChessBoard {
     pub whites_turn: bool,
     /// State of the game (stored as the enum GameResult)
     pub game_result: GameResult,
+    /// Reason the game ended (None while the game is ongoing)
+    pub termination: Option<Termination>,
+    /// How draws by repetition and the fifty-move rule are handled (kept when a new position is loaded)
+    pub draw_policy: DrawPolicy,
+    /// Clock of a timed game (None if the game is not timed), removed when a new position is loaded, taking back a move (unmake_move, undo) restores the clock as it was before the move and redo as it was when the move was undone
+    pub clock: Option<Clock>,
     /// Stores the castling_rights of both players (K Q k q) (whites-kingside, whites queenside, blacks kingside, blacks queenside)
     pub castling_rights: (bool, bool, bool, bool),
     /// Moves (counting every move) since last capture/pawn move (useful for calculating 50-move rule)
@@ -283,8 +627,50 @@ pub struct ChessBoard {
     promotion_piece: PieceType,
     // Square of possible en passant
     en_passant_square: BitBoard,
-    // Stores the previous positions
-    positions: Vec<Vec<BitBoard>>,
+    // Zobrist hash of the position, updated with every change to the board
+    hash: u64,
+    // Stores the hashes of the previous positions
+    positions: Vec<u64>,
+    // Stores the state of the game before every move, to be able to undo moves
+    history: Vec<MoveRecord>,
+    // Stores moves that were undone, and the clock after them, to be able to redo them
+    redo_stack: Vec<(Move, Option<ClockState>)>,
+}
+
+/*
+State of the game before a move was made, restored when the move is undone
+*/
+#[derive(Debug, Clone)]
+struct MoveRecord {
+    m: Move,
+    pieces: [BitBoard; 12],
+    whites_turn: bool,
+    game_result: GameResult,
+    termination: Option<Termination>,
+    castling_rights: (bool, bool, bool, bool),
+    halfmove_clock: i32,
+    fullmove: i32,
+    player_in_check: bool,
+    en_passant_square: BitBoard,
+    hash: u64,
+    positions_len: usize,
+    clock: Option<ClockState>,
+}
+
+/*
+What limits the moves of one side in a position, computed once and used for every piece
+*/
+struct Legality {
+    // Square of the king (64 if there is no king)
+    king_square: usize,
+    // Enemy pieces giving check
+    checkers: BitBoard,
+    // Squares a piece (not the king) must move to, to block or capture a checking piece (every square if not in check)
+    check_mask: BitBoard,
+    // Own pieces pinned to the king, they can only move along the line of the pin
+    pinned: BitBoard,
+    // Squares attacked by the enemy, with the king removed so it can't step back along a checking ray
+    king_danger: BitBoard,
 }
 
 impl Default for ChessBoard {
@@ -314,6 +700,9 @@ impl Default for ChessBoard {
             /* Game Info */
             whites_turn: true,
             game_result: GameResult::Ongoing,
+            termination: None,
+            draw_policy: DrawPolicy::Fide,
+            clock: None,
             castling_rights: (true, true, true, true),
             halfmove_clock: 0,
             fullmove: 1,
@@ -322,14 +711,17 @@ impl Default for ChessBoard {
             
             promotion_piece: PieceType::Empty,
             en_passant_square: 0,
+            hash: ZOBRIST.castling[15],
             positions: vec![],
+            history: vec![],
+            redo_stack: vec![],
         }
     }
 }
 
 impl ChessBoard {
     pub fn new () -> Self {
-        ChessBoard {
+        let mut chess = ChessBoard {
             /* All White Pieces */
             white_pawns: MASK_RANK[1],
             white_knights: PIECE[1] | PIECE[6],
@@ -342,7 +734,7 @@ impl ChessBoard {
             black_pawns: MASK_RANK[6],
             black_knights: PIECE[7*8+1] | PIECE[7*8+6],
             black_bishops: PIECE[7*8+2] | PIECE[7*8+5],
-            black_rooks: PIECE[7*8+0] | PIECE[7*8+7],
+            black_rooks: PIECE[7*8 ] | PIECE[7*8+7],
             black_queens: PIECE[7*8+3],
             black_kings: PIECE[7*8+4],
         
@@ -354,6 +746,9 @@ impl ChessBoard {
             /* Game Info */
             whites_turn: true,
             game_result: GameResult::Ongoing,
+            termination: None,
+            draw_policy: DrawPolicy::Fide,
+            clock: None,
             castling_rights: (true, true, true, true),
             halfmove_clock: 0,
             fullmove: 1,
@@ -427,8 +822,14 @@ impl ChessBoard {
             
             promotion_piece: PieceType::Empty,
             en_passant_square: 0,
+            hash: 0,
             positions: vec![],
-        }
+            history: vec![],
+            redo_stack: vec![],
+        };
+        chess.hash = chess.compute_hash();
+        chess.store_position();
+        chess
     }
     /// Reset entire board to a blank state
     /// 
@@ -472,6 +873,8 @@ impl ChessBoard {
         /* Game Info */
         self.whites_turn = true;
         self.game_result = GameResult::Ongoing;
+        self.termination = None;
+        self.clock = None;
         self.castling_rights = (true, true, true, true);
         self.halfmove_clock = 0;
         self.fullmove = 1;
@@ -480,7 +883,10 @@ impl ChessBoard {
         self.board = vec![PieceType::Empty;64];
         
         self.en_passant_square = 0;
+        self.hash = self.compute_hash();
         self.positions = Vec::new();
+        self.history = Vec::new();
+        self.redo_stack = Vec::new();
     }
 
 
@@ -497,20 +903,21 @@ impl ChessBoard {
         let all_pieces = black_pieces | white_pieces;
         
         let mut attacks: BitBoard = compute_white_pawn_attacks(white_pieces & self.white_pawns, black_pieces | self.en_passant_square)
-                | compute_knight_attacks(white_pieces & self.white_knights, self.white_pieces)
+                | compute_knight_attacks(white_pieces & self.white_knights, white_pieces)
                 | compute_king_attacks(white_pieces & self.white_kings, white_pieces);
 
-        for i in 0..64 {
-            if white_pieces & self.white_bishops & PIECE[i] != 0{
-                attacks |= compute_bishop_attacks(PIECE[i], all_pieces, black_pieces);
-            }
-            if white_pieces & self.white_rooks & PIECE[i] != 0 {
-                attacks |= compute_rook_attacks(PIECE[i], all_pieces, black_pieces);
-            }
-            if white_pieces & self.white_queens & PIECE[i] != 0{
-                attacks |= compute_bishop_attacks(PIECE[i], all_pieces, black_pieces);
-                attacks |= compute_rook_attacks(PIECE[i], all_pieces, black_pieces);
-            }
+        // Loop through the sliding pieces only, removing the lowest one each time
+        let mut diagonal_sliders = white_pieces & (self.white_bishops | self.white_queens);
+        while diagonal_sliders != 0 {
+            let square = diagonal_sliders & diagonal_sliders.wrapping_neg();
+            attacks |= compute_bishop_attacks(square, all_pieces, black_pieces);
+            diagonal_sliders &= !square;
+        }
+        let mut straight_sliders = white_pieces & (self.white_rooks | self.white_queens);
+        while straight_sliders != 0 {
+            let square = straight_sliders & straight_sliders.wrapping_neg();
+            attacks |= compute_rook_attacks(square, all_pieces, black_pieces);
+            straight_sliders &= !square;
         }
 
         attacks
@@ -532,17 +939,18 @@ impl ChessBoard {
                 | compute_knight_attacks(black_pieces & self.black_knights, black_pieces)
                 | compute_king_attacks(black_pieces & self.black_kings, black_pieces);
 
-        for i in 0..64 {
-            if black_pieces & self.black_bishops & PIECE[i] != 0{
-                attacks |= compute_bishop_attacks(PIECE[i], all_pieces, white_pieces);
-            }
-            if black_pieces & self.black_rooks & PIECE[i] != 0 {
-                attacks |= compute_rook_attacks(PIECE[i], all_pieces, white_pieces);
-            }
-            if black_pieces & self.black_queens & PIECE[i] != 0{
-                attacks |= compute_bishop_attacks(PIECE[i], all_pieces, white_pieces);
-                attacks |= compute_rook_attacks(PIECE[i], all_pieces, white_pieces);
-            }
+        // Loop through the sliding pieces only, removing the lowest one each time
+        let mut diagonal_sliders = black_pieces & (self.black_bishops | self.black_queens);
+        while diagonal_sliders != 0 {
+            let square = diagonal_sliders & diagonal_sliders.wrapping_neg();
+            attacks |= compute_bishop_attacks(square, all_pieces, white_pieces);
+            diagonal_sliders &= !square;
+        }
+        let mut straight_sliders = black_pieces & (self.black_rooks | self.black_queens);
+        while straight_sliders != 0 {
+            let square = straight_sliders & straight_sliders.wrapping_neg();
+            attacks |= compute_rook_attacks(square, all_pieces, white_pieces);
+            straight_sliders &= !square;
         }
 
         attacks
@@ -558,7 +966,7 @@ impl ChessBoard {
             self.compute_black_attacks(None, None)
         );
 
-        return white_kings & black_attacks != 0;
+        white_kings & black_attacks != 0
     }
 
     /*
@@ -571,111 +979,234 @@ impl ChessBoard {
             self.compute_white_attacks(None, None)
         );
         
-        return black_kings & white_attacks != 0;
+        black_kings & white_attacks != 0
     }
 
     fn white_in_checkmate(&self) -> bool {
         // White has to be in check
-        if self.white_in_check(None, None) == false { return false; }
+        if !self.white_in_check(None, None) { return false; }
 
         // Test if white has any possible moves
-        for i in 0..64 {
-            if self.white_pieces & PIECE[i] == 0 { continue; }
-            if self.get_moves(i) != 0 { return false; }
-        }
-        true
+        !self.has_legal_move(true)
     }
 
     fn black_in_checkmate(&self) -> bool {
         // Black has to be in check
-        if self.black_in_check(None, None) == false { return false; }
+        if !self.black_in_check(None, None) { return false; }
 
         // Test if black has any possible moves
-        for i in 0..64 {
-            if self.black_pieces & PIECE[i] == 0 { continue; }
-            if self.get_moves(i) != 0 { return false; }
-        }
-        true
+        !self.has_legal_move(false)
     }
 
-    fn white_in_stalemate(&self) -> Result<bool, String> {
+    fn white_in_stalemate(&self) -> bool {
         // If it's not whites turn, white cant be in stalemate
-        if !self.whites_turn {
-            return Err("Not whites turn -> not stalemate".to_string());
-        }
+        if !self.whites_turn { return false; }
         // If white is in check, no stalemate is possible
-        if self.white_in_check(None, None) {
-            return Err("White is in check -> not stalemate".to_string());
-        }
+        if self.white_in_check(None, None) { return false; }
 
         // Test if white has any possible moves no stalemate is possible
-        for i in 0..64 {
-            if self.white_pieces & PIECE[i] == 0 { continue; }
-            if self.get_moves(i) != 0 { return Err(format!("White piece at {} -> not stalemate", i)); }
-        }
-        
-        Ok(true)
+        !self.has_legal_move(true)
     }
 
-    fn black_in_stalemate(&self) -> Result<bool, String> {
-        // If it's not whites turn, white cant be in stalemate
-        if self.whites_turn {
-            return Err("Not blacks turn -> not stalemate".to_string());
-        }
+    fn black_in_stalemate(&self) -> bool {
+        // If it's not blacks turn, black cant be in stalemate
+        if self.whites_turn { return false; }
         // If black is in check, no stalemate is possible
-        if self.black_in_check(None, None) {
-            return Err("Black is in check -> not stalemate".to_string());
-        }
+        if self.black_in_check(None, None) { return false; }
 
         // Test if black has any possible moves no stalemate is possible
-        for i in 0..64 {
-            if self.black_pieces & PIECE[i] == 0 { continue; }
-            if self.get_moves(i) != 0 { return Err(format!("White piece at {} -> not stalemate", i)); }
-        }        
-        
-        Ok(true)
+        !self.has_legal_move(false)
+    }
+
+    // Checks if any piece of a player (white if true) has a legal move
+    fn has_legal_move(&self, white: bool) -> bool {
+        let pieces = if white { self.white_pieces } else { self.black_pieces };
+        let legality = self.legality(white);
+        squares(pieces).any(|i| self.legal_targets(i, &legality) != 0)
     }
 
     fn is_three_fold_repetition(&self) -> bool {
-        let mut repetitions = 1;
-        let current = &self.positions[self.positions.len() - 1];
-        
-        // Loop through every stored position and compare it to the last stored position
-        for i in 0..&self.positions.len()-1 {
-            let vb = &self.positions[i];
-            let mut identical = true;
+        if self.repetition_count() >= 3 { return true; }
+        false
+    }
 
-            for i in 0..vb.len() {
-                if vb[i] != current[i] { identical = false; break; }
-            }
+    /*
+    Number of times the current position has appeared (including now)
+    */
+    fn repetition_count(&self) -> usize {
+        let current = match self.positions.last() {
+            Some(hash) => *hash,
+            None => return 0
+        };
+
+        // Only positions since the last capture or pawn move can be repeated
+        self.positions.iter().rev()
+            .take(self.halfmove_clock.max(0) as usize + 1)
+            .filter(|hash| **hash == current)
+            .count()
+    }
 
-            if !identical { continue; }
-            
-            repetitions += 1;
+    fn store_position(&mut self) {
+        self.positions.push(self.hash);
+    }
+
+    /// Returns the Zobrist hash of the current position (a 64 bit key)
+    /// 
+    /// The hash is made from the pieces, the player whos turn it is, the castling rights and the en passant square (only if a pawn can capture there),
+    /// so two positions with the same hash are almost certainly the same position - useful as the index of a transposition table or an opening book
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// let start = chess.hash();
+    /// 
+    /// // Move the knights out and back again
+    /// chess.move_piece(6, 21).unwrap();
+    /// chess.move_piece(62, 45).unwrap();
+    /// assert_ne!(chess.hash(), start);
+    /// chess.move_piece(21, 6).unwrap();
+    /// chess.move_piece(45, 62).unwrap();
+    /// assert_eq!(chess.hash(), start);
+    /// ```
+    /// 
+    pub fn hash(&self) -> u64 {
+        self.hash
+    }
+
+    /// Checks if neither player has enough pieces left to checkmate (a dead position), which ends the game as a draw
+    /// 
+    /// Returns true for king against king, king and knight against king, and kings with bishops all on squares of the same color
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// assert!(!chess.is_insufficient_material());
+    /// 
+    /// // King and bishop against king and bishop, both bishops on light squares
+    /// chess.load("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1".to_string());
+    /// assert!(chess.is_insufficient_material());
+    /// 
+    /// // Bishops on squares of different colors can get a checkmate
+    /// chess.load("4kb2/8/8/8/8/8/8/4KB2 w - - 0 1".to_string());
+    /// assert!(!chess.is_insufficient_material());
+    /// ```
+    /// 
+    pub fn is_insufficient_material(&self) -> bool {
+        let pawns_rooks_queens = self.white_pawns | self.black_pawns | self.white_rooks | self.black_rooks | self.white_queens | self.black_queens;
+        if pawns_rooks_queens != 0 { return false; }
+
+        let knights = self.white_knights | self.black_knights;
+        let bishops = self.white_bishops | self.black_bishops;
+
+        // A single minor piece
+        if (knights | bishops).count_ones() <= 1 { return true; }
+
+        // Only bishops, all on the same color
+        if knights == 0 && (bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0) { return true; }
+
+        false
+    }
+
+    /// Checks if a player (white if true, black if false) can't checkmate by any series of legal moves, even with the help of the other player
+    /// 
+    /// Used for the rule that a player running out of time draws instead of losing, if the other player can't checkmate
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// 
+    /// // White has only a knight against a lone king, black has a pawn
+    /// chess.load("4k3/8/8/8/8/8/8/4KN2 w - - 0 1".to_string());
+    /// assert!(chess.has_insufficient_mating_material(true));
+    /// assert!(chess.has_insufficient_mating_material(false));
+    /// 
+    /// // The knight can mate if black has something to block its own king with
+    /// chess.load("4k3/4p3/8/8/8/8/8/4KN2 w - - 0 1".to_string());
+    /// assert!(!chess.has_insufficient_mating_material(true));
+    /// assert!(!chess.has_insufficient_mating_material(false));
+    /// ```
+    /// 
+    pub fn has_insufficient_mating_material(&self, white: bool) -> bool {
+        let (own, enemy) = if white { (self.white_pieces, self.black_pieces) } else { (self.black_pieces, self.white_pieces) };
+        let kings = self.white_kings | self.black_kings;
+        let queens = self.white_queens | self.black_queens;
+        let pawns = self.white_pawns | self.black_pawns;
+        let knights = self.white_knights | self.black_knights;
+        let bishops = self.white_bishops | self.black_bishops;
+        let rooks = self.white_rooks | self.black_rooks;
+
+        if own & (pawns | rooks | queens) != 0 { return false; }
+
+        // A single knight can only mate a king blocked in by its own pieces (a queen can always move away)
+        if own & knights != 0 {
+            return own.count_ones() <= 2 && enemy & !kings & !queens == 0;
+        }
+
+        // Bishops can only mate when there is a bishop of the other color or a knight or pawn to block the king
+        if own & bishops != 0 {
+            let same_color = bishops & DARK_SQUARES == 0 || bishops & !DARK_SQUARES == 0;
+            return same_color && pawns == 0 && knights == 0;
         }
 
-        if repetitions >= 3 { return true; }
-        return false;
+        // Only a king
+        true
     }
 
-    fn store_position(&mut self) {
-        // Store the castling rights as a bitboard
-        let mut castling: BitBoard = 0;
-        if self.castling_rights.0 { castling |= (1 as BitBoard) << 1; }
-        if self.castling_rights.1 { castling |= (1 as BitBoard) << 2; }
-        if self.castling_rights.2 { castling |= (1 as BitBoard) << 3; }
-        if self.castling_rights.3 { castling |= (1 as BitBoard) << 4; }
-
-        // Store the whether the possibility of en passant exists as bitboard (not accounting for pinned pawns)
-        let mut en_passant_possible: BitBoard = 0;
-        if self.en_passant_square & 
-            (compute_white_pawn_attacks(self.white_pawns, self.en_passant_square)
-            | compute_black_pawn_attacks(self.black_pawns, self.en_passant_square)
-            ) != 0 {
-            en_passant_possible = 1;
+    // Computes the Zobrist hash of the position from scratch
+    fn compute_hash(&self) -> u64 {
+        let mut hash: u64 = 0;
+
+        for (piece_type, pieces) in self.piece_bitboards().iter().enumerate() {
+            let mut pieces = *pieces;
+            while pieces != 0 {
+                hash ^= ZOBRIST.pieces[piece_type][pieces.trailing_zeros() as usize];
+                pieces &= pieces - 1;
+            }
         }
-        
-        let vb: Vec<BitBoard> = vec![
+
+        hash ^= self.castling_key() ^ self.en_passant_key();
+        if !self.whites_turn { hash ^= ZOBRIST.black_to_move; }
+
+        hash
+    }
+
+    // Zobrist key of the castling rights
+    fn castling_key(&self) -> u64 {
+        let mut rights = 0;
+        if self.castling_rights.0 { rights |= 1; }
+        if self.castling_rights.1 { rights |= 2; }
+        if self.castling_rights.2 { rights |= 4; }
+        if self.castling_rights.3 { rights |= 8; }
+        ZOBRIST.castling[rights]
+    }
+
+    // Zobrist key of the en passant square, only if a pawn is next to it (positions are the same if no pawn can capture)
+    fn en_passant_key(&self) -> u64 {
+        if self.en_passant_square == 0 { return 0; }
+
+        let square = bit_scan(self.en_passant_square);
+        let capturing_pawns = if square / 8 == 2 {
+            PAWN_ATTACKS[0][square] & self.black_pawns
+        } else {
+            PAWN_ATTACKS[1][square] & self.white_pawns
+        };
+
+        if capturing_pawns == 0 { return 0; }
+        ZOBRIST.en_passant[square % 8]
+    }
+
+    // The bitboards of every piece type, in the order of PieceType
+    fn piece_bitboards(&self) -> [BitBoard; 12] {
+        [
             self.white_pawns,
             self.white_knights,
             self.white_bishops,
@@ -688,180 +1219,228 @@ impl ChessBoard {
             self.black_rooks,
             self.black_queens,
             self.black_kings,
-            en_passant_possible,
-            castling            
-        ];
-
-        self.positions.push(vb);
+        ]
     }
 
     /// Get BitBoard of possible moves a piece
     /// 
     fn get_moves (&self, position: usize) -> BitBoard {
-        let square: BitBoard = PIECE[position];
-        let mut moves = 0;
         let piece_type = self.piece_at(position);
-                
-        match piece_type {
-            PieceType::WhiteKing =>     moves |= compute_king_attacks(square, self.white_pieces),
-            PieceType::WhiteQueen =>    moves |= compute_rook_attacks(square, self.all_pieces, self.black_pieces)
-                                              | compute_bishop_attacks(square, self.all_pieces, self.black_pieces),
-            PieceType::WhiteRook =>     moves |= compute_rook_attacks(square, self.all_pieces, self.black_pieces),
-            PieceType::WhiteBishop =>   moves |= compute_bishop_attacks(square, self.all_pieces, self.black_pieces),
-            PieceType::WhiteKnight =>   moves |= compute_knight_attacks(square, self.white_pieces),
-            PieceType::WhitePawn =>     moves |= compute_white_pawn_moves(square, self.all_pieces, self.black_pieces | self.en_passant_square),
-            PieceType::BlackKing =>     moves |= compute_king_attacks(square, self.black_pieces),
-            PieceType::BlackQueen =>    moves |= compute_rook_attacks(square, self.all_pieces, self.white_pieces)
-                                              | compute_bishop_attacks(square, self.all_pieces, self.white_pieces),
-            PieceType::BlackRook =>     moves |= compute_rook_attacks(square, self.all_pieces, self.white_pieces),
-            PieceType::BlackBishop =>   moves |= compute_bishop_attacks(square, self.all_pieces, self.white_pieces),
-            PieceType::BlackKnight =>   moves |= compute_knight_attacks(square, self.black_pieces),
-            PieceType::BlackPawn =>     moves |= compute_black_pawn_moves(square, self.all_pieces, self.white_pieces | self.en_passant_square),
-            _ => moves = 0
-        }
-
-        let is_white: bool = piece_type.is_white();
-
-        // Check if this move places own side in check
-        if !piece_type.is_king() {
-            for i in 0..64 {
-                if moves & PIECE[i] == 0 { continue; }
-                // If white moved a piece (not a king)
-                if is_white {
-                    let mut black_attacks = self.compute_black_attacks(
-                        Some(self.black_pieces & !PIECE[i]),
-                        Some(self.white_pieces & !square | PIECE[i])
-                    );
-                    // Remove pawn if move is en-passant
-                    if piece_type == PieceType::WhitePawn && PIECE[i] == self.en_passant_square {
-                        black_attacks = self.compute_black_attacks(
-                            Some(self.black_pieces & !PIECE[i] & !PIECE[i-8]),
-                            Some(self.white_pieces & !square | PIECE[i])
-                        );
-                    }
-                    if self.white_in_check(Some(black_attacks), None) {
-                        moves &= !PIECE[i];
-                    }
-                }
-                // If black moved a piece (not a king)
-                else {
-                    let mut white_attacks = self.compute_white_attacks(
-                        Some(self.black_pieces & !square | PIECE[i]),
-                        Some(self.white_pieces & !PIECE[i])
-                    );
-                    // Remove enemy pawn if en-passanted
-                    if piece_type == PieceType::WhitePawn && PIECE[i] == self.en_passant_square {
-                        white_attacks = self.compute_white_attacks(
-                            Some(self.black_pieces & !square | PIECE[i]),
-                            Some(self.white_pieces & !PIECE[i] & !PIECE[i+8])
-                        );
-                    }
-                    if self.black_in_check(Some(white_attacks), None ) {
-                        moves &= !PIECE[i];
-                    }
-                }
-            }
+        if piece_type == PieceType::Empty { return 0; }
+
+        let legality = self.legality(piece_type.is_white());
+        self.legal_targets(position, &legality)
+    }
+
+    /*
+    Every square attacked by one side, including squares of its own pieces (defended pieces)
+    The sliding pieces are blocked by the given occupancy
+    */
+    fn attacked_squares (&self, by_white: bool, occupied: BitBoard) -> BitBoard {
+        let (pawns, knights, bishops, rooks, queens, kings, color) = if by_white {
+            (self.white_pawns, self.white_knights, self.white_bishops, self.white_rooks, self.white_queens, self.white_kings, 0)
+        } else {
+            (self.black_pawns, self.black_knights, self.black_bishops, self.black_rooks, self.black_queens, self.black_kings, 1)
+        };
+
+        let mut attacks: BitBoard = 0;
+
+        let mut pieces = pawns;
+        while pieces != 0 {
+            attacks |= PAWN_ATTACKS[color][pieces.trailing_zeros() as usize];
+            pieces &= pieces - 1;
+        }
+        let mut pieces = knights;
+        while pieces != 0 {
+            attacks |= KNIGHT_ATTACKS[pieces.trailing_zeros() as usize];
+            pieces &= pieces - 1;
+        }
+        let mut pieces = kings;
+        while pieces != 0 {
+            attacks |= KING_ATTACKS[pieces.trailing_zeros() as usize];
+            pieces &= pieces - 1;
+        }
+        let mut pieces = bishops | queens;
+        while pieces != 0 {
+            attacks |= bishop_attacks(pieces.trailing_zeros() as usize, occupied);
+            pieces &= pieces - 1;
+        }
+        let mut pieces = rooks | queens;
+        while pieces != 0 {
+            attacks |= rook_attacks(pieces.trailing_zeros() as usize, occupied);
+            pieces &= pieces - 1;
         }
-        else {
-            // Piece is a king
-            for i in 0..64 {
-                if moves & PIECE[i] == 0 { continue; }
-                // If white moved the king
-                if is_white && self.white_in_check(
-                        Some(self.compute_black_attacks(
-                            Some(self.black_pieces & !PIECE[i]), 
-                            Some(self.white_pieces & !square | PIECE[i]))
-                        ),
-                        Some(PIECE[i])
-                    ) {
-                    moves &= !PIECE[i];
-                } 
-                // If black moved the king
-                else if !is_white && self.black_in_check(Some(
-                    self.compute_white_attacks(
-                        Some(self.black_pieces & !square | PIECE[i]),
-                         Some(self.white_pieces)
-                    )), Some(PIECE[i])) {
-                    moves &= !PIECE[i];
 
-                }
+        attacks
+    }
+
+    /*
+    Finds the checking pieces, the pinned pieces and the squares the king can't move to, for one side
+    */
+    fn legality (&self, white: bool) -> Legality {
+        let (kings, own_pieces, enemy_pieces) = if white {
+            (self.white_kings, self.white_pieces, self.black_pieces)
+        } else {
+            (self.black_kings, self.black_pieces, self.white_pieces)
+        };
+        let (enemy_pawns, enemy_knights, enemy_diagonal, enemy_straight) = if white {
+            (self.black_pawns, self.black_knights, self.black_bishops | self.black_queens, self.black_rooks | self.black_queens)
+        } else {
+            (self.white_pawns, self.white_knights, self.white_bishops | self.white_queens, self.white_rooks | self.white_queens)
+        };
+
+        // Without a king nothing can be checked or pinned
+        if kings == 0 {
+            return Legality {
+                king_square: 64,
+                checkers: 0,
+                check_mask: !0,
+                pinned: 0,
+                king_danger: self.attacked_squares(!white, self.all_pieces),
+            };
+        }
+
+        let king_square = kings.trailing_zeros() as usize;
+        let color = if white { 0 } else { 1 };
+
+        let checkers = (PAWN_ATTACKS[color][king_square] & enemy_pawns)
+            | (KNIGHT_ATTACKS[king_square] & enemy_knights)
+            | (bishop_attacks(king_square, self.all_pieces) & enemy_diagonal)
+            | (rook_attacks(king_square, self.all_pieces) & enemy_straight);
+
+        let check_mask = match checkers.count_ones() {
+            0 => !0,
+            1 => checkers | BETWEEN[king_square][checkers.trailing_zeros() as usize],
+            // Double check, only the king can move
+            _ => 0
+        };
+
+        // Sliders that would attack the king if only enemy pieces blocked, pin the piece between them if it is the only one
+        let mut pinned: BitBoard = 0;
+        let mut snipers = (bishop_attacks(king_square, enemy_pieces) & enemy_diagonal)
+            | (rook_attacks(king_square, enemy_pieces) & enemy_straight);
+        while snipers != 0 {
+            let blockers = BETWEEN[king_square][snipers.trailing_zeros() as usize] & self.all_pieces;
+            if blockers.count_ones() == 1 && blockers & own_pieces != 0 {
+                pinned |= blockers;
             }
-            
-            // Add castling moves - Need anEmpty implementation for Fischer Random etc.
+            snipers &= snipers - 1;
+        }
+
+        Legality {
+            king_square,
+            checkers,
+            check_mask,
+            pinned,
+            king_danger: self.attacked_squares(!white, self.all_pieces & !kings),
+        }
+    }
+
+    /*
+    Legal moves of the piece at a position, for the side described by legality
+    */
+    fn legal_targets (&self, position: usize, legality: &Legality) -> BitBoard {
+        let piece_type = self.piece_at(position);
+        let is_white = piece_type.is_white();
+        let square: BitBoard = PIECE[position];
+        let (own_pieces, enemy_pieces) = if is_white {
+            (self.white_pieces, self.black_pieces)
+        } else {
+            (self.black_pieces, self.white_pieces)
+        };
+
+        if piece_type.is_king() {
+            let mut moves = KING_ATTACKS[position] & !own_pieces & !legality.king_danger;
+
+            // Add castling moves - Need an implementation for Fischer Random etc.
+            // The king can't castle out of, through or into check
+            let not_in_check = legality.checkers == 0;
+            let free = |empty: BitBoard, safe: BitBoard| self.all_pieces & empty == 0 && legality.king_danger & safe == 0;
+
             // Whites Kingside
-            if is_white && self.castling_rights.0 &&
-                self.all_pieces & PIECE[5] == 0 &&
-                self.all_pieces & PIECE[6] == 0 &&
-                !self.white_in_check(None, None) &&
-                !self.white_in_check(Some(self.compute_black_attacks(
-                    Some(self.black_pieces), 
-                    Some(self.white_pieces & !square | PIECE[5]))
-                    ), Some(PIECE[5])) &&
-                !self.white_in_check(Some(self.compute_black_attacks(
-                    Some(self.black_pieces), 
-                    Some(self.white_pieces & !square | PIECE[6]))
-                    ), Some(PIECE[6])) {
-                    moves |= PIECE[6];
+            if is_white && not_in_check && self.castling_rights.0 &&
+                free(PIECE[5] | PIECE[6], PIECE[5] | PIECE[6]) {
+                moves |= PIECE[6];
             }
-
             // Whites Queenside
-            if is_white && self.castling_rights.1 &&
-                self.all_pieces & PIECE[3] == 0 &&
-                self.all_pieces & PIECE[2] == 0 &&
-                self.all_pieces & PIECE[1] == 0 &&
-                !self.white_in_check(None, None) &&
-                !self.white_in_check(Some(self.compute_black_attacks(
-                    Some(self.black_pieces), 
-                    Some(self.white_pieces & !square | PIECE[3]))
-                    ), Some(PIECE[3])) &&
-                !self.white_in_check(Some(self.compute_black_attacks(
-                    Some(self.black_pieces), 
-                    Some(self.white_pieces & !square | PIECE[2]))
-                    ), Some(PIECE[2])) {
-                    moves |= PIECE[2];
+            if is_white && not_in_check && self.castling_rights.1 &&
+                free(PIECE[1] | PIECE[2] | PIECE[3], PIECE[2] | PIECE[3]) {
+                moves |= PIECE[2];
             }
             // Blacks Kingside
-            if !is_white && self.castling_rights.2 &&
-                self.all_pieces & PIECE[8*7+5] == 0 &&
-                self.all_pieces & PIECE[8*7+6] == 0 &&
-                !self.black_in_check(None, None) &&
-                !self.black_in_check(Some(
-                    self.compute_white_attacks(
-                        Some(self.black_pieces & !square | PIECE[8*7+5]),
-                         Some(self.white_pieces)
-                    )), Some(PIECE[8*7+5])) &&
-                !self.black_in_check(Some(
-                    self.compute_white_attacks(
-                        Some(self.black_pieces & !square | PIECE[8*7+6]),
-                         Some(self.white_pieces)
-                    )), Some(PIECE[8*7+6])) {
-                    moves |= PIECE[8*7+6];
+            if !is_white && not_in_check && self.castling_rights.2 &&
+                free(PIECE[8*7+5] | PIECE[8*7+6], PIECE[8*7+5] | PIECE[8*7+6]) {
+                moves |= PIECE[8*7+6];
             }
             // Blacks Queenside
-            if !is_white && self.castling_rights.3 &&
-                self.all_pieces & PIECE[8*7+3] == 0 &&
-                self.all_pieces & PIECE[8*7+2] == 0 &&
-                self.all_pieces & PIECE[8*7+1] == 0 &&
-                !self.black_in_check(None, None) &&
-                !self.black_in_check(Some(
-                    self.compute_white_attacks(
-                        Some(self.black_pieces & !square | PIECE[8*7+3]),
-                         Some(self.white_pieces)
-                    )), Some(PIECE[8*7+3])) &&
-                !self.black_in_check(Some(
-                    self.compute_white_attacks(
-                        Some(self.black_pieces & !square | PIECE[8*7+2]),
-                         Some(self.white_pieces)
-                    )), Some(PIECE[8*7+2])) {
-                    moves |= PIECE[8*7+2];
+            if !is_white && not_in_check && self.castling_rights.3 &&
+                free(PIECE[8*7+1] | PIECE[8*7+2] | PIECE[8*7+3], PIECE[8*7+2] | PIECE[8*7+3]) {
+                moves |= PIECE[8*7+2];
+            }
+
+            return moves;
+        }
+
+        let mut moves = match piece_type {
+            PieceType::WhiteQueen | PieceType::BlackQueen =>
+                (bishop_attacks(position, self.all_pieces) | rook_attacks(position, self.all_pieces)) & !own_pieces,
+            PieceType::WhiteRook | PieceType::BlackRook => rook_attacks(position, self.all_pieces) & !own_pieces,
+            PieceType::WhiteBishop | PieceType::BlackBishop => bishop_attacks(position, self.all_pieces) & !own_pieces,
+            PieceType::WhiteKnight | PieceType::BlackKnight => KNIGHT_ATTACKS[position] & !own_pieces,
+            PieceType::WhitePawn => compute_white_pawn_moves(square, self.all_pieces, enemy_pieces),
+            PieceType::BlackPawn => compute_black_pawn_moves(square, self.all_pieces, enemy_pieces),
+            _ => 0
+        };
+
+        // Moves must block or capture a checking piece
+        moves &= legality.check_mask;
+
+        // Add en passant if the pawn can capture on the square (white on rank 6, black on rank 3)
+        if piece_type.is_pawn() && self.en_passant_square != 0 {
+            let target = bit_scan(self.en_passant_square);
+            let color = if is_white { 0 } else { 1 };
+            if target / 8 == (if is_white { 5 } else { 2 }) &&
+                PAWN_ATTACKS[color][position] & self.en_passant_square != 0 &&
+                self.en_passant_is_legal(position, target, legality) {
+                moves |= self.en_passant_square;
             }
         }
 
+        // Pinned pieces can only move along the pin
+        if legality.pinned & square != 0 {
+            moves &= LINE[legality.king_square][position];
+        }
+
         moves
     }
 
-    /// Returns a list of all squares the piece at a certain position can move to
-    /// 
+    /*
+    Tests if capturing en passant leaves the king safe
+    Both pawns leave the rank at once, so a rook or queen on the same rank as the king can be discovered
+    */
+    fn en_passant_is_legal (&self, from: usize, target: usize, legality: &Legality) -> bool {
+        let is_white = self.white_pieces & PIECE[from] != 0;
+        let captured = if is_white { target - 8 } else { target + 8 };
+
+        // The capture must remove the checking pawn, or block the check
+        if legality.checkers != 0 && legality.checkers & PIECE[captured] == 0 && legality.check_mask & PIECE[target] == 0 {
+            return false;
+        }
+        if legality.king_square > 63 { return true; }
+
+        let occupied = (self.all_pieces & !PIECE[from] & !PIECE[captured]) | PIECE[target];
+        let (enemy_diagonal, enemy_straight) = if is_white {
+            (self.black_bishops | self.black_queens, self.black_rooks | self.black_queens)
+        } else {
+            (self.white_bishops | self.white_queens, self.white_rooks | self.white_queens)
+        };
+
+        bishop_attacks(legality.king_square, occupied) & enemy_diagonal == 0 &&
+            rook_attacks(legality.king_square, occupied) & enemy_straight == 0
+    }
+
+    /// Returns a list of all squares the piece at a certain position can move to
+    /// 
     /// Will only show legal moves of the current players turns own pieces (cant move enemies pieces)
     /// 
     /// 
@@ -882,7 +1461,7 @@ impl ChessBoard {
             }
         }
 
-        return moves;
+        moves
     }
 
     /// Gives the PieceType at a certain square (0-63 inclusive)
@@ -928,7 +1507,7 @@ impl ChessBoard {
     /// 
     /// Returns Ok(false) without the move is an unhandled promotion   
     /// 
-    /// Returns Err(m) without moving the piece if for any reason the piece could not move and gives a message m (String) for the reason why
+    /// Returns Err(e) without moving the piece if for any reason the piece could not move, and gives the reason e (MoveError)
     /// 
     /// # Examples
     /// 
@@ -957,17 +1536,20 @@ impl ChessBoard {
     /// }
     /// ```
     /// 
-    pub fn handle_promotion (&mut self, from: usize, to: usize, piece_type: PieceType) -> Result<bool, String> {
-        self.promotion_piece = piece_type;
-
-        if piece_type.is_king() || piece_type.is_pawn() {
-            return Err("Can't promote to a king or a pawn".to_string());
+    pub fn handle_promotion (&mut self, from: usize, to: usize, piece_type: PieceType) -> Result<bool, MoveError> {
+        if piece_type.is_king() || piece_type.is_pawn() || piece_type == PieceType::Empty {
+            return Err(MoveError::InvalidPromotionPiece { piece: piece_type });
         }
         if (self.whites_turn && !piece_type.is_white()) || (!self.whites_turn && piece_type.is_white()) {
-            return Err("Wrong color promotion piece".to_string());
+            return Err(MoveError::InvalidPromotionPiece { piece: piece_type });
         }
 
-        self.move_piece(from, to)
+        self.promotion_piece = piece_type;
+        let result = self.move_piece(from, to);
+        // Don't let the promotion piece be used by a later move if this move failed
+        self.promotion_piece = PieceType::Empty;
+
+        result
     }
 
     /// Method to move piece from one square to another square
@@ -978,7 +1560,9 @@ impl ChessBoard {
     /// 
     /// Returns Ok(false) without moving the piece if the move is a promotion (use chess.handle_promotion(from, to, piece_type) instead)  
     /// 
-    /// Returns Err(m) without moving the piece if for any reason the piece could not move and gives a message m (String) for the reason why
+    /// Returns Err(e) without moving the piece if for any reason the piece could not move, and gives the reason e (MoveError)
+    /// 
+    /// In a timed game the move is made at the latest time given to chess.update_clock(now), the time since then is not counted and a player whos time has run out since then can still move, use chess.move_piece_at(from, to, now) to give the time of the move
     /// 
     /// # Examples
     /// 
@@ -1007,30 +1591,224 @@ impl ChessBoard {
     /// }
     /// ```
     /// 
-    pub fn move_piece (&mut self, from: usize, to: usize) -> Result<bool, String> {
-        if self.game_result != GameResult::Ongoing {
-            return Err("Game is finished".to_string());
+    pub fn move_piece (&mut self, from: usize, to: usize) -> Result<bool, MoveError> {
+        let piece_type = self.check_move(from, to)?;
+
+        // Same players turn to specify what piece type to promote to, nothing is changed until then
+        if self.promotion_piece == PieceType::Empty &&
+            ((piece_type == PieceType::WhitePawn && to / 8 == 7) || 
+            (piece_type == PieceType::BlackPawn && to / 8 == 0)) {
+            return Ok(false);
+        }
+
+        // Make the move on the board, and change player turn
+        self.apply_move(piece_type, from, to);
+
+        // Check for checkmate, stalemate...
+        self.update_board();
+
+        self.store_position();
+
+        // Draws by repetition and the number of moves, as chosen by the draw policy
+        let (repetitions, moves) = match self.draw_policy {
+            DrawPolicy::Fide => (5, 150),
+            DrawPolicy::AutoDraw => (3, 100),
+            DrawPolicy::None => (usize::MAX, i32::MAX)
+        };
+
+        if self.game_result == GameResult::Ongoing && self.repetition_count() >= repetitions {
+            self.game_result = GameResult::Draw;
+            self.termination = Some(if self.repetition_count() >= 5 {
+                Termination::FivefoldRepetition
+            } else {
+                Termination::ThreefoldRepetition
+            });
+        }
+
+        if self.game_result == GameResult::Ongoing && self.halfmove_clock >= moves {
+            self.game_result = GameResult::Draw;
+            self.termination = Some(if self.halfmove_clock >= 150 {
+                Termination::SeventyFiveMoveRule
+            } else {
+                Termination::FiftyMoveRule
+            });
+        }
+
+        // Detect if player is in check
+        self.player_in_check = false;
+        if self.whites_turn && self.white_in_check(None, None) {
+            self.player_in_check = true;
+        }
+        if !self.whites_turn && self.black_in_check(None, None) {
+            self.player_in_check = true;
         }
 
+        // The player who moved stops their clock, at the time of the last update, and the clock of the other player starts
+        if let Some(clock) = self.clock.as_mut() {
+            let now = clock.last_update();
+            clock.press(now);
+            if self.game_result != GameResult::Ongoing {
+                clock.stop(now);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /*
+    Checks that the player in turn can move the piece on the square from to the square to (not looking at promotions), and gives the piece
+    */
+    fn check_move (&self, from: usize, to: usize) -> Result<PieceType, MoveError> {
+        if self.game_result != GameResult::Ongoing { return Err(MoveError::GameOver); }
+        if from > 63 { return Err(MoveError::SquareOutOfRange { square: from }); }
+        if to > 63 { return Err(MoveError::SquareOutOfRange { square: to }); }
+
         // if piece doesn't exist
-        if self.all_pieces & PIECE[from] == 0 { return Err("Piece doesn't exist".to_string()); }
+        if self.all_pieces & PIECE[from] == 0 { return Err(MoveError::NoPieceOnSquare { square: from }); }
 
-        let moves = self.get_moves(from);
+        // Can't move piece if it's not that sides turn
+        let piece_type: PieceType = self.piece_at(from);
+        if piece_type.is_white() != self.whites_turn { return Err(MoveError::WrongSideToMove { square: from }); }
 
         // break if piece cant move to desired position
-        if moves == 0 { return Err("Piece can't move at all".to_string()); }
-        if moves & PIECE[to] == 0 { return Err("Piece can't move to desired square".to_string()); }
+        let moves = self.get_moves(from);
+        if moves == 0 { return Err(MoveError::NoLegalMoves { square: from }); }
+        if moves & PIECE[to] == 0 { return Err(MoveError::IllegalDestination { from, to }); }
 
-        let piece_type: PieceType = self.piece_at(from);
-        
-        // Can't move piece if it's not that sides turn
-        if self.whites_turn && !piece_type.is_white() { return Err("Not black's turn".to_string()); }
-        if !self.whites_turn && piece_type.is_white() { return Err("Not white's turn".to_string()); }
+        Ok(piece_type)
+    }
+
+    /// Moves a piece like chess.move_piece(from, to), for a timed game where the move is made at the time now
+    /// 
+    /// The clock is updated to now before the move, so the move is not made if the player ran out of time (the game is then finished)
+    /// 
+    /// Returns the same as chess.move_piece(from, to)
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use std::time::Duration;
+    /// use davbjor_chess::{ChessBoard, GameResult, Termination};
+    /// use davbjor_chess::clock::TimeControl;
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// chess.start_clock(TimeControl::sudden_death(Duration::from_secs(60)), Duration::ZERO);
+    /// 
+    /// // E2 -> E4 after 5 seconds, E7 -> E5 after 10 more seconds
+    /// assert_eq!(chess.move_piece_at(12, 28, Duration::from_secs(5)), Ok(true));
+    /// assert_eq!(chess.move_piece_at(52, 36, Duration::from_secs(15)), Ok(true));
+    /// 
+    /// let clock = chess.clock.as_ref().unwrap();
+    /// assert_eq!(clock.remaining(true, Duration::from_secs(15)), Duration::from_secs(55));
+    /// assert_eq!(clock.remaining(false, Duration::from_secs(15)), Duration::from_secs(50));
+    /// 
+    /// // White takes too long
+    /// assert!(chess.move_piece_at(6, 21, Duration::from_secs(75)).is_err());
+    /// assert_eq!(chess.game_result, GameResult::Black);
+    /// assert_eq!(chess.termination, Some(Termination::Timeout));
+    /// ```
+    /// 
+    pub fn move_piece_at (&mut self, from: usize, to: usize, now: Duration) -> Result<bool, MoveError> {
+        self.update_clock(now);
+        self.move_piece(from, to)
+    }
+
+    /// Starts the clock of a timed game at the time now (any Duration since a fixed point, e.g. the start of the game)
+    /// 
+    /// The clock of the player to move starts counting down, and switches to the other player after every move
+    /// 
+    /// Moves made with chess.move_piece(from, to) (or chess.make_move(m)) are timed at the latest time given to chess.update_clock(now), so the time since then is free and a player can move after their time has run out until the clock is updated, use chess.move_piece_at(from, to, now) to give the time of the move
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use std::time::Duration;
+    /// use davbjor_chess::{ChessBoard};
+    /// use davbjor_chess::clock::TimeControl;
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// chess.start_clock(TimeControl::fischer(Duration::from_secs(300), Duration::from_secs(3)), Duration::ZERO);
+    /// 
+    /// chess.update_clock(Duration::from_secs(4));
+    /// chess.apply_uci_moves(&["e2e4"]).unwrap();
+    /// 
+    /// let clock = chess.clock.as_ref().unwrap();
+    /// assert_eq!(clock.remaining(true, Duration::from_secs(4)), Duration::from_secs(299));
+    /// assert!(!clock.white_to_move());
+    /// ```
+    /// 
+    pub fn start_clock (&mut self, control: TimeControl, now: Duration) {
+        let mut clock = Clock::new(control);
+        if self.game_result == GameResult::Ongoing {
+            clock.start(self.whites_turn, now);
+        }
+        self.clock = Some(clock);
+    }
+
+    /// Updates the clock of a timed game to the time now
+    /// 
+    /// If the player to move has run out of time the game is finished, won by the other player or a draw if the other player can't checkmate
+    /// 
+    /// The clock is stopped when the game has finished in any other way
+    /// 
+    /// Returns true if the player to move ran out of time, false if there is time left (or the game is not timed)
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use std::time::Duration;
+    /// use davbjor_chess::{ChessBoard, GameResult, Termination};
+    /// use davbjor_chess::clock::TimeControl;
+    /// 
+    /// // Black has only the king left, so white running out of time is a draw
+    /// let mut chess = ChessBoard::new();
+    /// chess.load("4k3/8/8/8/8/8/4P3/4K3 w - - 0 1".to_string());
+    /// chess.start_clock(TimeControl::sudden_death(Duration::from_secs(10)), Duration::ZERO);
+    /// 
+    /// assert!(!chess.update_clock(Duration::from_secs(9)));
+    /// assert!(chess.update_clock(Duration::from_secs(10)));
+    /// assert_eq!(chess.game_result, GameResult::Draw);
+    /// assert_eq!(chess.termination, Some(Termination::Timeout));
+    /// ```
+    /// 
+    pub fn update_clock (&mut self, now: Duration) -> bool {
+        let Some(clock) = self.clock.as_mut() else { return false; };
+
+        if self.game_result != GameResult::Ongoing {
+            clock.stop(now);
+            return false;
+        }
+
+        let Some(white) = clock.update(now) else { return false; };
+
+        // Running out of time only loses if the other player could still checkmate
+        self.game_result = if self.has_insufficient_mating_material(!white) {
+            GameResult::Draw
+        } else if white {
+            GameResult::Black
+        } else {
+            GameResult::White
+        };
+        self.termination = Some(Termination::Timeout);
+
+        true
+    }
 
+    /*
+    Makes a move on the bitboards and changes player turn, without checking that it is legal or if the game has ended
+    The state before the move is stored, so it can be taken back with unmake_move
+    */
+    fn apply_move (&mut self, piece_type: PieceType, from: usize, to: usize) {
         // Store if piece was captured (for halfmove clock)
         let mut capture: bool = false;
         if self.all_pieces & PIECE[to] != 0 { capture = true; }
 
+        // Store the state of the game before the move (for undo)
+        self.store_move_record(piece_type, from, to);
+
+        // Remove the castling rights and en passant square from the hash, they are added again after the move
+        self.hash ^= self.castling_key() ^ self.en_passant_key();
+
         // Move piece in bitboards
         self.update_board_after_move(piece_type, from, to);
 
@@ -1052,7 +1830,7 @@ impl ChessBoard {
             }
             // White Kingside
             if self.castling_rights.3 && to == 8*7+2 {
-                self.update_board_after_move(PieceType::BlackRook, 8*7+0, 8*7+3);
+                self.update_board_after_move(PieceType::BlackRook, 8*7 , 8*7+3);
             }
         }
 
@@ -1067,10 +1845,10 @@ impl ChessBoard {
         // Detect possible en passant square
         self.en_passant_square = 0;
         if piece_type == PieceType::WhitePawn && from / 8 == 1 && to / 8 == 3 {
-            self.en_passant_square = PIECE[from + 8 as usize];
+            self.en_passant_square = PIECE[from + 8_usize];
         }
         if piece_type == PieceType::BlackPawn && from / 8 == 6 && to / 8 == 4 {
-            self.en_passant_square = PIECE[from - 8 as usize];
+            self.en_passant_square = PIECE[from - 8_usize];
         }
 
         // Halfmove clock
@@ -1080,7 +1858,7 @@ impl ChessBoard {
         }
 
         // Add fullmove if black just moved
-        if self.whites_turn == false {
+        if !self.whites_turn {
             self.fullmove += 1;
         }
 
@@ -1093,53 +1871,244 @@ impl ChessBoard {
             self.castling_rights.2 = false;
             self.castling_rights.3 = false;
         }
-        if from == SQUARE::H1 { self.castling_rights.0 = false; }
-        if from == SQUARE::A1 { self.castling_rights.1 = false; }
-        if from == SQUARE::H8 { self.castling_rights.0 = false; }
-        if from == SQUARE::A8 { self.castling_rights.1 = false; }
+        if from == Square::H1 { self.castling_rights.0 = false; }
+        if from == Square::A1 { self.castling_rights.1 = false; }
+        if from == Square::H8 { self.castling_rights.2 = false; }
+        if from == Square::A8 { self.castling_rights.3 = false; }
+
+        // A captured rook can't castle either
+        if to == Square::H1 { self.castling_rights.0 = false; }
+        if to == Square::A1 { self.castling_rights.1 = false; }
+        if to == Square::H8 { self.castling_rights.2 = false; }
+        if to == Square::A8 { self.castling_rights.3 = false; }
 
         // Promotion handling
         if (piece_type == PieceType::WhitePawn && to / 8 == 7) || 
             (piece_type == PieceType::BlackPawn && to / 8 == 0) {
-            // Same players turn to specify what piece type to promote to
-            //handlePromotion(from, to);
-            if self.promotion_piece == PieceType::Empty {
-                return Ok(false);
-            }
             self.update_board_after_move(self.promotion_piece, to, to);
         }
-        
-        // Update derived bitboards, check for checkmate, stalemate...
-        self.update_board();
 
-        self.store_position();
+        self.update_derived_boards();
 
-        if self.is_three_fold_repetition() {
-            self.game_result = GameResult::Draw;
+        // Change player turn
+        self.whites_turn = !self.whites_turn;
+        self.hash ^= self.castling_key() ^ self.en_passant_key() ^ ZOBRIST.black_to_move;
+
+        self.promotion_piece = PieceType::Empty;
+    }
+
+    /*
+    Makes a legal move (from legal_moves) without looking for the end of the game, used when searching through moves
+    Taken back with unmake_move
+    */
+    fn make_move_unchecked (&mut self, m: Move) {
+        self.promotion_piece = m.promotion.unwrap_or(PieceType::Empty);
+        self.apply_move(self.piece_at(m.from), m.from, m.to);
+    }
+
+    /*
+    Copy of the position without the history of the game (moves, positions and clock), to try a move on
+    */
+    fn position_copy (&self) -> ChessBoard {
+        ChessBoard {
+            white_pawns: self.white_pawns,
+            white_knights: self.white_knights,
+            white_bishops: self.white_bishops,
+            white_rooks: self.white_rooks,
+            white_queens: self.white_queens,
+            white_kings: self.white_kings,
+            black_pawns: self.black_pawns,
+            black_knights: self.black_knights,
+            black_bishops: self.black_bishops,
+            black_rooks: self.black_rooks,
+            black_queens: self.black_queens,
+            black_kings: self.black_kings,
+            white_pieces: self.white_pieces,
+            black_pieces: self.black_pieces,
+            all_pieces: self.all_pieces,
+            whites_turn: self.whites_turn,
+            game_result: self.game_result,
+            termination: self.termination,
+            draw_policy: self.draw_policy,
+            castling_rights: self.castling_rights,
+            halfmove_clock: self.halfmove_clock,
+            fullmove: self.fullmove,
+            player_in_check: self.player_in_check,
+            promotion_piece: self.promotion_piece,
+            en_passant_square: self.en_passant_square,
+            hash: self.hash,
+            board: self.board.clone(),
+            clock: None,
+            positions: Vec::new(),
+            history: Vec::new(),
+            redo_stack: Vec::new(),
         }
+    }
 
-        if self.game_result == GameResult::Ongoing && self.halfmove_clock >= 100 {
-            self.game_result = GameResult::Draw;
+    // Checks if the player whos turn it is is in check
+    fn side_to_move_in_check (&self) -> bool {
+        self.legality(self.whites_turn).checkers != 0
+    }
+
+    fn store_move_record (&mut self, piece_type: PieceType, from: usize, to: usize) {
+        let mut m = Move::new(from, to, None);
+        m.en_passant = piece_type.is_pawn() && PIECE[to] == self.en_passant_square;
+        m.capture = self.all_pieces & PIECE[to] != 0 || m.en_passant;
+        m.castling = piece_type.is_king() && from.abs_diff(to) == 2;
+        m.double_push = piece_type.is_pawn() && from.abs_diff(to) == 16;
+        if (piece_type == PieceType::WhitePawn && to / 8 == 7) ||
+            (piece_type == PieceType::BlackPawn && to / 8 == 0) {
+            m.promotion = Some(self.promotion_piece);
         }
 
-        // Change player turn
-        self.whites_turn = !self.whites_turn;
+        self.history.push(MoveRecord {
+            m,
+            pieces: self.piece_bitboards(),
+            whites_turn: self.whites_turn,
+            game_result: self.game_result,
+            termination: self.termination,
+            castling_rights: self.castling_rights,
+            halfmove_clock: self.halfmove_clock,
+            fullmove: self.fullmove,
+            player_in_check: self.player_in_check,
+            en_passant_square: self.en_passant_square,
+            hash: self.hash,
+            positions_len: self.positions.len(),
+            clock: self.clock.as_ref().map(Clock::state),
+        });
+
+        // A new move makes the undone moves impossible to redo
+        self.redo_stack.clear();
+    }
 
+    /// Takes back the last move made, restoring the exact state of the game before it
+    /// 
+    /// Restores captured pieces, the rook moved by castling, the pawn taken en passant, castling rights, move counters, chess.clock, chess.game_result, chess.termination and chess.player_in_check
+    /// 
+    /// Returns Some(m) with the move (Move) that was taken back, or None if no moves have been made
+    /// 
+    /// Unlike chess.undo(), the move can't be redone with chess.redo()
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// chess.move_piece(12, 28).unwrap();
+    /// 
+    /// let m = chess.unmake_move().unwrap();
+    /// assert_eq!((m.from, m.to), (12, 28));
+    /// assert_eq!(chess.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    /// ```
+    /// 
+    pub fn unmake_move (&mut self) -> Option<Move> {
+        let record = self.history.pop()?;
+
+        self.white_pawns = record.pieces[0];
+        self.white_knights = record.pieces[1];
+        self.white_bishops = record.pieces[2];
+        self.white_rooks = record.pieces[3];
+        self.white_queens = record.pieces[4];
+        self.white_kings = record.pieces[5];
+        self.black_pawns = record.pieces[6];
+        self.black_knights = record.pieces[7];
+        self.black_bishops = record.pieces[8];
+        self.black_rooks = record.pieces[9];
+        self.black_queens = record.pieces[10];
+        self.black_kings = record.pieces[11];
+
+        self.whites_turn = record.whites_turn;
+        self.game_result = record.game_result;
+        self.termination = record.termination;
+        self.castling_rights = record.castling_rights;
+        self.halfmove_clock = record.halfmove_clock;
+        self.fullmove = record.fullmove;
+        self.player_in_check = record.player_in_check;
+        self.en_passant_square = record.en_passant_square;
+        self.hash = record.hash;
+        self.positions.truncate(record.positions_len);
         self.promotion_piece = PieceType::Empty;
+        self.restore_clock(record.clock);
 
-        // Detect if player is in check
-        self.player_in_check = false;
-        if self.whites_turn && self.white_in_check(None, None) {
-            self.player_in_check = true;
-        }
-        if !self.whites_turn && self.black_in_check(None, None) {
-            self.player_in_check = true;
+        self.update_derived_boards();
+
+        Some(record.m)
+    }
+
+    /// Takes back the last move made (like chess.unmake_move()), and stores it so it can be made again with chess.redo()
+    /// 
+    /// Returns Some(m) with the move (Move) that was taken back, or None if no moves have been made
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// chess.move_piece(12, 28).unwrap();
+    /// chess.move_piece(52, 36).unwrap();
+    /// 
+    /// // Go back two moves and forward again
+    /// chess.undo();
+    /// chess.undo();
+    /// assert!(chess.undo().is_none());
+    /// chess.redo();
+    /// chess.redo();
+    /// assert_eq!(chess.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPP1PPP/RNBQKBNR w KQkq e6 0 2");
+    /// ```
+    /// 
+    pub fn undo (&mut self) -> Option<Move> {
+        let clock = self.clock.as_ref().map(Clock::state);
+        let m = self.unmake_move()?;
+        self.redo_stack.push((m, clock));
+        Some(m)
+    }
+
+    /// Makes the last move taken back by chess.undo() again, and sets chess.clock back to how it was after the move
+    /// 
+    /// Returns Some(m) with the move (Move) that was made, or None if there are no moves to redo
+    /// 
+    /// Making any other move (with chess.move_piece(...) etc.) removes all moves that could be redone
+    /// 
+    pub fn redo (&mut self) -> Option<Move> {
+        let (m, clock) = self.redo_stack.pop()?;
+
+        // Keep the rest of the moves to redo, since making a move clears them
+        let redo_stack = std::mem::take(&mut self.redo_stack);
+        let result = self.make_move(m);
+        self.redo_stack = redo_stack;
+
+        match result {
+            Ok(()) => {
+                self.restore_clock(clock);
+                Some(m)
+            },
+            Err(_) => None
         }
+    }
 
-        return Ok(true);
+    /*
+    Sets the clock back to a stored state, a game that was not timed at the time has its clock removed
+    */
+    fn restore_clock (&mut self, state: Option<ClockState>) {
+        match (self.clock.as_mut(), state) {
+            (Some(clock), Some(state)) => clock.restore(state),
+            _ => self.clock = None
+        }
     }
 
     fn update_board_after_move (&mut self, piece_type: PieceType, from: usize, to: usize) {
+        // Remove the pieces on both squares from the hash
+        let squares = PIECE[from] | PIECE[to];
+        for (i, pieces) in self.piece_bitboards().iter().enumerate() {
+            let mut removed = pieces & squares;
+            while removed != 0 {
+                self.hash ^= ZOBRIST.pieces[i][removed.trailing_zeros() as usize];
+                removed &= removed - 1;
+            }
+        }
+
         self.white_pawns &= !PIECE[to] & !PIECE[from];
         self.white_knights &= !PIECE[to] & !PIECE[from];
         self.white_bishops &= !PIECE[to] & !PIECE[from];
@@ -1169,6 +2138,11 @@ impl ChessBoard {
             PieceType::BlackKing => {  self.black_kings |= PIECE[to]; },
             _ => panic!("No Piece Type")
         }
+
+        // Add the moved piece to the hash
+        if to < 64 {
+            self.hash ^= ZOBRIST.pieces[piece_type as usize][to];
+        }
     }
     
     /// Get the number of possible moves for the current player in a position
@@ -1186,21 +2160,19 @@ impl ChessBoard {
     /// 
     pub fn count_moves(&self) -> usize {
         let own_pieces = if self.whites_turn { self.white_pieces } else { self.black_pieces };
+        let legality = self.legality(self.whites_turn);
         let mut count = 0;
 
-        for i in 0..64 {
-            if own_pieces & PIECE[i] != 0 {
-                let moves = self.get_moves(i);
-                
-                // Count promotion extra times
-                if self.piece_at(i) == PieceType::WhitePawn && i / 8 == 6 {
-                    count += bit_count(moves & MASK_RANK[7]) * 3;
-                }
-                if self.piece_at(i) == PieceType::BlackPawn && i / 8 == 1 {
-                    count += bit_count(moves & MASK_RANK[0]) * 3;
-                }
-                count += bit_count(moves);
+        for i in squares(own_pieces) {
+            let moves = self.legal_targets(i, &legality);
+            // Count promotion extra times
+            if self.piece_at(i) == PieceType::WhitePawn && i / 8 == 6 {
+                count += bit_count(moves & MASK_RANK[7]) * 3;
+            }
+            if self.piece_at(i) == PieceType::BlackPawn && i / 8 == 1 {
+                count += bit_count(moves & MASK_RANK[0]) * 3;
             }
+            count += bit_count(moves);
         }
 
         count
@@ -1225,9 +2197,8 @@ impl ChessBoard {
     /// 
     /// Loading a FEN-string resets the games state (chess.game_result, ...)
     /// 
-    /// If a bad FEN-string is passed the game in the best case be cleared, and in the worst case crash
+    /// If a bad FEN-string is passed the unreadable parts are skipped, use ChessBoard::from_fen to get an error instead
     /// 
-    
     pub fn load (&mut self, fen: String) {
         // Clear the entire board
         self.clear();
@@ -1249,10 +2220,9 @@ impl ChessBoard {
             for s in row_char.iter() {
                 let pos = y*8+x;
                 
-                /*
-                TODO!!
-                Implement safe-guard system for bad FEN strings, (check chars and such)
-                 */ 
+                // Skip pieces outside of the board
+                if pos > 63 { break; }
+
                 match s {   
                     /* Add Black Piece from FEN */
                     'p' => self.black_pawns |= PIECE[pos],
@@ -1269,7 +2239,9 @@ impl ChessBoard {
                     'Q' => self.white_queens |= PIECE[pos],
                     'K' => self.white_kings |= PIECE[pos],
                     /* Read amount of empty space from FEN */
-                    _ => x += (*s as usize) - ('0') as usize - 1
+                    '1'..='8' => x += (*s as usize) - ('0') as usize - 1,
+                    /* Ignore unknown characters */
+                    _ => continue
                 }
                 x += 1;
             }
@@ -1287,18 +2259,13 @@ impl ChessBoard {
         // Read castling rights
         self.castling_rights = (false, false, false, false);
         if fen_vec.len() >= 3 {
-            if fen_vec[2].chars().nth(0).unwrap_or('-') == 'K' && self.white_kings & PIECE[4] != 0 && self.white_rooks & PIECE[7] != 0 { 
-                self.castling_rights.0 = true; 
-            }
-            if fen_vec[2].chars().nth(1).unwrap_or('-') == 'Q' && self.white_kings & PIECE[4] != 0 && self.white_rooks & PIECE[0] != 0 { 
-                self.castling_rights.1 = true; 
-            }
-            if fen_vec[2].chars().nth(2).unwrap_or('-') == 'k' && self.black_kings & PIECE[8*7+4] != 0 && self.black_rooks & PIECE[8*7+7] != 0 { 
-                self.castling_rights.2 = true; 
-            }
-            if fen_vec[2].chars().nth(3).unwrap_or('-') == 'q' && self.black_kings & PIECE[8*7+4] != 0 && self.black_rooks & PIECE[8*7+0] != 0 { 
-                self.castling_rights.3 = true; 
-            }
+            self.castling_rights = (
+                fen_vec[2].contains('K'),
+                fen_vec[2].contains('Q'),
+                fen_vec[2].contains('k'),
+                fen_vec[2].contains('q')
+            );
+            self.remove_invalid_castling_rights();
         }
 
         // Read en passant square
@@ -1320,107 +2287,575 @@ impl ChessBoard {
         }
 
         self.game_result = GameResult::Ongoing;
+        self.termination = None;
 
         // Update the derived boards
         self.update_board();
 
         // Store position
+        self.hash = self.compute_hash();
         self.store_position();
     }
 
-    
-    /// Ends the game by white surrendering
-    /// 
-    /// # Examples
-    /// 
-    /// ```
-    /// use davbjor_chess::{ChessBoard, GameResult};
+    /// Creates a game from a FEN-string, checking every field of the string
     /// 
-    /// // create a new game
-    /// let mut chess = ChessBoard::new();
+    /// Returns Ok(chess) with the position of the FEN-string
     /// 
-    /// // Surrender the game for white
-    /// chess.white_surrender();
+    /// Returns Err(e) if the FEN-string is malformed, where e (FenError) gives which field was wrong and at which character
     /// 
-    /// if chess.game_result != GameResult::Black {
-    ///     println!("White did not surrender, something wierd is going on!");
-    /// }
-    /// ```
+    /// The halfmove clock and fullmove count may be left out, and then default to 0 and 1
     /// 
-    pub fn white_surrender(&mut self){
-        self.game_result = GameResult::Black;
-    }
-
-    /// Ends the game by black surrendering
+    /// Castling rights are only kept if the king and the rook are still on their starting squares
     /// 
     /// # Examples
     /// 
     /// ```
-    /// use davbjor_chess::{ChessBoard, GameResult};
-    /// 
-    /// // create a new game
-    /// let mut chess = ChessBoard::new();
+    /// use davbjor_chess::{ChessBoard, FenError};
     /// 
-    /// // Surrender the game for black
-    /// chess.black_surrender();
+    /// let chess = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R b Kq - 0 1").unwrap();
+    /// assert_eq!(chess.castling_rights, (true, false, false, true));
     /// 
-    /// if chess.game_result != GameResult::White {
-    ///     println!("Black did not surrender, something wierd is going on!");
-    /// }
+    /// // Rank with 9 files
+    /// assert_eq!(
+    ///     ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R1 w KQkq - 0 1").err(),
+    ///     Some(FenError::WrongFileCount { found: 9, index: 23 })
+    /// );
     /// ```
     /// 
-    pub fn black_surrender(&mut self){
-        self.game_result = GameResult::White;
-    }
+    pub fn from_fen(fen: &str) -> Result<ChessBoard, FenError> {
+        let chars: Vec<char> = fen.chars().collect();
+
+        // Split FEN into fields, storing the index of the first character of every field
+        let mut fields: Vec<(usize, String)> = vec![];
+        let mut i = 0;
+        while i < chars.len() {
+            if chars[i].is_whitespace() { i += 1; continue; }
+            let start = i;
+            while i < chars.len() && !chars[i].is_whitespace() { i += 1; }
+            fields.push((start, chars[start..i].iter().collect()));
+        }
 
-    /// Ends the game by draw (if both players want it)
-    /// 
-    /// # Examples
-    /// 
-    /// ```
-    /// use davbjor_chess::{ChessBoard, GameResult};
-    /// 
-    /// // create a new game
-    /// let mut chess = ChessBoard::new();
-    /// 
-    /// // End game by draw
-    /// chess.mutual_draw();
-    /// 
-    /// if chess.game_result != GameResult::Draw {
-    ///     println!("The game is not a tie, something wierd is going on!");
-    /// }
-    /// ```
-    /// 
-    pub fn mutual_draw(&mut self){
-        self.game_result = GameResult::Draw;
-    }
+        let field_order = [
+            FenField::Placement,
+            FenField::PlayerTurn,
+            FenField::CastlingRights,
+            FenField::EnPassant
+        ];
+        for (n, field) in field_order.iter().enumerate() {
+            if fields.len() <= n {
+                return Err(FenError::MissingField { field: *field, index: chars.len() });
+            }
+        }
+        if fields.len() == 5 {
+            return Err(FenError::MissingField { field: FenField::FullmoveCount, index: chars.len() });
+        }
+        if fields.len() > 6 {
+            return Err(FenError::TooManyFields { index: fields[6].0 });
+        }
+
+        let mut chess = ChessBoard::default();
+
+        // Read the piece placement from the top rank down
+        let (start, placement) = &fields[0];
+        let ranks: Vec<&str> = placement.split('/').collect();
+        if ranks.len() != 8 {
+            return Err(FenError::WrongRankCount { found: ranks.len(), index: *start });
+        }
+        let mut index = *start;
+        for (n, rank) in ranks.iter().enumerate() {
+            let y = 7 - n;
+            let mut x: usize = 0;
+            for c in rank.chars() {
+                let piece_type = match c {
+                    'P' => PieceType::WhitePawn,
+                    'N' => PieceType::WhiteKnight,
+                    'B' => PieceType::WhiteBishop,
+                    'R' => PieceType::WhiteRook,
+                    'Q' => PieceType::WhiteQueen,
+                    'K' => PieceType::WhiteKing,
+                    'p' => PieceType::BlackPawn,
+                    'n' => PieceType::BlackKnight,
+                    'b' => PieceType::BlackBishop,
+                    'r' => PieceType::BlackRook,
+                    'q' => PieceType::BlackQueen,
+                    'k' => PieceType::BlackKing,
+                    '1'..='8' => PieceType::Empty,
+                    _ => return Err(FenError::InvalidPiece { character: c, index })
+                };
+
+                if piece_type == PieceType::Empty {
+                    x += (c as usize) - ('0' as usize);
+                }
+                else {
+                    if x < 8 {
+                        chess.update_board_after_move(piece_type, 64, y*8+x);
+                    }
+                    x += 1;
+                }
+                if x > 8 {
+                    return Err(FenError::WrongFileCount { found: x, index });
+                }
+                index += 1;
+            }
+            if x != 8 {
+                return Err(FenError::WrongFileCount { found: x, index });
+            }
+            // Skip the '/'
+            index += 1;
+        }
+
+        // Read player turn
+        let (start, turn) = &fields[1];
+        chess.whites_turn = match turn.as_str() {
+            "w" => true,
+            "b" => false,
+            _ => return Err(FenError::InvalidPlayerTurn { index: *start })
+        };
+
+        // Read castling rights, the letters can come in any order
+        let (start, castling) = &fields[2];
+        chess.castling_rights = (false, false, false, false);
+        if castling != "-" {
+            for (n, c) in castling.chars().enumerate() {
+                let right = match c {
+                    'K' => &mut chess.castling_rights.0,
+                    'Q' => &mut chess.castling_rights.1,
+                    'k' => &mut chess.castling_rights.2,
+                    'q' => &mut chess.castling_rights.3,
+                    _ => return Err(FenError::InvalidCastlingRights { character: c, index: start + n })
+                };
+                if *right {
+                    return Err(FenError::InvalidCastlingRights { character: c, index: start + n });
+                }
+                *right = true;
+            }
+        }
+        chess.remove_invalid_castling_rights();
+
+        // Read en passant square
+        let (start, en_passant) = &fields[3];
+        if en_passant != "-" {
+            let sq = string_to_square(en_passant.to_string());
+            if sq == 64 || (sq / 8 != 2 && sq / 8 != 5) || en_passant.chars().any(|c| c.is_uppercase()) {
+                return Err(FenError::InvalidEnPassant { index: *start });
+            }
+            chess.en_passant_square = PIECE[sq];
+        }
+
+        // Read halfmove clock and fullmove count
+        if fields.len() == 6 {
+            let (start, halfmove) = &fields[4];
+            chess.halfmove_clock = match halfmove.parse::<i32>() {
+                Ok(n) if n >= 0 && halfmove.chars().all(|c| c.is_ascii_digit()) => n,
+                _ => return Err(FenError::InvalidHalfmoveClock { index: *start })
+            };
+
+            let (start, fullmove) = &fields[5];
+            chess.fullmove = match fullmove.parse::<i32>() {
+                Ok(n) if n >= 1 && fullmove.chars().all(|c| c.is_ascii_digit()) => n,
+                _ => return Err(FenError::InvalidFullmoveCount { index: *start })
+            };
+        }
+
+        // Update the derived boards
+        chess.update_board();
+
+        // Detect if player is in check
+        chess.player_in_check = if chess.whites_turn {
+            chess.white_in_check(None, None)
+        } else {
+            chess.black_in_check(None, None)
+        };
+
+        // Store position
+        chess.hash = chess.compute_hash();
+        chess.store_position();
+
+        Ok(chess)
+    }
+
+    /// Creates a game from a FEN-string like ChessBoard::from_fen, but also rejects positions that break the rules of chess
+    /// 
+    /// Returns Err(FenError::IllegalPosition(errors)) with the list from ChessBoard::validate if the position is illegal
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard, FenError, PositionError};
+    /// 
+    /// assert!(ChessBoard::from_fen_legal("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1").is_ok());
+    /// 
+    /// // Two white kings
+    /// assert_eq!(
+    ///     ChessBoard::from_fen_legal("k7/8/8/8/8/8/8/K6K w - - 0 1").err(),
+    ///     Some(FenError::IllegalPosition(vec![PositionError::TooManyWhiteKings]))
+    /// );
+    /// ```
+    /// 
+    pub fn from_fen_legal(fen: &str) -> Result<ChessBoard, FenError> {
+        let chess = ChessBoard::from_fen(fen)?;
+
+        let errors = chess.validate();
+        if !errors.is_empty() {
+            return Err(FenError::IllegalPosition(errors));
+        }
+
+        Ok(chess)
+    }
+
+    /// Checks if the current position is allowed by the rules of chess
+    /// 
+    /// Returns a list of every rule the position breaks (as the enum PositionError), the list is empty if the position is legal
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard, PositionError};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// assert!(chess.validate().is_empty());
+    /// 
+    /// // White pawn on the first rank, and black is in check when it is whites turn
+    /// chess.load("4k3/8/8/8/8/8/8/P3Q2K w - - 0 1".to_string());
+    /// assert_eq!(chess.validate(), vec![PositionError::PawnOnBackRank { square: 0 }, PositionError::OpponentInCheck]);
+    /// ```
+    /// 
+    pub fn validate(&self) -> Vec<PositionError> {
+        let mut errors: Vec<PositionError> = vec![];
+
+        // Both sides need exactly one king
+        match bit_count(self.white_kings) {
+            0 => errors.push(PositionError::MissingWhiteKing),
+            1 => (),
+            _ => errors.push(PositionError::TooManyWhiteKings)
+        }
+        match bit_count(self.black_kings) {
+            0 => errors.push(PositionError::MissingBlackKing),
+            1 => (),
+            _ => errors.push(PositionError::TooManyBlackKings)
+        }
+
+        // Pawn and piece count
+        if bit_count(self.white_pawns) > 8 { errors.push(PositionError::TooManyWhitePawns); }
+        if bit_count(self.black_pawns) > 8 { errors.push(PositionError::TooManyBlackPawns); }
+        if bit_count(self.white_pieces) > 16 { errors.push(PositionError::TooManyWhitePieces); }
+        if bit_count(self.black_pieces) > 16 { errors.push(PositionError::TooManyBlackPieces); }
+
+        // Pawns can never stand on the first or last rank
+        let pawns_on_back_rank = (self.white_pawns | self.black_pawns) & (MASK_RANK[0] | MASK_RANK[7]);
+        for i in squares(pawns_on_back_rank) {
+            errors.push(PositionError::PawnOnBackRank { square: i });
+        }
+
+        // The player who just moved can't have left their king in check
+        if self.whites_turn && self.black_in_check(None, None) {
+            errors.push(PositionError::OpponentInCheck);
+        }
+        if !self.whites_turn && self.white_in_check(None, None) {
+            errors.push(PositionError::OpponentInCheck);
+        }
+
+        // En passant square has to be right behind a pawn that just moved two squares
+        if self.en_passant_square != 0 {
+            let square = bit_scan(self.en_passant_square);
+            let valid = if self.whites_turn {
+                square / 8 == 5
+                    && self.black_pawns & PIECE[square - 8] != 0
+                    && self.all_pieces & (PIECE[square] | PIECE[square + 8]) == 0
+            } else {
+                square / 8 == 2
+                    && self.white_pawns & PIECE[square + 8] != 0
+                    && self.all_pieces & (PIECE[square] | PIECE[square - 8]) == 0
+            };
+            if !valid {
+                errors.push(PositionError::InvalidEnPassant { square });
+            }
+        }
+
+        errors
+    }
+
+    // Remove castling rights where the king or the rook is not on its starting square
+    fn remove_invalid_castling_rights(&mut self) {
+        if self.white_kings & PIECE[4] == 0 || self.white_rooks & PIECE[7] == 0 {
+            self.castling_rights.0 = false;
+        }
+        if self.white_kings & PIECE[4] == 0 || self.white_rooks & PIECE[0] == 0 {
+            self.castling_rights.1 = false;
+        }
+        if self.black_kings & PIECE[8*7+4] == 0 || self.black_rooks & PIECE[8*7+7] == 0 {
+            self.castling_rights.2 = false;
+        }
+        if self.black_kings & PIECE[8*7+4] == 0 || self.black_rooks & PIECE[8*7 ] == 0 {
+            self.castling_rights.3 = false;
+        }
+    }
+
+    /// Exports the current position of the game as a FEN-string
+    /// 
+    /// All six fields are written: piece placement, player turn, castling rights, en passant square, halfmove clock and fullmove count
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    /// 
+    /// // create a new game
+    /// let mut chess = ChessBoard::new();
+    /// 
+    /// assert_eq!(chess.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+    /// 
+    /// // Make a move and export the new position
+    /// chess.move_piece(12, 28).unwrap();
+    /// assert_eq!(chess.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+    /// ```
+    /// 
+    pub fn to_fen(&self) -> String {
+        let mut fen = String::new();
+
+        // Write the position from the top rank down, with files from left to right
+        for y in (0..8).rev() {
+            let mut empty = 0;
+            for x in 0..8 {
+                let c = match self.piece_at(y*8+x) {
+                    PieceType::WhitePawn => 'P',
+                    PieceType::WhiteKnight => 'N',
+                    PieceType::WhiteBishop => 'B',
+                    PieceType::WhiteRook => 'R',
+                    PieceType::WhiteQueen => 'Q',
+                    PieceType::WhiteKing => 'K',
+                    PieceType::BlackPawn => 'p',
+                    PieceType::BlackKnight => 'n',
+                    PieceType::BlackBishop => 'b',
+                    PieceType::BlackRook => 'r',
+                    PieceType::BlackQueen => 'q',
+                    PieceType::BlackKing => 'k',
+                    PieceType::Empty => { empty += 1; continue; }
+                };
+                if empty > 0 {
+                    fen.push_str(&empty.to_string());
+                    empty = 0;
+                }
+                fen.push(c);
+            }
+            if empty > 0 { fen.push_str(&empty.to_string()); }
+            if y > 0 { fen.push('/'); }
+        }
+
+        // Write player turn
+        fen.push_str(if self.whites_turn { " w " } else { " b " });
+
+        // Write castling rights
+        let mut castling = String::new();
+        if self.castling_rights.0 { castling.push('K'); }
+        if self.castling_rights.1 { castling.push('Q'); }
+        if self.castling_rights.2 { castling.push('k'); }
+        if self.castling_rights.3 { castling.push('q'); }
+        if castling.is_empty() { castling.push('-'); }
+        fen.push_str(&castling);
+
+        // Write en passant square
+        fen.push(' ');
+        if self.en_passant_square != 0 {
+            fen.push_str(&square_to_string(bit_scan(self.en_passant_square)));
+        }
+        else {
+            fen.push('-');
+        }
+
+        // Write halfmove clock and fullmove count
+        fen.push_str(&format!(" {} {}", self.halfmove_clock, self.fullmove));
+
+        fen
+    }
+
+    
+    /// Ends the game by white surrendering
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard, GameResult};
+    /// 
+    /// // create a new game
+    /// let mut chess = ChessBoard::new();
+    /// 
+    /// // Surrender the game for white
+    /// chess.white_surrender();
+    /// 
+    /// if chess.game_result != GameResult::Black {
+    ///     println!("White did not surrender, something wierd is going on!");
+    /// }
+    /// ```
+    /// 
+    pub fn white_surrender(&mut self){
+        self.game_result = GameResult::Black;
+        self.termination = Some(Termination::Resignation);
+    }
+
+    /// Ends the game by black surrendering
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard, GameResult};
+    /// 
+    /// // create a new game
+    /// let mut chess = ChessBoard::new();
+    /// 
+    /// // Surrender the game for black
+    /// chess.black_surrender();
+    /// 
+    /// if chess.game_result != GameResult::White {
+    ///     println!("Black did not surrender, something wierd is going on!");
+    /// }
+    /// ```
+    /// 
+    pub fn black_surrender(&mut self){
+        self.game_result = GameResult::White;
+        self.termination = Some(Termination::Resignation);
+    }
+
+    /// Ends the game by a player (white if true) leaving it unfinished, the other player wins
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard, GameResult, Termination};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// 
+    /// // Black leaves the game
+    /// chess.abandon(false);
+    /// assert_eq!(chess.game_result, GameResult::White);
+    /// assert_eq!(chess.termination, Some(Termination::Abandoned));
+    /// ```
+    /// 
+    pub fn abandon(&mut self, white: bool){
+        self.game_result = if white { GameResult::Black } else { GameResult::White };
+        self.termination = Some(Termination::Abandoned);
+    }
+
+    /// Checks if the player whos turn it is can claim a draw, because the position has appeared three times or 50 moves have been made by each player without a capture or pawn move
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// assert!(!chess.can_claim_draw());
+    /// 
+    /// chess.load("4k3/8/8/8/8/8/8/R3K3 w - - 100 80".to_string());
+    /// assert!(chess.can_claim_draw());
+    /// ```
+    /// 
+    pub fn can_claim_draw(&self) -> bool {
+        if self.game_result != GameResult::Ongoing { return false; }
+        self.is_three_fold_repetition() || self.halfmove_clock >= 100
+    }
+
+    /// Ends the game by draw, claimed by the player whos turn it is (see chess.can_claim_draw())
+    /// 
+    /// Returns Ok(()) and sets chess.termination to Termination::ThreefoldRepetition or Termination::FiftyMoveRule if the draw could be claimed
+    /// 
+    /// Returns Err(e) without changing the game if it can't be claimed, and gives the reason e (MoveError)
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard, GameResult, Termination};
+    /// 
+    /// let mut chess = ChessBoard::new();
+    /// assert!(chess.claim_draw().is_err());
+    /// 
+    /// chess.apply_uci_moves(&["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1", "f6g8"]).unwrap();
+    /// assert!(chess.claim_draw().is_ok());
+    /// assert_eq!(chess.game_result, GameResult::Draw);
+    /// assert_eq!(chess.termination, Some(Termination::ThreefoldRepetition));
+    /// ```
+    /// 
+    pub fn claim_draw(&mut self) -> Result<(), MoveError> {
+        if self.game_result != GameResult::Ongoing {
+            return Err(MoveError::GameOver);
+        }
+
+        if self.is_three_fold_repetition() {
+            self.termination = Some(Termination::ThreefoldRepetition);
+        }
+        else if self.halfmove_clock >= 100 {
+            self.termination = Some(Termination::FiftyMoveRule);
+        }
+        else {
+            return Err(MoveError::NoDrawToClaim);
+        }
+
+        self.game_result = GameResult::Draw;
+        Ok(())
+    }
+
+    /// Ends the game by draw (if both players want it)
+    /// 
+    /// # Examples
+    /// 
+    /// ```
+    /// use davbjor_chess::{ChessBoard, GameResult};
+    /// 
+    /// // create a new game
+    /// let mut chess = ChessBoard::new();
+    /// 
+    /// // End game by draw
+    /// chess.mutual_draw();
+    /// 
+    /// if chess.game_result != GameResult::Draw {
+    ///     println!("The game is not a tie, something wierd is going on!");
+    /// }
+    /// ```
+    /// 
+    pub fn mutual_draw(&mut self){
+        self.game_result = GameResult::Draw;
+        self.termination = Some(Termination::Agreement);
+    }
 
     // Updates the derived boards
     fn update_board (&mut self) {
-        self.white_pieces = self.white_pawns | self.white_knights | self.white_bishops | self.white_rooks | self.white_queens | self.white_kings;
-        self.black_pieces = self.black_pawns | self.black_knights | self.black_bishops | self.black_rooks | self.black_queens | self.black_kings;
-        self.all_pieces = self.white_pieces | self.black_pieces;
+        self.update_derived_boards();
 
         // Check if board is in checkmate / stalemate
         if self.black_in_checkmate() {
             self.game_result = GameResult::White;
+            self.termination = Some(Termination::Checkmate);
         }
         if self.white_in_checkmate() {
             self.game_result = GameResult::Black;
+            self.termination = Some(Termination::Checkmate);
         }
-        if self.black_in_stalemate().is_ok() {
+        if self.black_in_stalemate() {
             self.game_result = GameResult::Draw;
+            self.termination = Some(Termination::Stalemate);
         }
         
-        if self.white_in_stalemate().is_ok() {
+        if self.white_in_stalemate() {
             self.game_result = GameResult::Draw;
+            self.termination = Some(Termination::Stalemate);
         }
 
-        for i in 0..64 {
-            self.board[i] = PieceType::Empty;
-            if self.all_pieces & PIECE[i] != 0 {
-                self.board[i] = self.piece_at(i);
-            }
+        // Neither player can checkmate
+        if self.game_result == GameResult::Ongoing && self.is_insufficient_material() {
+            self.game_result = GameResult::Draw;
+            self.termination = Some(Termination::InsufficientMaterial);
+        }
+    }
+
+    // Updates the derived bitboards and the board of PieceType's from the piece bitboards
+    fn update_derived_boards (&mut self) {
+        self.white_pieces = self.white_pawns | self.white_knights | self.white_bishops | self.white_rooks | self.white_queens | self.white_kings;
+        self.black_pieces = self.black_pawns | self.black_knights | self.black_bishops | self.black_rooks | self.black_queens | self.black_kings;
+        self.all_pieces = self.white_pieces | self.black_pieces;
+
+        self.board.fill(PieceType::Empty);
+        for i in squares(self.all_pieces) {
+            self.board[i] = self.piece_at(i);
         }
     }
 
@@ -1446,7 +2881,7 @@ impl ChessBoard {
     }
 
     pub fn print_board(&self, b: BitBoard){
-        print!("\n");
+        println!();
         for y in (0..8).rev() {
             print!("{}   ", y+1);
             for x in 0..8 {
@@ -1471,17 +2906,19 @@ impl ChessBoard {
                     print!(". ");
                 }
             }
-            print!("\n");
+            println!();
         }
-        print!("\n");
-        print!("    A B C D E F G H\n");
-        print!("-------------------\n");
+        println!();
+        println!("    A B C D E F G H");
+        println!("-------------------");
     }
 }
 
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::compute::patterns::{compute_bishop_attacks_by_rays, compute_rook_attacks_by_rays};
+    use crate::lookup::tables::{MASK_FILE, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, BETWEEN, LINE, BISHOP_MAGICS, ROOK_MAGICS};
 
     #[test]
     fn castling() {
@@ -1491,14 +2928,14 @@ mod tests {
         //chess.print_board(0);
 
         // Cant castle into check
-        //chess.print_board(chess.get_moves(SQUARE::E1));
-        assert!(chess.move_piece(SQUARE::E1, SQUARE::C1).is_err());
-        assert!(chess.move_piece(SQUARE::E1, SQUARE::G1).is_ok());
+        //chess.print_board(chess.get_moves(Square::E1));
+        assert!(chess.move_piece(Square::E1, Square::C1).is_err());
+        assert!(chess.move_piece(Square::E1, Square::G1).is_ok());
         
         // Cant castle through check
-        //chess.print_board(chess.get_moves(SQUARE::E8));
-        assert!(chess.move_piece(SQUARE::E8, SQUARE::G8).is_err());
-        assert!(chess.move_piece(SQUARE::E8, SQUARE::C8).is_ok());
+        //chess.print_board(chess.get_moves(Square::E8));
+        assert!(chess.move_piece(Square::E8, Square::G8).is_err());
+        assert!(chess.move_piece(Square::E8, Square::C8).is_ok());
 
         //chess.print_board(0);
     }
@@ -1533,11 +2970,11 @@ mod tests {
     fn white_in_check() {
         let mut chess = ChessBoard::new();
         chess.load("2k5/8/4q3/8/6b1/1n6/1PPP4/3KR3".to_string());
-        assert_eq!(chess.white_in_check(None, None), true);
+        assert!(chess.white_in_check(None, None));
         assert_eq!(chess.game_result, GameResult::Ongoing);
 
         chess.load("k6q/8/8/8/8/8/8/7K".to_string());
-        assert_eq!(chess.white_in_check(None, None), true);
+        assert!(chess.white_in_check(None, None));
         assert_eq!(chess.game_result, GameResult::Ongoing);
     }
 
@@ -1550,12 +2987,12 @@ mod tests {
     fn checkmate_bug() {
         let mut chess = ChessBoard::new();
 
-        assert!(chess.move_piece(SQUARE::E2, SQUARE::E4).is_ok());
-        assert!(chess.move_piece(SQUARE::E7, SQUARE::E5).is_ok());
-        assert!(chess.move_piece(SQUARE::F1, SQUARE::C4).is_ok());
-        assert!(chess.move_piece(SQUARE::A7, SQUARE::A5).is_ok());
-        assert!(chess.move_piece(SQUARE::C4, SQUARE::F7).is_ok());
-        //chess.print_board(chess.get_moves(SQUARE::E8));
+        assert!(chess.move_piece(Square::E2, Square::E4).is_ok());
+        assert!(chess.move_piece(Square::E7, Square::E5).is_ok());
+        assert!(chess.move_piece(Square::F1, Square::C4).is_ok());
+        assert!(chess.move_piece(Square::A7, Square::A5).is_ok());
+        assert!(chess.move_piece(Square::C4, Square::F7).is_ok());
+        //chess.print_board(chess.get_moves(Square::E8));
         assert_eq!(chess.game_result, GameResult::Ongoing);
 
     }
@@ -1566,24 +3003,24 @@ mod tests {
         chess.load("rnbqkbnr/1p3p1p/8/P1PpP1P1/p1p1p1pP/8/1P1P1P2/RNBQKBNR w KQkq d6 0 1".to_string());
         
         // White can do en passant at d6 (due to fen string recording d6)
-            //chess.print_board(chess.get_moves(SQUARE::E5));
-        assert!(chess.move_piece(SQUARE::E5, SQUARE::D6).is_ok());
-        assert_eq!(chess.piece_at(SQUARE::D5), PieceType::Empty);
+            //chess.print_board(chess.get_moves(Square::E5));
+        assert!(chess.move_piece(Square::E5, Square::D6).is_ok());
+        assert_eq!(chess.piece_at(Square::D5), PieceType::Empty);
         
         // Black cant do en passant at h3 (due to that move not being made last turn)
-            //chess.print_board(chess.get_moves(SQUARE::G4));
-        assert!(chess.move_piece(SQUARE::G4, SQUARE::H3).is_err());
-        assert_eq!(chess.piece_at(SQUARE::H4), PieceType::WhitePawn);
+            //chess.print_board(chess.get_moves(Square::G4));
+        assert!(chess.move_piece(Square::G4, Square::H3).is_err());
+        assert_eq!(chess.piece_at(Square::H4), PieceType::WhitePawn);
 
         // Move white to allow en passant at b3
-        assert!(chess.move_piece(SQUARE::G4, SQUARE::G3).is_ok());
-        assert!(chess.move_piece(SQUARE::B2, SQUARE::B4).is_ok());
+        assert!(chess.move_piece(Square::G4, Square::G3).is_ok());
+        assert!(chess.move_piece(Square::B2, Square::B4).is_ok());
 
         // Both pawn A4, and pawn C4 should be able to do en passant at B#
-            //chess.print_board(chess.get_moves(SQUARE::A4));
-            //chess.print_board(chess.get_moves(SQUARE::C4));
-        assert!(chess.move_piece(SQUARE::A4, SQUARE::B3).is_ok());
-        assert_eq!(chess.piece_at(SQUARE::B4), PieceType::Empty);
+            //chess.print_board(chess.get_moves(Square::A4));
+            //chess.print_board(chess.get_moves(Square::C4));
+        assert!(chess.move_piece(Square::A4, Square::B3).is_ok());
+        assert_eq!(chess.piece_at(Square::B4), PieceType::Empty);
 
         //chess.print_board(0);
     }
@@ -1596,81 +3033,666 @@ mod tests {
 
         chess.load("3r3k/1p2P1pp/8/p7/8/5NK1/1qp3PP/8 w - - 0 39".to_string());
         assert_eq!(chess.count_moves(), 22);
-        assert!(chess.handle_promotion(SQUARE::E7, SQUARE::D8, PieceType::WhiteQueen).is_ok());
+        assert!(chess.handle_promotion(Square::E7, Square::D8, PieceType::WhiteQueen).is_ok());
 
 
         chess.load("8/pp3P1k/1npNp3/4P3/2PP1PR1/4K3/P1r5/7q w - - 1 38".to_string());
         assert_eq!(chess.count_moves(), 24);
-        assert!(chess.handle_promotion(SQUARE::F7, SQUARE::F8, PieceType::WhiteKnight).is_ok());
+        assert!(chess.handle_promotion(Square::F7, Square::F8, PieceType::WhiteKnight).is_ok());
 
 
         chess.load("8/pPr4k/6p1/8/1P5p/8/5PK1/8 w - - 0 37".to_string());
         assert_eq!(chess.count_moves(), 13);
-        assert!(chess.handle_promotion(SQUARE::B7, SQUARE::B8, PieceType::WhiteQueen).is_ok());
+        assert!(chess.handle_promotion(Square::B7, Square::B8, PieceType::WhiteQueen).is_ok());
 
 
         chess.load("r1bqr3/pp1n1Pkp/4p2b/3pP3/3N4/2NPBR2/PP4PP/R5K1 w - - 1 18".to_string());
         assert_eq!(chess.count_moves(), 51);
-        assert!(chess.handle_promotion(SQUARE::F7, SQUARE::E8, PieceType::WhiteKnight).is_ok());
+        assert!(chess.handle_promotion(Square::F7, Square::E8, PieceType::WhiteKnight).is_ok());
 
 
         chess.load("8/5QP1/2qp3k/4p3/8/6K1/4N3/1q6 w - - 0 60".to_string());
         //assert_eq!(chess.count_moves(), 35);
         println!("{}", chess.count_moves());
-        assert!(chess.handle_promotion(SQUARE::G7, SQUARE::G8, PieceType::WhiteKnight).is_ok());
+        assert!(chess.handle_promotion(Square::G7, Square::G8, PieceType::WhiteKnight).is_ok());
+    }
+
+    #[test]
+    fn legal_moves() {
+        let mut chess = ChessBoard::new();
+        assert_eq!(chess.legal_moves().len(), 20);
+
+        // Same amount as count_moves, promotions are listed once per piece
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/5Q2/PPPBBPpP/RN2K2R w KQkq - 0 2",
+            "3r3k/1p2P1pp/8/p7/8/5NK1/1qp3PP/8 w - - 0 39",
+            "8/pp3P1k/1npNp3/4P3/2PP1PR1/4K3/P1r5/7q w - - 1 38",
+            "2r3k1/4q3/p3prpp/1p1Q4/2pP3P/8/PP3PP1/1B2RRK1 b - - 0 24",
+        ] {
+            chess.load(fen.to_string());
+            assert_eq!(chess.legal_moves().len(), chess.count_moves());
+        }
+
+        // Flags of special moves
+        chess.load("r3k2r/8/8/3pP3/8/8/8/R3K2R w KQkq d6 0 1".to_string());
+        let moves = chess.legal_moves();
+        let find = |from: usize, to: usize| moves.iter().find(|m| m.from == from && m.to == to).copied().unwrap();
+        assert!(find(Square::E1, Square::G1).castling);
+        assert!(find(Square::E1, Square::C1).castling);
+        assert!(!find(Square::E1, Square::F1).castling);
+        assert!(find(Square::E5, Square::D6).en_passant);
+        assert!(find(Square::E5, Square::D6).capture);
+        assert!(find(Square::A1, Square::A8).capture);
+        assert!(!find(Square::E5, Square::E6).capture);
+
+        // Double push and promotions for black
+        assert!(chess.make_move(find(Square::E1, Square::G1)).is_ok());
+        assert_eq!(chess.piece_at(Square::F1), PieceType::WhiteRook);
+        chess.load("4k3/8/8/8/8/8/1p6/R3K3 b - - 0 1".to_string());
+        let moves = chess.legal_moves();
+        assert_eq!(moves.iter().filter(|m| m.from == Square::B2 && m.to == Square::B1).count(), 4);
+        assert_eq!(moves.iter().filter(|m| m.from == Square::B2 && m.to == Square::A1 && m.capture).count(), 4);
+        assert!(moves.iter().all(|m| m.promotion.is_none_or(|p| !p.is_white())));
+        chess.load("4k3/p7/8/8/8/8/8/4K3 b - - 0 1".to_string());
+        assert!(chess.legal_moves().iter().any(|m| m.from == Square::A7 && m.to == Square::A5 && m.double_push));
+
+        // Promotion without a piece does not change the game
+        chess.load("8/P6k/8/8/8/8/8/K7 w - - 0 1".to_string());
+        assert_eq!(chess.move_piece(Square::A7, Square::A8), Ok(false));
+        assert_eq!(chess.to_fen(), "8/P6k/8/8/8/8/8/K7 w - - 0 1");
+        assert!(chess.make_move(Move::new(Square::A7, Square::A8, None)).is_err());
+        assert!(chess.make_move(Move::new(Square::A1, Square::A2, Some(PieceType::WhiteQueen))).is_err());
+        assert!(chess.make_move(Move::new(Square::A7, Square::A8, Some(PieceType::WhiteRook))).is_ok());
+        assert_eq!(chess.piece_at(Square::A8), PieceType::WhiteRook);
+    }
+
+    #[test]
+    fn undo_redo() {
+        let mut chess = ChessBoard::new();
+        let mut fens = vec![chess.to_fen()];
+
+        // Castling, en passant, captures, promotion and checkmate
+        let moves = [
+            (Square::E2, Square::E4), (Square::D7, Square::D5),
+            (Square::E4, Square::E5), (Square::F7, Square::F5),
+            (Square::E5, Square::F6), (Square::G8, Square::F6),
+            (Square::G1, Square::F3), (Square::C8, Square::G4),
+            (Square::F1, Square::E2), (Square::B8, Square::C6),
+            (Square::E1, Square::G1), (Square::D8, Square::D6),
+            (Square::D2, Square::D4), (Square::E8, Square::C8),
+        ];
+        for (from, to) in moves {
+            assert!(chess.move_piece(from, to).is_ok());
+            fens.push(chess.to_fen());
+        }
+        assert_eq!(chess.piece_at(Square::F1), PieceType::WhiteRook);
+        assert_eq!(chess.piece_at(Square::D8), PieceType::BlackRook);
+
+        // Undo everything
+        for i in (0..moves.len()).rev() {
+            let m = chess.undo().unwrap();
+            assert_eq!((m.from, m.to), moves[i]);
+            assert_eq!(chess.to_fen(), fens[i]);
+        }
+        assert!(chess.undo().is_none());
+        assert_eq!(chess.board, ChessBoard::new().board);
+
+        // Redo everything
+        for i in 0..moves.len() {
+            let m = chess.redo().unwrap();
+            assert_eq!((m.from, m.to), moves[i]);
+            assert_eq!(chess.to_fen(), fens[i+1]);
+        }
+        assert!(chess.redo().is_none());
+
+        // A new move removes the moves to redo
+        chess.undo();
+        assert!(chess.move_piece(Square::A7, Square::A6).is_ok());
+        assert!(chess.redo().is_none());
+
+        // The flags of the moves are recorded
+        chess.load("r3k2r/8/8/3pP3/8/8/1p6/R3K2R w KQkq d6 0 1".to_string());
+        assert!(chess.move_piece(Square::E5, Square::D6).is_ok());
+        assert!(chess.handle_promotion(Square::B2, Square::A1, PieceType::BlackKnight).is_ok());
+        let m = chess.unmake_move().unwrap();
+        assert_eq!(m.promotion, Some(PieceType::BlackKnight));
+        assert!(m.capture);
+        assert_eq!(chess.piece_at(Square::A1), PieceType::WhiteRook);
+        let m = chess.unmake_move().unwrap();
+        assert!(m.en_passant && m.capture);
+        assert_eq!(chess.piece_at(Square::D5), PieceType::BlackPawn);
+        assert_eq!(chess.to_fen(), "r3k2r/8/8/3pP3/8/8/1p6/R3K2R w KQkq d6 0 1");
+
+        // Game result and check are restored
+        chess.load("k7/8/1K6/8/8/8/8/7R w - - 0 1".to_string());
+        assert!(chess.move_piece(Square::H1, Square::H8).is_ok());
+        assert_eq!(chess.game_result, GameResult::White);
+        assert!(chess.player_in_check);
+        chess.undo();
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        assert!(!chess.player_in_check);
+        chess.redo();
+        assert_eq!(chess.game_result, GameResult::White);
+
+        // Repetitions are forgotten when undone
+        chess.reset();
+        chess.draw_policy = DrawPolicy::AutoDraw;
+        for _ in 0..2 {
+            assert!(chess.move_piece(Square::G1, Square::F3).is_ok());
+            assert!(chess.move_piece(Square::G8, Square::F6).is_ok());
+            assert!(chess.move_piece(Square::F3, Square::G1).is_ok());
+            assert!(chess.move_piece(Square::F6, Square::G8).is_ok());
+        }
+        assert_eq!(chess.game_result, GameResult::Draw);
+        chess.undo();
+        chess.undo();
+        chess.undo();
+        chess.undo();
+        assert!(chess.move_piece(Square::G1, Square::F3).is_ok());
+        assert!(chess.move_piece(Square::G8, Square::F6).is_ok());
+        assert!(chess.move_piece(Square::F3, Square::G1).is_ok());
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+    }
+
+    #[test]
+    fn san() {
+        let mut chess = ChessBoard::new();
+
+        // Play a game by SAN, checking that the moves are written back the same way
+        let game = [
+            "e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7",
+            "Re1", "b5", "Bb3", "d6", "c3", "O-O", "h3", "Nb8", "d4", "Nbd7",
+            "c4", "c6", "cxb5", "axb5", "Nc3", "Bb7", "Bg5", "b4", "Nb1", "h6",
+            "Bh4", "c5", "dxe5", "Nxe4", "Bxe7", "Qxe7", "exd6", "Qf6", "Nbd2", "Nxd6",
+            "Nc4", "Nxc4", "Bxc4", "Nb6", "Ne5", "Rae8", "Bxf7+", "Rxf7", "Nxf7", "Rxe1+",
+            "Qxe1", "Kxf7", "Qe3", "Qg5", "Qxg5", "hxg5", "b3", "Ke6", "a3", "Kd6",
+            "axb4", "cxb4", "Ra5", "Nd5", "f3", "Bc8", "Kf2", "Bf5", "Ra7", "g6",
+            "Ra6+", "Kc5", "Ke1", "Nf4", "g3", "Nxh3", "Kd2", "Kb5", "Rd6", "Kc5",
+            "Ra6", "Nf2", "g4", "Bd3", "Re6",
+        ];
+        for san in game {
+            let m = chess.parse_san(san).unwrap();
+            assert_eq!(chess.to_san(m), san);
+            assert!(chess.make_move(m).is_ok());
+        }
+        assert_eq!(chess.to_fen(), "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43");
+
+        // Disambiguation by file, rank and both
+        chess.load("k7/8/8/8/1Q3Q2/8/8/K4Q2 w - - 0 1".to_string());
+        assert_eq!(chess.to_san(chess.parse_san("Qb4d2").unwrap()), "Qbd2");
+        assert_eq!(chess.to_san(chess.parse_san("Qf1d3").unwrap()), "Qd3");
+        assert!(chess.parse_san("Qd2").is_err());
+        assert!(chess.parse_san("Qfd2").is_ok());
+        chess.load("7k/8/8/R7/8/8/8/R6K w - - 0 1".to_string());
+        assert_eq!(chess.to_san(chess.parse_san("R1a3").unwrap()), "R1a3");
+        assert!(chess.parse_san("Raa3").is_err());
+        assert_eq!(chess.to_san(chess.parse_san("R5a3").unwrap()), "R5a3");
+        assert!(chess.parse_san("Ra3").is_err());
+        chess.load("4k3/8/8/8/8/Q7/8/Q1Q4K w - - 0 1".to_string());
+        assert_eq!(chess.to_san(chess.parse_san("Qa1b2").unwrap()), "Qa1b2");
+        assert!(chess.parse_san("Q1b2").is_err());
+        assert_eq!(chess.to_san(Move::new(Square::C1, Square::B2, None)), "Qcb2");
+        assert_eq!(chess.to_san(Move::new(Square::A3, Square::B2, None)), "Q3b2");
+
+        // Promotions and checkmate
+        chess.load("r3k3/1P6/8/8/8/8/8/4K2R w K - 0 1".to_string());
+        let m = chess.parse_san("bxa8=Q+").unwrap();
+        assert_eq!(m.promotion, Some(PieceType::WhiteQueen));
+        assert_eq!(chess.to_san(m), "bxa8=Q+");
+        assert!(chess.parse_san("bxa8").is_err());
+        assert_eq!(chess.parse_san("bxa8N").unwrap().promotion, Some(PieceType::WhiteKnight));
+        assert_eq!(chess.to_san(chess.parse_san("b8=R").unwrap()), "b8=R+");
+        assert_eq!(chess.to_san(chess.parse_san("0-0").unwrap()), "O-O");
+        chess.load("6k1/5ppp/8/8/8/8/8/R5K1 w - - 0 1".to_string());
+        assert_eq!(chess.to_san(chess.parse_san("Ra8").unwrap()), "Ra8#");
+
+        // Bad input
+        for bad in ["", "O-O-O", "Nf9", "Zf3", "e", "Ke3", "Nf3x", "E4"] {
+            assert!(chess.parse_san(bad).is_err());
+        }
+    }
+
+    #[test]
+    fn uci_moves() {
+        let mut chess = ChessBoard::new();
+
+        // Castling, en passant and promotion
+        assert!(chess.apply_uci_moves(&[
+            "e2e4", "g8f6", "e4e5", "d7d5", "e5d6", "e7e6", "d6c7", "f8e7", "g1f3", "e8g8", "c7b8n"
+        ]).is_ok());
+        assert_eq!(chess.to_fen(), "rNbq1rk1/pp2bppp/4pn2/8/8/5N2/PPPP1PPP/RNBQKB1R b KQ - 0 6");
+        let m = chess.unmake_move().unwrap();
+        assert_eq!(m.to_uci(), "c7b8n");
+        assert_eq!(format!("{}", m), "c7b8n");
+
+        // Promotions need a piece
+        assert!(chess.parse_uci("c7b8").is_err());
+        assert_eq!(chess.parse_uci("c7d8Q").unwrap().promotion, Some(PieceType::WhiteQueen));
+        assert!(chess.parse_uci("c7d8k").is_err());
+        assert!(chess.parse_uci("c7c8q").is_err());
+
+        // Bad input
+        for bad in ["", "e2", "e2e", "e2e4e4", "i2e4", "e2e9", "e2-e4", "ü2e4"] {
+            assert!(chess.parse_uci(bad).is_err());
+        }
+
+        // Written moves can be read again
+        for m in chess.legal_moves() {
+            assert_eq!(chess.parse_uci(&m.to_uci()), Ok(m));
+        }
+        // A list of moves that fails keeps the moves that can be redone
+        chess.reset();
+        assert!(chess.apply_uci_moves(&["e2e4", "e7e5"]).is_ok());
+        chess.undo();
+        assert!(chess.apply_uci_moves(&["d7d5", "e4d5", "e2e4"]).is_err());
+        assert!(chess.apply_uci_moves(&[]).is_ok());
+        assert_eq!(chess.redo().map(|m| m.to_uci()), Some("e7e5".to_string()));
+    }
+
+    #[test]
+    fn pgn_import() {
+        use crate::pgn::{read_games, read_games_from, PgnError};
+
+        let pgn = r#"
+% Escaped line that is ignored
+[Event "F/S Return Match"]
+[Site "Belgrade, Serbia JUG"]
+[Date "1992.11.04"]
+[Round "29"]
+[White "Fischer, Robert J."]
+[Black "Spassky, Boris V."]
+[Result "1/2-1/2"]
+[Annotator "Someone \"quoted\""]
+
+{Opening comment} 1. e4 e5 2. Nf3 Nc6 3. Bb5 {This opening is called the Ruy Lopez.} 3... a6
+4. Ba4 Nf6 5. O-O Be7 6. Re1 b5 7. Bb3 d6 8. c3 O-O 9. h3 Nb8 10. d4 Nbd7
+11. c4 c6 12. cxb5 axb5 13. Nc3 Bb7 14. Bg5 b4 15. Nb1 h6 16. Bh4 c5 17. dxe5
+Nxe4 18. Bxe7 Qxe7 19. exd6 Qf6 20. Nbd2 Nxd6 21. Nc4 Nxc4 22. Bxc4 Nb6
+23. Ne5 Rae8 24. Bxf7+ Rxf7 25. Nxf7 Rxe1+ 26. Qxe1 Kxf7 27. Qe3 Qg5 28. Qxg5
+hxg5 29. b3 Ke6 30. a3 Kd6 31. axb4 cxb4 32. Ra5 Nd5 33. f3 Bc8 34. Kf2 Bf5
+35. Ra7 g6 36. Ra6+ Kc5 37. Ke1 Nf4 38. g3 Nxh3 39. Kd2 Kb5 40. Rd6 Kc5 41. Ra6
+Nf2 42. g4 Bd3 43. Re6 1/2-1/2
+
+[Event "Second"]
+[Result "1-0"]
+
+1.e4 e5 2.Qh5!? Nc6 (2...Nf6?? $4 {loses} 3.Qxe5+ (3. Qxf7+?! Kxf7) Qe7) (2...g6 3.Qf3) 3.Bc4 ; line comment
+Nf6 4.Qxf7# 1-0 {Comment after the game}
+
+[Event "From position"]
+[SetUp "1"]
+[FEN "4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"]
+
+1. 0-0-0 Ke7 *
+"#;
+        let games = read_games(pgn).unwrap();
+        assert_eq!(games.len(), 3);
+
+        // Seven tag roster and extra tags
+        let game = &games[0];
+        assert_eq!(game.tag("Event"), Some("F/S Return Match"));
+        assert_eq!(game.tag("White"), Some("Fischer, Robert J."));
+        assert_eq!(game.tag("Annotator"), Some("Someone \"quoted\""));
+        assert_eq!(game.tag("ECO"), None);
+        assert_eq!(game.tags.len(), 8);
+        assert_eq!(game.result, GameResult::Draw);
+        assert_eq!(game.moves.len(), 85);
+        assert_eq!(game.moves[0].comments_before, vec!["Opening comment".to_string()]);
+        assert_eq!(game.moves[4].comments, vec!["This opening is called the Ruy Lopez.".to_string()]);
+        assert_eq!(game.board().unwrap().to_fen(), "8/8/4R1p1/2k3p1/1p4P1/1P1b1P2/3K1n2/8 b - - 2 43");
+
+        // Variations, NAGs and comments
+        let game = &games[1];
+        assert_eq!(game.result, GameResult::White);
+        assert_eq!(game.moves.len(), 7);
+        assert_eq!(game.moves[2].nags, vec![5]);
+        let nc6 = &game.moves[3];
+        assert_eq!(nc6.variations.len(), 2);
+        let variation = &nc6.variations[0];
+        assert_eq!(variation.len(), 3);
+        assert_eq!(variation[0].san, "Nf6");
+        assert_eq!(variation[0].nags, vec![4, 4]);
+        assert_eq!(variation[0].comments, vec!["loses".to_string()]);
+        assert_eq!(variation[1].variations[0][0].san, "Qxf7+");
+        assert_eq!(variation[1].variations[0][0].nags, vec![6]);
+        assert_eq!(variation[1].variations[0][1].san, "Kxf7");
+        assert_eq!(nc6.variations[1][1].m.to_uci(), "h5f3");
+        assert_eq!(game.moves[4].comments, vec!["line comment".to_string()]);
+        assert_eq!(game.board().unwrap().game_result, GameResult::White);
+        assert_eq!(game.comments, vec!["Comment after the game".to_string()]);
+
+        // Starting from a FEN-tag
+        let game = &games[2];
+        assert_eq!(game.result, GameResult::Ongoing);
+        assert_eq!(game.moves[0].m.to_uci(), "e1c1");
+        assert_eq!(game.board().unwrap().to_fen(), "8/4k3/8/8/8/8/8/2KR4 w - - 2 2");
+
+        // From a reader
+        assert_eq!(read_games_from(pgn.as_bytes()).unwrap(), games);
+        assert_eq!(read_games("").unwrap(), vec![]);
+
+        // Errors
+        assert_eq!(read_games("1. e4 e5 2. Ke3"), Err(PgnError::IllegalMove {
+            line: Some(1), san: "Ke3".to_string(), error: MoveError::NoMatchingMove("Ke3".to_string())
+        }));
+        assert_eq!(read_games("[Event \"x]\n1. e4"), Err(PgnError::InvalidTag { line: 1 }));
+        assert_eq!(read_games("1. e4 {comment\n"), Err(PgnError::UnclosedComment { line: 1 }));
+        assert_eq!(read_games("1. e4 (1. d4\n\n"), Err(PgnError::UnclosedVariation { line: 1 }));
+        assert_eq!(read_games("1. e4 )"), Err(PgnError::UnexpectedToken { line: 1, token: ")".to_string() }));
+        assert!(matches!(read_games("[FEN \"8/8 w - - 0 1\"]\n*"), Err(PgnError::InvalidFen { line: Some(1), .. })));
+
+        // Games changed after reading have no line to point at
+        let mut game = read_games("1. e4 e5").unwrap().remove(0);
+        game.moves.swap(0, 1);
+        assert!(matches!(game.board(), Err(PgnError::IllegalMove { line: None, .. })));
+        game.tags.push(("FEN".to_string(), "8/8 w - - 0 1".to_string()));
+        assert!(matches!(game.start_board(), Err(PgnError::InvalidFen { line: None, .. })));
+    }
+
+    #[test]
+    fn pgn_export() {
+        use crate::pgn::{read_games, PgnError};
+
+        // Play a game and write it
+        let mut chess = ChessBoard::new();
+        let game = [
+            "e4", "e5", "Nf3", "Nc6", "Bb5", "a6", "Ba4", "Nf6", "O-O", "Be7",
+            "Re1", "b5", "Bb3", "d6", "c3", "O-O", "h3", "Nb8", "d4", "Nbd7",
+            "c4", "c6", "cxb5", "axb5", "Nc3", "Bb7", "Bg5", "b4", "Nb1", "h6",
+            "Bh4", "c5", "dxe5", "Nxe4", "Bxe7", "Qxe7", "exd6", "Qf6", "Nbd2", "Nxd6",
+        ];
+        for san in game {
+            let m = chess.parse_san(san).unwrap();
+            assert!(chess.make_move(m).is_ok());
+        }
+        chess.mutual_draw();
+
+        let mut record = chess.game_record();
+        record.set_tag("Event", "F/S Return Match");
+        record.set_tag("White", "Fischer, Robert J.");
+        record.set_tag("Black", "Spassky, Boris \"V\"");
+        record.set_tag("ECO", "C95");
+        assert_eq!(record.moves.len(), game.len());
+
+        let pgn = record.to_pgn().unwrap();
+        assert!(pgn.starts_with("[Event \"F/S Return Match\"]\n[Site \"?\"]\n[Date \"????.??.??\"]\n[Round \"?\"]\n[White \"Fischer, Robert J.\"]\n[Black \"Spassky, Boris \\\"V\\\"\"]\n[Result \"1/2-1/2\"]\n[ECO \"C95\"]\n\n1. e4 e5 2. Nf3"));
+        assert!(pgn.ends_with(" 20. Nbd2 Nxd6 1/2-1/2\n"));
+        assert!(!pgn.contains("FEN"));
+        assert!(pgn.lines().all(|line| line.len() <= 80));
+
+        // Read it back
+        let games = read_games(&pgn).unwrap();
+        assert_eq!(games.len(), 1);
+        assert_eq!(games[0].tag("Black"), Some("Spassky, Boris \"V\""));
+        assert_eq!(games[0].result, GameResult::Draw);
+        assert_eq!(games[0].moves.iter().map(|m| m.san.as_str()).collect::<Vec<&str>>(), game);
+
+        // Game from a position, starting with black, ending in checkmate
+        chess.load("6k1/p4ppp/8/8/8/8/1r6/R5K1 b - - 3 30".to_string());
+        assert!(chess.apply_uci_moves(&["b2b1", "a1b1", "a7a6", "b1b8"]).is_ok());
+        let pgn = chess.game_record().to_pgn().unwrap();
+        assert!(pgn.contains("[Result \"1-0\"]\n[SetUp \"1\"]\n[FEN \"6k1/p4ppp/8/8/8/8/1r6/R5K1 b - - 3 30\"]\n\n30... Rb1+ 31. Rxb1 a6 32. Rb8# 1-0\n"));
+        let games = read_games(&pgn).unwrap();
+        assert_eq!(games[0].board().unwrap().to_fen(), chess.to_fen());
+
+        // Unfinished game
+        chess.reset();
+        assert_eq!(chess.game_record().to_pgn().unwrap().lines().last(), Some("*"));
+
+        // A game played past a fivefold repetition is written and read back in full
+        chess.draw_policy = DrawPolicy::None;
+        for _ in 0..5 {
+            chess.apply_uci_moves(&["g1f3", "g8f6", "f3g1", "f6g8"]).unwrap();
+        }
+        chess.apply_uci_moves(&["e2e4"]).unwrap();
+        let record = chess.game_record();
+        assert_eq!(record.moves.len(), 21);
+        let pgn = record.to_pgn().unwrap();
+        assert!(pgn.ends_with(" 11. e4 *\n"));
+        let games = read_games(&pgn).unwrap();
+        assert_eq!(games[0].moves.len(), 21);
+        assert_eq!(games[0].board().unwrap().to_fen(), chess.to_fen());
+
+        // A move that can't be made is an error, not left out
+        let mut record = chess.game_record();
+        record.moves.push(Move::new(Square::E4, Square::E6, None));
+        assert!(matches!(record.to_pgn(), Err(PgnError::IllegalMove { line: None, .. })));
     }
 
     #[test]
     fn three_fold_repetition() {
         let mut chess = ChessBoard::new();
+        chess.draw_policy = DrawPolicy::AutoDraw;
 
         // Move pawns
-        assert!(chess.move_piece(SQUARE::E2, SQUARE::E4).is_ok());
-        assert!(chess.move_piece(SQUARE::E7, SQUARE::E5).is_ok());
+        assert!(chess.move_piece(Square::E2, Square::E4).is_ok());
+        assert!(chess.move_piece(Square::E7, Square::E5).is_ok());
 
         // Begin to shuffle kings
-        assert!(chess.move_piece(SQUARE::E1, SQUARE::E2).is_ok());
-        assert!(chess.move_piece(SQUARE::E8, SQUARE::E7).is_ok());
+        assert!(chess.move_piece(Square::E1, Square::E2).is_ok());
+        assert!(chess.move_piece(Square::E8, Square::E7).is_ok());
         // Above is the first time in repeatable position due to castling-rights now being gone
 
 
-        assert!(chess.move_piece(SQUARE::E2, SQUARE::E1).is_ok());
-        assert!(chess.move_piece(SQUARE::E7, SQUARE::E8).is_ok());
-        assert!(chess.move_piece(SQUARE::E1, SQUARE::E2).is_ok());
-        assert!(chess.move_piece(SQUARE::E8, SQUARE::E7).is_ok());
+        assert!(chess.move_piece(Square::E2, Square::E1).is_ok());
+        assert!(chess.move_piece(Square::E7, Square::E8).is_ok());
+        assert!(chess.move_piece(Square::E1, Square::E2).is_ok());
+        assert!(chess.move_piece(Square::E8, Square::E7).is_ok());
         
         // Now twice repeated
-        assert!(chess.move_piece(SQUARE::E2, SQUARE::E1).is_ok());
-        assert!(chess.move_piece(SQUARE::E7, SQUARE::E8).is_ok());
-        assert!(chess.move_piece(SQUARE::E1, SQUARE::E2).is_ok());
+        assert!(chess.move_piece(Square::E2, Square::E1).is_ok());
+        assert!(chess.move_piece(Square::E7, Square::E8).is_ok());
+        assert!(chess.move_piece(Square::E1, Square::E2).is_ok());
         
         // Last one
-        assert!(chess.move_piece(SQUARE::E8, SQUARE::E7).is_ok());
+        assert!(chess.move_piece(Square::E8, Square::E7).is_ok());
 
         // Cant move anymore
-        assert!(chess.move_piece(SQUARE::E8, SQUARE::E7).is_err());
+        assert!(chess.move_piece(Square::E8, Square::E7).is_err());
 
         // Check if game is draw
         assert_eq!(chess.game_result,GameResult::Draw);
     }
 
+    #[test]
+    fn fen_round_trip() {
+        let mut chess = ChessBoard::new();
+
+        let fens = [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/pppp1ppp/4p2b/8/8/B2P4/PPP1PPPP/R3K2R w KQkq - 0 1",
+            "rnbqkbnr/1p3p1p/8/P1PpP1P1/p1p1p1pP/8/1P1P1P2/RNBQKBNR w KQkq d6 0 1",
+            "3r3k/1p2P1pp/8/p7/8/5NK1/1qp3PP/8 w - - 0 39",
+            "8/pp3P1k/1npNp3/4P3/2PP1PR1/4K3/P1r5/7q w - - 1 38",
+            "8/pPr4k/6p1/8/1P5p/8/5PK1/8 w - - 0 37",
+            "r1bqr3/pp1n1Pkp/4p2b/3pP3/3N4/2NPBR2/PP4PP/R5K1 w - - 1 18",
+            "8/5QP1/2qp3k/4p3/8/6K1/4N3/1q6 w - - 0 60",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/5Q2/PPPBBPpP/RN2K2R w KQkq - 0 2",
+            "1r6/3k2p1/7p/Ppp2r1P/K1N1B1p1/2P2NP1/b7/4b3 w - - 0 56",
+            "3n4/2k5/p5pr/2pBP2P/PpN1KP2/1P6/8/6b1 w - - 0 32",
+            "8/6kp/1r2rR1B/4P3/p1p5/1bN2P2/1Pn2K2/8 b - - 1 39",
+            "5kr1/1r2p1b1/p2p1R2/3q1Q1p/5P2/4R2P/P5PK/8 b - - 0 41",
+            "5Qk1/1p2r1bp/3pN1p1/3pq3/2P1p3/1P5P/P5P1/5RK1 b - - 1 27",
+            "2r2rk1/6pp/p4nbN/1p1pq1Q1/4p3/7P/PPP1NPP1/R4RK1 b - - 8 25",
+            "2r3k1/4q3/p3prpp/1p1Q4/2pP3P/8/PP3PP1/1B2RRK1 b - - 0 24",
+            "3r2k1/pb3pp1/1p6/8/8/P4P2/3R1QPP/3q2K1 w - - 0 34",
+        ];
+
+        for fen in fens {
+            chess.load(fen.to_string());
+            assert_eq!(chess.to_fen(), fen);
+        }
+
+        // Castling rights and en passant square follow the game
+        chess.reset();
+        assert!(chess.move_piece(Square::E2, Square::E4).is_ok());
+        assert_eq!(chess.to_fen(), "rnbqkbnr/pppppppp/8/8/4P3/8/PPPP1PPP/RNBQKBNR b KQkq e3 0 1");
+        assert!(chess.move_piece(Square::E7, Square::E5).is_ok());
+        assert!(chess.move_piece(Square::E1, Square::E2).is_ok());
+        assert_eq!(chess.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/8/PPPPKPPP/RNBQ1BNR b kq - 1 2");
+    }
+
+    #[test]
+    fn fen_errors() {
+        // Same position as load
+        let chess = ChessBoard::from_fen("rnbqkbnr/1p3p1p/8/P1PpP1P1/p1p1p1pP/8/1P1P1P2/RNBQKBNR w KQkq d6 0 1").unwrap();
+        assert_eq!(chess.to_fen(), "rnbqkbnr/1p3p1p/8/P1PpP1P1/p1p1p1pP/8/1P1P1P2/RNBQKBNR w KQkq d6 0 1");
+
+        // Castling rights in any order, clocks can be left out
+        let chess = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w qkQK -").unwrap();
+        assert_eq!(chess.castling_rights, (true, true, true, true));
+        assert_eq!(chess.to_fen(), "r3k2r/8/8/8/8/8/8/R3K2R w KQkq - 0 1");
+        let chess = ChessBoard::from_fen("r3k2r/8/8/8/8/8/8/R3K2R w Kq - 0 1").unwrap();
+        assert_eq!(chess.castling_rights, (true, false, false, true));
+
+        // Player in check is detected
+        let chess = ChessBoard::from_fen("k6q/8/8/8/8/8/8/7K w - - 0 1").unwrap();
+        assert!(chess.player_in_check);
+
+        assert_eq!(ChessBoard::from_fen("").err(), Some(FenError::MissingField { field: FenField::Placement, index: 0 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w").err(), Some(FenError::MissingField { field: FenField::CastlingRights, index: 17 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - - 0").err(), Some(FenError::MissingField { field: FenField::FullmoveCount, index: 23 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - - 0 1 x").err(), Some(FenError::TooManyFields { index: 26 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - - 0 1 x").unwrap_err().field(), None);
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - - 0 0").unwrap_err().field(), Some(FenField::FullmoveCount));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8 w - - 0 1").err(), Some(FenError::WrongRankCount { found: 7, index: 0 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8/8 w - - 0 1").err(), Some(FenError::WrongRankCount { found: 9, index: 0 }));
+        assert_eq!(ChessBoard::from_fen("2r3r3/8/8/8/8/8/8/8 w - - 0 1").err(), Some(FenError::WrongFileCount { found: 10, index: 4 }));
+        assert_eq!(ChessBoard::from_fen("8/7/8/8/8/8/8/8 w - - 0 1").err(), Some(FenError::WrongFileCount { found: 7, index: 3 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/3x4/8/8/8/8 w - - 0 1").err(), Some(FenError::InvalidPiece { character: 'x', index: 7 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/09/8/8/8/8 w - - 0 1").err(), Some(FenError::InvalidPiece { character: '0', index: 6 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 W - - 0 1").err(), Some(FenError::InvalidPlayerTurn { index: 16 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w ---- - 0 1").err(), Some(FenError::InvalidCastlingRights { character: '-', index: 18 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w KK - 0 1").err(), Some(FenError::InvalidCastlingRights { character: 'K', index: 19 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - e4 0 1").err(), Some(FenError::InvalidEnPassant { index: 20 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - i6 0 1").err(), Some(FenError::InvalidEnPassant { index: 20 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - - -1 1").err(), Some(FenError::InvalidHalfmoveClock { index: 22 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - - 0 0").err(), Some(FenError::InvalidFullmoveCount { index: 24 }));
+        assert_eq!(ChessBoard::from_fen("8/8/8/8/8/8/8/8 w - - 0 99999999999").err(), Some(FenError::InvalidFullmoveCount { index: 24 }));
+
+        // Never panics on garbage
+        for fen in ["/", "////////", "ü/ü", "  ", "8/8/8/8/8/8/8/8 w KQkq e3e3 0 1", "88888888/8/8/8/8/8/8/8 w - - 0 1"] {
+            assert!(ChessBoard::from_fen(fen).is_err());
+        }
+
+        // Load does not panic on garbage either
+        let mut chess = ChessBoard::new();
+        chess.load("rnbqkbnr/ppxppppp/99/8/8/8/PPPPPPPP/RNBQKBNR/PPPPPPPP w KQkq".to_string());
+    }
+
+    #[test]
+    fn move_errors() {
+        let mut chess = ChessBoard::new();
+        assert_eq!(chess.move_piece(Square::E4, Square::E5), Err(MoveError::NoPieceOnSquare { square: Square::E4 }));
+        assert_eq!(chess.move_piece(Square::E7, Square::E5), Err(MoveError::WrongSideToMove { square: Square::E7 }));
+        assert_eq!(chess.move_piece(Square::A1, Square::A3), Err(MoveError::NoLegalMoves { square: Square::A1 }));
+        assert_eq!(chess.move_piece(Square::E2, Square::E5), Err(MoveError::IllegalDestination { from: Square::E2, to: Square::E5 }));
+        assert_eq!(chess.move_piece(64, Square::E5), Err(MoveError::SquareOutOfRange { square: 64 }));
+        assert_eq!(chess.move_piece(Square::E2, 100), Err(MoveError::SquareOutOfRange { square: 100 }));
+        assert_eq!(chess.claim_draw(), Err(MoveError::NoDrawToClaim));
+
+        // Moves in notation
+        assert_eq!(chess.parse_uci("e2e9"), Err(MoveError::InvalidNotation("e2e9".to_string())));
+        assert_eq!(chess.parse_uci("e2e5"), Err(MoveError::IllegalDestination { from: Square::E2, to: Square::E5 }));
+        assert_eq!(chess.parse_uci("e2e4q"), Err(MoveError::InvalidPromotionPiece { piece: PieceType::WhiteQueen }));
+        assert_eq!(chess.parse_san("Zf3"), Err(MoveError::InvalidNotation("Zf3".to_string())));
+        assert_eq!(chess.parse_san("Nf4"), Err(MoveError::NoMatchingMove("Nf4".to_string())));
+        assert_eq!(chess.apply_uci_moves(&["e2e4", "e7e5", "e1e3"]), Err(MoveError::IllegalDestination { from: Square::E1, to: Square::E3 }));
+        assert_eq!(chess.to_fen(), "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1");
+
+        chess.load("4k3/8/8/8/8/8/4K3/R6R w - - 0 1".to_string());
+        assert_eq!(chess.parse_san("Rd1"), Err(MoveError::AmbiguousMove("Rd1".to_string())));
+
+        // Promotions
+        chess.load("1n5k/P7/8/8/8/8/8/K7 w - - 0 1".to_string());
+        assert_eq!(chess.parse_uci("a7a8"), Err(MoveError::PromotionRequired { from: Square::A7, to: Square::A8 }));
+        assert_eq!(chess.parse_san("a8"), Err(MoveError::PromotionRequired { from: Square::A7, to: Square::A8 }));
+        assert_eq!(chess.make_move(Move::new(Square::A7, Square::B8, None)), Err(MoveError::PromotionRequired { from: Square::A7, to: Square::B8 }));
+        assert_eq!(chess.make_move(Move::new(Square::A1, Square::A2, Some(PieceType::WhiteQueen))), Err(MoveError::InvalidPromotionPiece { piece: PieceType::WhiteQueen }));
+        assert_eq!(chess.handle_promotion(Square::A7, Square::A8, PieceType::WhiteKing), Err(MoveError::InvalidPromotionPiece { piece: PieceType::WhiteKing }));
+        assert_eq!(chess.handle_promotion(Square::A7, Square::A8, PieceType::BlackQueen), Err(MoveError::InvalidPromotionPiece { piece: PieceType::BlackQueen }));
+
+        // The game is over
+        chess.white_surrender();
+        assert_eq!(chess.move_piece(Square::A1, Square::B1), Err(MoveError::GameOver));
+        assert_eq!(chess.claim_draw(), Err(MoveError::GameOver));
+
+        assert_eq!(MoveError::IllegalDestination { from: Square::E2, to: Square::E5 }.to_string(), "Piece at e2 can't move to e5");
+    }
+
+    #[test]
+    fn validate_position() {
+        let mut chess = ChessBoard::new();
+        assert!(chess.validate().is_empty());
+
+        // Positions from the other tests are legal
+        chess.load("rnbqkbnr/1p3p1p/8/P1PpP1P1/p1p1p1pP/8/1P1P1P2/RNBQKBNR w KQkq d6 0 1".to_string());
+        assert!(chess.validate().is_empty());
+        chess.load("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/5Q2/PPPBBPpP/RN2K2R w KQkq - 0 2".to_string());
+        assert!(chess.validate().is_empty());
+
+        // No kings
+        chess.load("8/8/8/8/8/8/8/8 w - - 0 1".to_string());
+        assert_eq!(chess.validate(), vec![PositionError::MissingWhiteKing, PositionError::MissingBlackKing]);
+
+        // Two white kings
+        chess.load("k7/8/8/8/8/8/8/K6K w - - 0 1".to_string());
+        assert_eq!(chess.validate(), vec![PositionError::TooManyWhiteKings]);
+
+        // Pawns on the back rank
+        chess.load("k6p/8/8/8/8/8/8/K1P5 w - - 0 1".to_string());
+        assert_eq!(chess.validate(), vec![
+            PositionError::PawnOnBackRank { square: Square::C1 },
+            PositionError::PawnOnBackRank { square: Square::H8 }
+        ]);
+
+        // Nine pawns
+        chess.load("k7/8/8/8/8/P7/PPPPPPPP/K7 w - - 0 1".to_string());
+        assert_eq!(chess.validate(), vec![PositionError::TooManyWhitePawns]);
+
+        // Side not to move is in check
+        chess.load("k6Q/8/8/8/8/8/8/K7 w - - 0 1".to_string());
+        assert_eq!(chess.validate(), vec![PositionError::OpponentInCheck]);
+        chess.load("k6Q/8/8/8/8/8/8/K7 b - - 0 1".to_string());
+        assert!(chess.validate().is_empty());
+
+        // En passant square without a pawn that just moved two squares
+        chess.load("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1".to_string());
+        assert!(chess.validate().is_empty());
+        chess.load("4k3/8/8/8/4P3/8/8/4K3 b - d3 0 1".to_string());
+        assert_eq!(chess.validate(), vec![PositionError::InvalidEnPassant { square: Square::D3 }]);
+        chess.load("4k3/8/8/8/4P3/8/8/4K3 w - e3 0 1".to_string());
+        assert_eq!(chess.validate(), vec![PositionError::InvalidEnPassant { square: Square::E3 }]);
+
+        // Option to reject illegal positions when reading FEN
+        assert!(ChessBoard::from_fen("k7/8/8/8/8/8/8/K6K w - - 0 1").is_ok());
+        assert_eq!(
+            ChessBoard::from_fen_legal("k6Q/8/8/8/8/8/8/K7 w - - 0 1").err(),
+            Some(FenError::IllegalPosition(vec![PositionError::OpponentInCheck]))
+        );
+        assert!(ChessBoard::from_fen_legal("k6Q/8/8/8/8/8/8/K7 b - - 0 1").is_ok());
+    }
+
     #[test]    
     fn fifty_move_rule() {
         let mut chess = ChessBoard::new();
-        chess.load("k7/8/8/8/8/8/8/7K w ---- - 96 70".to_string());
+        chess.draw_policy = DrawPolicy::AutoDraw;
+        // A rook, as lone kings are a draw right away (insufficient material)
+        chess.load("k7/8/8/8/8/8/8/3R3K w ---- - 96 70".to_string());
 
         // Walk kings
-        assert!(chess.move_piece(SQUARE::H1, SQUARE::H2).is_ok());
-        assert!(chess.move_piece(SQUARE::A8, SQUARE::A7).is_ok());
-        assert!(chess.move_piece(SQUARE::H2, SQUARE::H3).is_ok());
-        assert!(chess.move_piece(SQUARE::A7, SQUARE::A6).is_ok());
+        assert!(chess.move_piece(Square::H1, Square::H2).is_ok());
+        assert!(chess.move_piece(Square::A8, Square::A7).is_ok());
+        assert!(chess.move_piece(Square::H2, Square::H3).is_ok());
+        assert!(chess.move_piece(Square::A7, Square::A6).is_ok());
         // Reached 100 moves
 
         // Cant move again
-        assert!(chess.move_piece(SQUARE::H3, SQUARE::H4).is_err());
+        assert!(chess.move_piece(Square::H3, Square::H4).is_err());
 
         // Check if game is draw
         assert_eq!(chess.game_result,GameResult::Draw);
+        assert_eq!(chess.termination, Some(Termination::FiftyMoveRule));
     }
 
     /// Testing the amount of legal moves, compared to a chess engines result
@@ -1717,4 +3739,990 @@ mod tests {
         
     }
 
+    /// Castling rights are lost when a rook moves away from, or is captured on, its starting square
+    /// 
+    #[test]
+    fn castling_rights_from_rooks() {
+        let mut chess = ChessBoard::from_fen("r3kb1r/8/8/8/8/8/8/R3K2R w KQkq - 0 1").unwrap();
+
+        // White rook captures the black rook on H8
+        assert!(chess.move_piece(Square::H1, Square::H8).is_ok());
+        assert_eq!(chess.to_fen(), "r3kb1R/8/8/8/8/8/8/R3K3 b Qq - 0 1");
+
+        // Black rook leaves A8
+        assert!(chess.move_piece(Square::A8, Square::A2).is_ok());
+        assert_eq!(chess.to_fen(), "4kb1R/8/8/8/8/8/r7/R3K3 w Q - 1 2");
+
+        // Black can't take en passant when the pawn is pinned along the rank
+        let chess = ChessBoard::from_fen("8/8/8/8/k2Pp2Q/8/8/3K4 b - d3 0 1").unwrap();
+        assert!(chess.legal_moves().iter().all(|m| !m.en_passant));
+    }
+
+    /// Compare the number of move sequences with the well known perft results
+    /// (https://www.chessprogramming.org/Perft_Results)
+    /// 
+    #[test]
+    fn perft_start_position() {
+        let chess = ChessBoard::new();
+        assert_eq!(chess.perft(4), 197_281);
+        assert_eq!(chess.perft(5), 4_865_609);
+    }
+
+    /// Positions testing pins, en passant, castling and promotions from a known perft suite
+    /// 
+    #[test]
+    fn perft_special_cases() {
+        for (fen, depth, nodes) in [
+            // Illegal en passant moves, the pawn is pinned along the rank or the diagonal
+            ("3k4/3p4/8/K1P4r/8/8/8/8 b - - 0 1", 6, 1_134_888),
+            ("8/8/4k3/8/2p5/8/B2P2K1/8 w - - 0 1", 6, 1_015_133),
+            // En passant capture gives check
+            ("8/8/1k6/2b5/2pP4/8/5K2/8 b - d3 0 1", 6, 1_440_467),
+            // Castling gives check, or is not allowed
+            ("5k2/8/8/8/8/8/8/4K2R w K - 0 1", 6, 661_072),
+            ("r3k2r/1b4bq/8/8/8/8/7B/R3K2R w KQkq - 0 1", 4, 1_274_206),
+            ("r3k2r/8/3Q4/8/8/5q2/8/R3K2R b KQkq - 0 1", 4, 1_720_476),
+            // Discovered check, and promotions giving check
+            ("8/8/1P2K3/8/2n5/1q6/8/5k2 b - - 0 1", 5, 1_004_658),
+            ("4k3/1P6/8/8/8/8/K7/8 w - - 0 1", 6, 217_342),
+            ("8/P1k5/K7/8/8/8/8/8 w - - 0 1", 6, 92_683),
+            // Stalemate and checkmate
+            ("K1k5/8/P7/8/8/8/8/8 w - - 0 1", 6, 2_217),
+            ("8/8/2k5/5q2/5n2/8/5K2/8 b - - 0 1", 4, 23_527),
+        ] {
+            assert_eq!(ChessBoard::from_fen(fen).unwrap().perft(depth), nodes, "{}", fen);
+        }
+    }
+
+    /// Only the king can move in double check, and pinned pieces only move along the pin
+    /// 
+    #[test]
+    fn pins_and_double_check() {
+        // Double check by the rook and the knight, the black rook can't take the knight
+        let chess = ChessBoard::from_fen("3rk3/8/3N4/8/8/8/8/4R1K1 b - - 0 1").unwrap();
+        assert!(!chess.legal_moves().is_empty());
+        assert!(chess.legal_moves().iter().all(|m| m.from == Square::E8));
+
+        // The bishop on E2 is pinned by the rook on E8, the bishop on D2 can only move along the diagonal
+        let chess = ChessBoard::from_fen("4r1k1/8/8/b7/8/8/3BB3/4K3 w - - 0 1").unwrap();
+        assert!(chess.get_moves_list(Square::E2).is_empty());
+        let mut diagonal = chess.get_moves_list(Square::D2);
+        diagonal.sort();
+        assert_eq!(diagonal, vec![Square::C3, Square::B4, Square::A5]);
+    }
+
+    #[test]
+    fn perft_kiwipete() {
+        let chess = ChessBoard::from_fen("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1").unwrap();
+        assert_eq!(chess.perft(1), 48);
+        assert_eq!(chess.perft(2), 2_039);
+        assert_eq!(chess.perft(4), 4_085_603);
+    }
+
+    #[test]
+    fn perft_position_3() {
+        let chess = ChessBoard::from_fen("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1").unwrap();
+        assert_eq!(chess.perft(5), 674_624);
+    }
+
+    #[test]
+    fn perft_position_4() {
+        let chess = ChessBoard::from_fen("r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1").unwrap();
+        assert_eq!(chess.perft(4), 422_333);
+
+        // Same position with the colors flipped
+        let chess = ChessBoard::from_fen("r2q1rk1/pP1p2pp/Q4n2/bbp1p3/Np6/1B3NBn/pPPP1PPP/R3K2R b KQ - 0 1").unwrap();
+        assert_eq!(chess.perft(4), 422_333);
+    }
+
+    #[test]
+    fn perft_position_5() {
+        let chess = ChessBoard::from_fen("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8").unwrap();
+        assert_eq!(chess.perft(4), 2_103_487);
+    }
+
+    #[test]
+    fn perft_position_6() {
+        let chess = ChessBoard::from_fen("r4rk1/1pp1qppp/p1np1n2/2b1p1B1/2B1P1b1/P1NP1N2/1PP1QPPP/R4RK1 w - - 0 10").unwrap();
+        assert_eq!(chess.perft(4), 3_894_594);
+
+        let divided = chess.divide(3);
+        assert_eq!(divided.len(), 46);
+        assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), 89_890);
+    }
+
+    /// The magic bitboard tables give the same attacks as walking the rays, on random boards
+    /// 
+    #[test]
+    fn slider_attacks() {
+        // Pseudo random boards (xorshift), fewer pieces by combining several numbers
+        let mut seed: u64 = 0x9E37_79B9_7F4A_7C15;
+        let mut random = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        for _ in 0..2000 {
+            let all_pieces = match random() % 3 {
+                0 => random(),
+                1 => random() & random(),
+                _ => random() & random() & random()
+            };
+            let enemy_pieces = all_pieces & random();
+
+            for &slider in PIECE.iter().take(64) {
+                let all_pieces = all_pieces | slider;
+                let enemy_pieces = enemy_pieces & !slider;
+
+                assert_eq!(
+                    compute_bishop_attacks(slider, all_pieces, enemy_pieces),
+                    compute_bishop_attacks_by_rays(slider, all_pieces, enemy_pieces)
+                );
+                assert_eq!(
+                    compute_rook_attacks(slider, all_pieces, enemy_pieces),
+                    compute_rook_attacks_by_rays(slider, all_pieces, enemy_pieces)
+                );
+            }
+        }
+    }
+
+    /*
+    Mask of the blockers that change the attacks of a slider on a square (the edges of the board are left out),
+    and the attacks for a set of blockers
+    */
+    fn slider_rays(square: usize, is_bishop: bool, blockers: BitBoard) -> (BitBoard, BitBoard) {
+        let slider = PIECE[square];
+        let edges = ((MASK_RANK[0] | MASK_RANK[7]) & !MASK_RANK[square / 8])
+            | ((MASK_FILE[0] | MASK_FILE[7]) & !MASK_FILE[square % 8]);
+        let occupied = blockers | slider;
+
+        if is_bishop {
+            (compute_bishop_attacks_by_rays(slider, slider, 0) & !edges, compute_bishop_attacks_by_rays(slider, occupied, blockers))
+        } else {
+            (compute_rook_attacks_by_rays(slider, slider, 0) & !edges, compute_rook_attacks_by_rays(slider, occupied, blockers))
+        }
+    }
+
+    /*
+    Every subset of a mask (Carry-Rippler), starting with the empty set
+    */
+    fn subsets(mask: BitBoard) -> Vec<BitBoard> {
+        let mut subsets = vec![];
+        let mut subset: BitBoard = 0;
+        loop {
+            subsets.push(subset);
+            subset = subset.wrapping_sub(mask) & mask;
+            if subset == 0 { return subsets; }
+        }
+    }
+
+    // Seed of the search for the magic numbers, the bishops are searched first and the rooks continue from the same seed
+    const MAGIC_SEED: u64 = 0x0123_4567_89AB_CDEF;
+
+    /*
+    Search for the magic numbers of every square, the candidates are sparse random numbers (splitmix64)
+    Returns the first candidate of every square that gives every set of blockers an index with the right attacks
+    */
+    fn find_magics(is_bishop: bool, seed: &mut u64) -> [BitBoard; 64] {
+        let mut random = || {
+            *seed = seed.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = *seed;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^ (z >> 31)
+        };
+        let mut magics = [0; 64];
+
+        for (square, magic) in magics.iter_mut().enumerate() {
+            let (mask, _) = slider_rays(square, is_bishop, 0);
+            let shift = 64 - mask.count_ones();
+            let blockers = subsets(mask);
+            let references: Vec<BitBoard> = blockers.iter().map(|b| slider_rays(square, is_bishop, *b).1).collect();
+
+            // The try a slot of the table was last filled in, so it doesn't have to be cleared for every candidate
+            let mut table = vec![(0u32, 0 as BitBoard); blockers.len()];
+            let mut tries = 0;
+            *magic = loop {
+                let candidate = random() & random() & random();
+                if (mask.wrapping_mul(candidate) >> 56).count_ones() < 6 { continue; }
+                tries += 1;
+
+                let works = blockers.iter().zip(&references).all(|(b, reference)| {
+                    let slot = &mut table[(b.wrapping_mul(candidate) >> shift) as usize];
+                    if slot.0 != tries {
+                        *slot = (tries, *reference);
+                        true
+                    } else {
+                        slot.1 == *reference
+                    }
+                });
+                if works { break candidate; }
+            };
+        }
+
+        magics
+    }
+
+    /// The magic numbers give the same attacks as walking the rays, for every set of blockers on the rays of every square
+    /// 
+    #[test]
+    fn magic_tables() {
+        for square in 0..64 {
+            for is_bishop in [true, false] {
+                let (mask, _) = slider_rays(square, is_bishop, 0);
+                for blockers in subsets(mask) {
+                    let magic = if is_bishop { bishop_attacks(square, blockers) } else { rook_attacks(square, blockers) };
+                    assert_eq!(magic, slider_rays(square, is_bishop, blockers).1, "square {} blockers {:x}", square, blockers);
+                }
+            }
+        }
+    }
+
+    /// The magic numbers in lookup.rs are the ones the search finds from the seed
+    /// 
+    #[test]
+    fn magic_numbers() {
+        let mut seed = MAGIC_SEED;
+        let bishops = find_magics(true, &mut seed);
+        let rooks = find_magics(false, &mut seed);
+        assert_eq!(bishops, BISHOP_MAGICS);
+        assert_eq!(rooks, ROOK_MAGICS);
+    }
+
+    /// The precomputed attack and ray tables
+    /// 
+    #[test]
+    fn attack_tables() {
+        assert_eq!(KING_ATTACKS[Square::A1], PIECE[Square::A2] | PIECE[Square::B2] | PIECE[Square::B1]);
+        assert_eq!(KNIGHT_ATTACKS[Square::H8], PIECE[Square::G6] | PIECE[Square::F7]);
+        assert_eq!(PAWN_ATTACKS[0][Square::A2], PIECE[Square::B3]);
+        assert_eq!(PAWN_ATTACKS[1][Square::E7], PIECE[Square::D6] | PIECE[Square::F6]);
+
+        // Known totals of moves from every square on an empty board
+        assert_eq!(KING_ATTACKS.iter().map(|b| b.count_ones()).sum::<u32>(), 420);
+        assert_eq!(KNIGHT_ATTACKS.iter().map(|b| b.count_ones()).sum::<u32>(), 336);
+        assert_eq!(PAWN_ATTACKS[0].iter().map(|b| b.count_ones()).sum::<u32>(), 98);
+
+        assert_eq!(BETWEEN[Square::A1][Square::D4], PIECE[Square::B2] | PIECE[Square::C3]);
+        assert_eq!(BETWEEN[Square::H1][Square::E1], PIECE[Square::G1] | PIECE[Square::F1]);
+        assert_eq!(BETWEEN[Square::A1][Square::B2], 0);
+        assert_eq!(BETWEEN[Square::A1][Square::B3], 0);
+        assert_eq!(LINE[Square::B3][Square::B7], MASK_FILE[1]);
+        assert_eq!(LINE[Square::C4][Square::A4], MASK_RANK[3]);
+        assert_eq!(LINE[Square::B2][Square::G7].count_ones(), 8);
+        assert_eq!(LINE[Square::A1][Square::B3], 0);
+
+        for a in 0..64 {
+            for b in 0..64 {
+                assert_eq!(BETWEEN[a][b], BETWEEN[b][a]);
+                assert_eq!(LINE[a][b], LINE[b][a]);
+                // Every square between two squares is on the line through them
+                assert_eq!(BETWEEN[a][b] & !LINE[a][b], 0);
+            }
+        }
+    }
+
+    /// The hash updated with every move is the same as the hash computed from scratch
+    /// 
+    #[test]
+    fn zobrist_hash() {
+        let mut seed: u64 = 0x2545_F491_4F6C_DD1D;
+        let mut random = || {
+            seed ^= seed << 13;
+            seed ^= seed >> 7;
+            seed ^= seed << 17;
+            seed
+        };
+
+        // Random games from positions with castling, en passant and promotions
+        for fen in [
+            "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1",
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+        ] {
+            for _ in 0..20 {
+                let mut chess = ChessBoard::from_fen(fen).unwrap();
+                let mut hashes = vec![chess.hash()];
+                for _ in 0..40 {
+                    let moves = chess.legal_moves();
+                    if moves.is_empty() || chess.game_result != GameResult::Ongoing { break; }
+                    let m = moves[random() as usize % moves.len()];
+                    assert!(chess.make_move(m).is_ok());
+                    assert_eq!(chess.hash(), chess.compute_hash());
+                    hashes.push(chess.hash());
+                }
+                // Taking back every move gives the earlier hashes
+                while chess.unmake_move().is_some() {
+                    hashes.pop();
+                    assert_eq!(Some(&chess.hash()), hashes.last());
+                }
+            }
+        }
+
+        // The same position reached by different move orders
+        let mut a = ChessBoard::new();
+        let mut b = ChessBoard::new();
+        assert!(a.apply_uci_moves(&["g1f3", "g8f6", "b1c3"]).is_ok());
+        assert!(b.apply_uci_moves(&["b1c3", "g8f6", "g1f3"]).is_ok());
+        assert_eq!(a.hash(), b.hash());
+
+        // The side to move, castling rights and en passant change the hash
+        let hash = |fen: &str| ChessBoard::from_fen(fen).unwrap().hash();
+        assert_ne!(hash("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"), hash("4k3/8/8/8/8/8/8/R3K3 b Q - 0 1"));
+        assert_ne!(hash("4k3/8/8/8/8/8/8/R3K3 w Q - 0 1"), hash("4k3/8/8/8/8/8/8/R3K3 w - - 0 1"));
+        assert_ne!(hash("4k3/8/8/8/3pP3/8/8/4K3 b - e3 0 1"), hash("4k3/8/8/8/3pP3/8/8/4K3 b - - 0 1"));
+
+        // An en passant square no pawn can capture on is not part of the position
+        assert_eq!(hash("4k3/8/8/8/4P3/8/8/4K3 b - e3 0 1"), hash("4k3/8/8/8/4P3/8/8/4K3 b - - 0 1"));
+    }
+
+    /// The engine finds known mates in 2 and 3 moves
+    /// 
+    #[test]
+    fn engine_mates() {
+        use crate::engine::{Engine, SearchLimits, Score};
+
+        let mut engine = Engine::new();
+        let limits = SearchLimits { depth: Some(6), ..Default::default() };
+
+        for (fen, mate, first_move) in [
+            // Mate in 2
+            ("r2qkb1r/pp2nppp/3p4/2pNN1B1/2BnP3/3P4/PPP2PPP/R2bK2R w KQkq - 1 1", 2, "Nf6+"),
+            ("kbK5/pp6/1P6/8/8/8/8/R7 w - - 0 1", 2, "Ra6"),
+            // Mate in 3
+            ("r5rk/5p1p/5R2/4B3/8/8/7P/7K w - - 0 1", 3, "Ra6+"),
+            ("2r3k1/p4p2/3Rp2p/1p2P1pK/8/1P4P1/P3Q2P/1q6 b - - 0 1", 3, "Qg6+"),
+        ] {
+            let mut chess = ChessBoard::new();
+            chess.load(fen.to_string());
+
+            let result = engine.search(&chess, limits);
+            assert_eq!(result.score, Score::Mate(mate), "{}", fen);
+            assert_eq!(chess.to_san(result.best_move.unwrap()), first_move, "{}", fen);
+            assert_eq!(result.pv.len() as i32, mate * 2 - 1);
+
+            // The mating line ends in checkmate
+            for m in result.pv {
+                assert!(chess.make_move(m).is_ok());
+            }
+            assert_ne!(chess.game_result, GameResult::Ongoing);
+            assert!(chess.legal_moves().is_empty());
+        }
+
+        // The side getting mated sees it coming
+        let mut chess = ChessBoard::new();
+        chess.load("r5rk/5p1p/R7/4B3/8/8/7P/7K b - - 1 1".to_string());
+        assert_eq!(engine.search(&chess, limits).score, Score::Mate(-2));
+
+        // A node limit stops the search, but still gives a legal move
+        let chess = ChessBoard::new();
+        let result = engine.search(&chess, SearchLimits { nodes: Some(2000), ..Default::default() });
+        assert!(result.nodes <= 2000 + 64);
+        assert!(chess.legal_moves().contains(&result.best_move.unwrap()));
+    }
+
+    #[test]
+    fn termination() {
+        let mut chess = ChessBoard::new();
+        assert_eq!(chess.termination, None);
+
+        // Checkmate, taken back with undo
+        chess.apply_uci_moves(&["f2f3", "e7e5", "g2g4", "d8h4"]).unwrap();
+        assert_eq!(chess.termination, Some(Termination::Checkmate));
+        chess.undo();
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        assert_eq!(chess.termination, None);
+
+        // Stalemate
+        chess.load("k7/8/8/8/8/8/5B2/1R5K w - - 0 1".to_string());
+        chess.apply_uci_moves(&["f2d4"]).unwrap();
+        assert_eq!(chess.game_result, GameResult::Draw);
+        assert_eq!(chess.termination, Some(Termination::Stalemate));
+
+        // Loading a position resets it
+        chess.load("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string());
+        assert_eq!(chess.termination, None);
+
+        // Threefold repetition
+        chess.draw_policy = DrawPolicy::AutoDraw;
+        chess.apply_uci_moves(&["g1f3", "g8f6", "f3g1", "f6g8", "g1f3", "g8f6", "f3g1"]).unwrap();
+        assert_eq!(chess.termination, None);
+        chess.apply_uci_moves(&["f6g8"]).unwrap();
+        assert_eq!(chess.game_result, GameResult::Draw);
+        assert_eq!(chess.termination, Some(Termination::ThreefoldRepetition));
+
+        // Fifty-move and seventy-five-move rule
+        chess.load("4k3/8/8/8/8/8/8/R3K3 w - - 99 80".to_string());
+        chess.apply_uci_moves(&["a1a2"]).unwrap();
+        assert_eq!(chess.termination, Some(Termination::FiftyMoveRule));
+        chess.load("4k3/8/8/8/8/8/8/R3K3 w - - 149 100".to_string());
+        chess.apply_uci_moves(&["a1a2"]).unwrap();
+        assert_eq!(chess.termination, Some(Termination::SeventyFiveMoveRule));
+
+        // Checkmate on the fiftieth move counts as checkmate
+        chess.load("6k1/8/6K1/8/8/8/8/R7 w - - 99 80".to_string());
+        chess.apply_uci_moves(&["a1a8"]).unwrap();
+        assert_eq!(chess.game_result, GameResult::White);
+        assert_eq!(chess.termination, Some(Termination::Checkmate));
+
+        // Ended by the players
+        let mut chess = ChessBoard::new();
+        chess.white_surrender();
+        assert_eq!(chess.game_result, GameResult::Black);
+        assert_eq!(chess.termination, Some(Termination::Resignation));
+
+        let mut chess = ChessBoard::new();
+        chess.black_surrender();
+        assert_eq!(chess.termination, Some(Termination::Resignation));
+
+        let mut chess = ChessBoard::new();
+        chess.mutual_draw();
+        assert_eq!(chess.termination, Some(Termination::Agreement));
+        assert_eq!(chess.termination.unwrap().to_string(), "agreement");
+
+        let mut chess = ChessBoard::new();
+        chess.abandon(true);
+        assert_eq!(chess.game_result, GameResult::Black);
+        assert_eq!(chess.termination, Some(Termination::Abandoned));
+        assert!(chess.move_piece(Square::E2, Square::E4).is_err());
+    }
+
+    #[test]
+    fn draw_policy() {
+        let shuffle = ["g1f3", "g8f6", "f3g1", "f6g8"];
+
+        // FIDE: threefold repetition can be claimed, fivefold ends the game
+        let mut chess = ChessBoard::new();
+        assert_eq!(chess.draw_policy, DrawPolicy::Fide);
+        assert!(chess.claim_draw().is_err());
+        for _ in 0..2 {
+            chess.apply_uci_moves(&shuffle).unwrap();
+        }
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        assert!(chess.can_claim_draw());
+
+        // The fourth time is still only a claim, the fifth ends the game
+        chess.apply_uci_moves(&shuffle).unwrap();
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        assert!(chess.can_claim_draw());
+        chess.apply_uci_moves(&shuffle).unwrap();
+        assert_eq!(chess.game_result, GameResult::Draw);
+        assert_eq!(chess.termination, Some(Termination::FivefoldRepetition));
+        assert!(!chess.can_claim_draw());
+        assert!(chess.claim_draw().is_err());
+
+        // Claiming a threefold repetition
+        chess.reset();
+        for _ in 0..2 {
+            chess.apply_uci_moves(&shuffle).unwrap();
+        }
+        assert!(chess.claim_draw().is_ok());
+        assert_eq!(chess.game_result, GameResult::Draw);
+        assert_eq!(chess.termination, Some(Termination::ThreefoldRepetition));
+
+        // FIDE: 50 moves can be claimed, 75 moves ends the game
+        chess.load("k7/8/8/8/8/8/8/3R3K w - - 99 70".to_string());
+        chess.apply_uci_moves(&["h1h2"]).unwrap();
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        assert!(chess.can_claim_draw());
+        assert!(chess.claim_draw().is_ok());
+        assert_eq!(chess.termination, Some(Termination::FiftyMoveRule));
+
+        chess.load("k7/8/8/8/8/8/8/3R3K w - - 148 95".to_string());
+        chess.apply_uci_moves(&["h1h2"]).unwrap();
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        chess.apply_uci_moves(&["a8a7"]).unwrap();
+        assert_eq!(chess.game_result, GameResult::Draw);
+        assert_eq!(chess.termination, Some(Termination::SeventyFiveMoveRule));
+
+        // No automatic draws, but a draw can still be claimed
+        chess.draw_policy = DrawPolicy::None;
+        chess.load("k7/8/8/8/8/8/8/3R3K w - - 149 95".to_string());
+        assert_eq!(chess.draw_policy, DrawPolicy::None);
+        chess.apply_uci_moves(&["h1h2"]).unwrap();
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        assert!(chess.can_claim_draw());
+
+        chess.reset();
+        for _ in 0..4 {
+            chess.apply_uci_moves(&shuffle).unwrap();
+        }
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+        assert!(chess.claim_draw().is_ok());
+        assert_eq!(chess.termination, Some(Termination::ThreefoldRepetition));
+    }
+
+    #[test]
+    fn insufficient_material() {
+        let mut chess = ChessBoard::new();
+
+        // Dead positions end the game when loaded
+        for fen in [
+            "4k3/8/8/8/8/8/8/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1K3 b - - 0 1",
+            "2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/2B1K3 w - - 0 1",
+        ] {
+            chess.load(fen.to_string());
+            assert!(chess.is_insufficient_material(), "{}", fen);
+            assert_eq!(chess.game_result, GameResult::Draw, "{}", fen);
+            assert_eq!(chess.termination, Some(Termination::InsufficientMaterial), "{}", fen);
+        }
+
+        for fen in [
+            "4k3/8/8/8/8/8/8/4KNN1 w - - 0 1",
+            "4k3/8/8/8/8/8/8/4KBN1 w - - 0 1",
+            "4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1",
+            "4kb2/8/8/8/8/8/8/4KB2 w - - 0 1",
+            "4kn2/8/8/8/8/8/8/4KN2 w - - 0 1",
+            "4k3/8/8/8/8/8/4P3/4K3 w - - 0 1",
+            "4k3/8/8/8/8/8/8/R3K3 w - - 0 1",
+        ] {
+            chess.load(fen.to_string());
+            assert!(!chess.is_insufficient_material(), "{}", fen);
+            assert_eq!(chess.game_result, GameResult::Ongoing, "{}", fen);
+        }
+
+        // Capturing the last piece that could mate
+        chess.load("4k3/8/8/8/8/8/3r4/3BK3 w - - 0 1".to_string());
+        chess.apply_uci_moves(&["e1d2"]).unwrap();
+        assert_eq!(chess.termination, Some(Termination::InsufficientMaterial));
+        chess.undo();
+        assert_eq!(chess.game_result, GameResult::Ongoing);
+
+        // Mating material of each player
+        for (fen, white, black) in [
+            ("4k3/8/8/8/8/8/8/4K3 w - - 0 1", true, true),
+            ("4k3/8/8/8/8/8/8/4KN2 w - - 0 1", true, true),
+            ("4k3/8/8/8/8/8/8/R3K3 w - - 0 1", false, true),
+            ("4k3/3p4/8/8/8/8/8/4K3 w - - 0 1", true, false),
+            // A knight or a bishop can mate a king blocked by its own pieces
+            ("4kq2/8/8/8/8/8/8/4KN2 w - - 0 1", true, false),
+            ("4kr2/8/8/8/8/8/8/4KN2 w - - 0 1", false, false),
+            ("4k3/3p4/8/8/8/8/8/4KB2 w - - 0 1", false, false),
+            ("2b1k3/8/8/8/8/8/8/4KB2 w - - 0 1", true, true),
+            ("4kb2/8/8/8/8/8/8/4KB2 w - - 0 1", false, false),
+        ] {
+            chess.load(fen.to_string());
+            assert_eq!(chess.has_insufficient_mating_material(true), white, "{}", fen);
+            assert_eq!(chess.has_insufficient_mating_material(false), black, "{}", fen);
+        }
+    }
+
+    #[test]
+    fn clock() {
+        use crate::clock::{Stage, Increment, TimeControlError};
+        let secs = Duration::from_secs;
+
+        assert_eq!(TimeControl::new(vec![]), Err(TimeControlError::NoStages));
+        assert_eq!(TimeControlError::NoStages.to_string(), "A time control needs at least one stage");
+
+        // Bronstein gives back the time used, up to the delay
+        let mut clock = Clock::new(TimeControl::bronstein(secs(60), secs(5)));
+        clock.start(true, secs(0));
+        assert_eq!(clock.press(secs(3)), None);
+        assert_eq!(clock.press(secs(11)), None);
+        assert_eq!(clock.remaining(true, secs(11)), secs(60));
+        assert_eq!(clock.remaining(false, secs(11)), secs(57));
+
+        // A simple delay is waited out before the clock counts down
+        let mut clock = Clock::new(TimeControl::simple_delay(secs(60), secs(5)));
+        clock.start(true, secs(0));
+        assert_eq!(clock.remaining(true, secs(4)), secs(60));
+        assert_eq!(clock.remaining(true, secs(7)), secs(58));
+        assert_eq!(clock.press(secs(3)), None);
+        assert_eq!(clock.press(secs(11)), None);
+        assert_eq!(clock.remaining(true, secs(11)), secs(60));
+        assert_eq!(clock.remaining(false, secs(11)), secs(57));
+
+        // Two moves with an increment, then a minute for the rest of the game
+        let control = TimeControl::new(vec![
+            Stage { moves: Some(2), time: secs(10), increment: Increment::Fischer(secs(1)) },
+            Stage { moves: None, time: secs(60), increment: Increment::None },
+        ]).unwrap();
+        let mut clock = Clock::new(control);
+        clock.start(true, secs(0));
+        for t in 1..=4 {
+            assert_eq!(clock.press(secs(t)), None);
+        }
+        assert_eq!(clock.remaining(true, secs(4)), secs(70));
+        assert_eq!(clock.remaining(false, secs(4)), secs(70));
+        clock.press(secs(6));
+        assert_eq!(clock.remaining(true, secs(6)), secs(68));
+
+        // The last stage starts again when it is finished
+        let control = TimeControl::new(vec![Stage { moves: Some(1), time: secs(10), increment: Increment::None }]).unwrap();
+        let mut clock = Clock::new(control);
+        clock.start(true, secs(0));
+        clock.press(secs(4));
+        assert_eq!(clock.remaining(true, secs(4)), secs(16));
+
+        // A stopped clock doesn't count down
+        clock.stop(secs(5));
+        assert!(!clock.is_running());
+        assert_eq!(clock.press(secs(100)), None);
+        assert_eq!(clock.remaining(false, secs(100)), secs(9));
+
+        // Flag fall
+        assert_eq!(clock.flag(), None);
+        clock.start(false, secs(100));
+        assert_eq!(clock.update(secs(108)), None);
+        assert_eq!(clock.update(secs(109)), Some(false));
+        assert_eq!(clock.press(secs(110)), Some(false));
+        assert_eq!(clock.remaining(false, secs(110)), secs(0));
+
+        // The clock stops when the game ends
+        let mut chess = ChessBoard::new();
+        chess.start_clock(TimeControl::sudden_death(secs(60)), secs(0));
+        for (t, (from, to)) in [(Square::F2, Square::F3), (Square::E7, Square::E5), (Square::G2, Square::G4), (Square::D8, Square::H4)].into_iter().enumerate() {
+            assert_eq!(chess.move_piece_at(from, to, secs(t as u64 + 1)), Ok(true));
+        }
+        assert_eq!(chess.termination, Some(Termination::Checkmate));
+        assert!(!chess.clock.as_ref().unwrap().is_running());
+        assert!(!chess.update_clock(secs(1000)));
+        assert_eq!(chess.game_result, GameResult::Black);
+        assert_eq!(chess.clock.as_ref().unwrap().remaining(true, secs(1000)), secs(58));
+
+        // A promotion without a piece doesn't switch the clock
+        chess.load("k7/4P3/8/8/8/8/8/K7 w - - 0 1".to_string());
+        assert!(chess.clock.is_none());
+        chess.start_clock(TimeControl::sudden_death(secs(60)), secs(0));
+        assert_eq!(chess.move_piece_at(Square::E7, Square::E8, secs(1)), Ok(false));
+        assert!(chess.clock.as_ref().unwrap().white_to_move());
+        assert_eq!(chess.handle_promotion(Square::E7, Square::E8, PieceType::WhiteQueen), Ok(true));
+        assert!(!chess.clock.as_ref().unwrap().white_to_move());
+
+        // Running out of time loses, unless the other player can't checkmate
+        assert!(chess.update_clock(secs(61)));
+        assert_eq!(chess.game_result, GameResult::White);
+        assert_eq!(chess.termination, Some(Termination::Timeout));
+        assert!(chess.move_piece(Square::A8, Square::B8).is_err());
+
+        chess.load("k6r/8/8/8/8/8/8/K7 b - - 0 1".to_string());
+        chess.start_clock(TimeControl::sudden_death(secs(60)), secs(0));
+        assert!(chess.update_clock(secs(60)));
+        assert_eq!(chess.game_result, GameResult::Draw);
+        assert_eq!(chess.termination, Some(Termination::Timeout));
+    }
+
+    #[test]
+    fn clock_history() {
+        let secs = Duration::from_secs;
+        let mut chess = ChessBoard::new();
+        chess.start_clock(TimeControl::sudden_death(secs(60)), secs(0));
+
+        chess.update_clock(secs(5));
+        let before_e4 = chess.clock.clone();
+        chess.move_piece(Square::E2, Square::E4).unwrap();
+        chess.update_clock(secs(12));
+        let before_e5 = chess.clock.clone();
+        chess.move_piece(Square::E7, Square::E5).unwrap();
+        let after_e5 = chess.clock.clone();
+
+        // Undo gives back the clock as it was before the move, and redo as it was when the move was undone
+        chess.undo();
+        assert_eq!(chess.clock, before_e5);
+        assert!(!chess.clock.as_ref().unwrap().white_to_move());
+        chess.undo();
+        assert_eq!(chess.clock, before_e4);
+        assert!(chess.clock.as_ref().unwrap().white_to_move());
+        chess.redo();
+        assert_eq!(chess.clock, before_e5);
+        chess.redo();
+        assert_eq!(chess.clock, after_e5);
+        assert_eq!(chess.clock.as_ref().unwrap().remaining(false, secs(12)), secs(53));
+
+        // Moves taken back when a list of moves fails leave the clock as it was
+        chess.update_clock(secs(20));
+        let before = chess.clock.clone();
+        assert!(chess.apply_uci_moves(&["g1f3", "b8c6", "f3e4"]).is_err());
+        assert_eq!(chess.clock, before);
+        assert!(chess.clock.as_ref().unwrap().white_to_move());
+
+        chess.unmake_move();
+        assert_eq!(chess.clock, before_e5);
+    }
+
+    #[test]
+    fn clock_move_times() {
+        let secs = Duration::from_secs;
+        let mut chess = ChessBoard::new();
+        chess.start_clock(TimeControl::sudden_death(secs(10)), secs(0));
+
+        // Moves without a time are made at the last update of the clock
+        chess.move_piece(Square::E2, Square::E4).unwrap();
+        assert_eq!(chess.clock.as_ref().unwrap().remaining(true, secs(0)), secs(10));
+        chess.update_clock(secs(4));
+        chess.move_piece(Square::E7, Square::E5).unwrap();
+        assert_eq!(chess.clock.as_ref().unwrap().remaining(false, secs(4)), secs(6));
+
+        // White's time has run out by now, but the clock doesn't know until it is updated
+        chess.move_piece(Square::G1, Square::F3).unwrap();
+        assert_eq!(chess.clock.as_ref().unwrap().remaining(true, secs(4)), secs(10));
+
+        // A move with a time is not made if the time has run out
+        assert_eq!(chess.move_piece_at(Square::B8, Square::C6, secs(30)), Err(MoveError::GameOver));
+        assert_eq!(chess.piece_at(Square::B8), PieceType::BlackKnight);
+        assert_eq!(chess.game_result, GameResult::White);
+        assert_eq!(chess.termination, Some(Termination::Timeout));
+    }
+
+    #[test]
+    fn captures_and_quiet_moves() {
+        let mut chess = ChessBoard::new();
+
+        for fen in [
+            "r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1",
+            "8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1",
+            "r3k2r/Pppp1ppp/1b3nbN/nP6/BBP1P3/q4N2/Pp1P2PP/R2Q1RK1 w kq - 0 1",
+            "rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8",
+            "8/8/8/2k5/2pP4/8/B7/4K3 b - d3 0 3",
+        ] {
+            chess.load(fen.to_string());
+            let captures = chess.legal_captures();
+            let quiets = chess.legal_quiet_moves();
+
+            assert!(captures.iter().all(|m| m.capture || m.is_promotion()), "{}", fen);
+            assert!(quiets.iter().all(|m| !m.capture && !m.is_promotion()), "{}", fen);
+
+            // Together they are every legal move, in the same order
+            let mut moves = [captures, quiets].concat();
+            moves.sort_by_key(|m| (m.from, m.to));
+            assert_eq!(moves, chess.legal_moves(), "{}", fen);
+        }
+    }
+
+    #[test]
+    fn static_exchange_evaluation() {
+        let mut chess = ChessBoard::new();
+
+        for (fen, uci, see) in [
+            // Nothing to capture, and nothing attacking the square
+            ("rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1", "g1f3", 0),
+            // Moving a piece to a square attacked by a pawn
+            ("4k3/8/3p4/8/8/3N4/8/4K3 w - - 0 1", "d3e5", -300),
+            // En passant
+            ("4k3/8/8/3pP3/8/8/8/4K3 w - d6 0 1", "e5d6", 100),
+            // Promotion
+            ("4k3/P7/8/8/8/8/8/4K3 w - - 0 1", "a7a8q", 800),
+            // The rook behind the capturing rook recaptures (x-ray)
+            ("4r1k1/8/8/4p3/8/8/4R3/4R1K1 w - - 0 1", "e2e5", 100),
+            ("4r1k1/8/8/4p3/8/8/4R3/6K1 w - - 0 1", "e2e5", -400),
+            // The king can't recapture a defended piece
+            ("4k3/3p4/8/8/8/8/3R4/3RK3 w - - 0 1", "d2d7", 100),
+            ("4k3/3p4/8/8/8/8/3R4/4K3 w - - 0 1", "d2d7", -400),
+            // Black captures
+            ("4k3/8/8/3q4/4P3/8/8/4K3 b - - 0 1", "d5e4", 100),
+            ("4k3/8/8/3q4/4P3/5P2/8/4K3 b - - 0 1", "d5e4", -800),
+        ] {
+            chess.load(fen.to_string());
+            let m = chess.parse_uci(uci).unwrap();
+            assert_eq!(chess.see(m), see, "{} {}", fen, uci);
+        }
+    }
+
+    #[test]
+    fn quiescence_search() {
+        use crate::engine::{Engine, SearchLimits};
+
+        // Taking the pawn looks good at depth 1, but the queen is lost
+        let mut chess = ChessBoard::new();
+        chess.load("4k3/8/4p3/3p4/8/8/8/3QK3 w - - 0 1".to_string());
+
+        let mut engine = Engine::new();
+        let result = engine.search(&chess, SearchLimits { depth: Some(1), ..Default::default() });
+        assert_ne!(result.best_move.unwrap().to_uci(), "d1d5");
+    }
+
+    #[test]
+    fn transposition_table() {
+        use crate::engine::{Engine, SearchLimits, Score};
+
+        let limits = SearchLimits { depth: Some(5), ..Default::default() };
+        let mut engine = Engine::new();
+        let mut chess = ChessBoard::new();
+
+        // Mate scores stored at one ply are still right when found at another ply, or in a later search
+        chess.load("r5rk/5p1p/R7/4B3/8/8/7P/7K w - - 0 1".to_string());
+        let mut pv = vec![];
+        for _ in 0..2 {
+            let result = engine.search(&chess, limits);
+            assert_eq!(result.score, Score::Mate(2));
+            pv = result.pv;
+        }
+        chess.make_move(pv[0]).unwrap();
+        chess.make_move(pv[1]).unwrap();
+        assert_eq!(engine.search(&chess, limits).score, Score::Mate(1));
+
+        // A table kept between searches gives the same result as an empty one, with fewer positions visited
+        chess.load("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string());
+        let limits = SearchLimits { depth: Some(4), ..Default::default() };
+        let mut fresh = Engine::new();
+        fresh.set_hash_size(1);
+        let first = fresh.search(&chess, limits);
+        let second = fresh.search(&chess, limits);
+        assert_eq!(first.score, second.score);
+        assert!(second.nodes < first.nodes);
+
+        fresh.clear_hash();
+        assert_eq!(fresh.search(&chess, limits).nodes, first.nodes);
+    }
+
+    #[test]
+    fn evaluation() {
+        use crate::eval::{evaluate, breakdown};
+
+        // Positions with the colours swapped have the opposite score
+        let mirrored = [
+            ("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1", "r3k2r/pppbbppp/2n2q1P/1P2p3/3pn3/BN2PNP1/P1PPQPB1/R3K2R b KQkq - 0 1"),
+            ("8/2p5/3p4/KP5r/1R3p1k/8/4P1P1/8 w - - 0 1", "8/4p1p1/8/1r3P1K/kp5R/3P4/2P5/8 b - - 0 1"),
+            ("rnbq1k1r/pp1Pbppp/2p5/8/2B5/8/PPP1NnPP/RNBQK2R w KQ - 1 8", "rnbqk2r/ppp1nNpp/8/2b5/8/2P5/PP1pBPPP/RNBQ1K1R b kq - 1 8"),
+        ];
+        let mut chess = ChessBoard::new();
+        assert_eq!(evaluate(&chess), 0);
+        for (fen, mirror) in mirrored {
+            chess.load(fen.to_string());
+            let score = evaluate(&chess);
+            chess.load(mirror.to_string());
+            assert_eq!(evaluate(&chess), -score, "{}", fen);
+        }
+
+        // The terms add up to the total
+        chess.load("r3k2r/p1ppqpb1/bn2pnp1/3PN3/1p2P3/2N2Q1p/PPPBBPPP/R3K2R w KQkq - 0 1".to_string());
+        let evaluation = breakdown(&chess);
+        assert_eq!(evaluation.phase, 24);
+        assert_eq!(evaluation.total, evaluation.material.score() + evaluation.piece_squares.score() + evaluation.mobility.score()
+            + evaluation.pawn_structure.score() + evaluation.king_safety.score() + evaluation.bishop_pair.score());
+
+        // Only pawns and kings is the endgame
+        chess.load("4k3/pppp4/8/8/8/8/PPPP4/4K3 w - - 0 1".to_string());
+        assert_eq!(breakdown(&chess).phase, 0);
+
+        // Doubled and isolated pawns are worse than connected ones
+        chess.load("4k3/8/8/8/8/8/PPP5/4K3 w - - 0 1".to_string());
+        let connected = breakdown(&chess).pawn_structure.white;
+        chess.load("4k3/8/8/8/8/P7/P6P/4K3 w - - 0 1".to_string());
+        let broken = breakdown(&chess).pawn_structure.white;
+        assert!(broken < connected);
+
+        // A passed pawn is worth more the closer it is to promotion, and not passed when blocked from the side
+        chess.load("4k3/8/8/8/3P4/8/8/4K3 w - - 0 1".to_string());
+        let passed = breakdown(&chess).pawn_structure.white;
+        chess.load("4k3/3P4/8/8/8/8/8/4K3 w - - 0 1".to_string());
+        assert!(breakdown(&chess).pawn_structure.white > passed);
+        chess.load("4k3/4p3/8/8/3P4/8/8/4K3 w - - 0 1".to_string());
+        let evaluation = breakdown(&chess);
+        assert!(evaluation.pawn_structure.white < passed);
+        assert!(evaluation.pawn_structure.black < 0);
+
+        // Bishop pair
+        chess.load("4k3/8/8/8/8/8/8/2B1KB2 w - - 0 1".to_string());
+        assert!(breakdown(&chess).bishop_pair.white > 0);
+        chess.load("4k3/8/8/8/8/8/8/2N1KB2 w - - 0 1".to_string());
+        assert_eq!(breakdown(&chess).bishop_pair.white, 0);
+
+        // A king behind its pawns is safer than one in the open
+        chess.load("rnbq1rk1/ppppbppp/5n2/4p3/4P3/5N2/PPPPBPPP/RNBQ1RK1 w - - 0 1".to_string());
+        let castled = breakdown(&chess).king_safety.white;
+        chess.load("rnbq1rk1/ppppbppp/5n2/4p3/4P3/5NK1/PPPPBPPP/RNBQ1R2 w - - 0 1".to_string());
+        assert!(breakdown(&chess).king_safety.white < castled);
+
+        // A piece up is a lot better
+        chess.load("rnbqkb1r/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1".to_string());
+        assert!(evaluate(&chess) > 200);
+    }
+
+    #[test]
+    fn uci_position_and_go() {
+        use crate::engine::uci::{parse_position, GoOptions, UciError};
+        let ms = Duration::from_millis;
+
+        let chess = parse_position(&["fen", "4k3/8/8/8/8/8/4P3/4K3", "w", "-", "-", "0", "1", "moves", "e2e4", "e8d7"]).unwrap();
+        assert_eq!(chess.to_fen(), "8/3k4/8/8/4P3/8/8/4K3 w - - 1 2");
+        assert_eq!(parse_position(&["startpos"]).unwrap().to_fen(), ChessBoard::new().to_fen());
+        assert_eq!(parse_position(&[]).err(), Some(UciError::MissingPosition));
+        assert!(matches!(parse_position(&["fen", "8/8/8", "w"]), Err(UciError::InvalidFen(_))));
+        assert!(matches!(parse_position(&["startpos", "moves", "e2e4", "e2e4"]), Err(UciError::InvalidMove(_))));
+
+        let go = GoOptions::parse(&["depth", "6", "nodes", "-5", "btime", "10000", "binc", "100", "movestogo", "0", "wtime", "x", "infinite"]);
+        assert_eq!(go.depth, Some(6));
+        assert_eq!(go.nodes, Some(1));
+        assert_eq!((go.wtime, go.btime, go.binc, go.movestogo), (None, Some(ms(10000)), ms(100), Some(1)));
+        assert!(go.infinite && !go.ponder);
+
+        // An infinite search has no limits, and a movetime is used instead of the clock
+        assert_eq!(go.limits(false, ms(50)), engine::SearchLimits::default());
+        let go = GoOptions::parse(&["wtime", "10000", "btime", "20000", "winc", "200", "binc", "400", "movetime", "500"]);
+        assert_eq!(go.limits(true, ms(50)).time, Some(ms(500)));
+
+        // The time budget is the time left over the moves to go, and three quarters of the increment
+        let go = GoOptions::parse(&["wtime", "3050", "btime", "6050", "winc", "200", "binc", "400", "ponder"]);
+        assert!(go.ponder);
+        assert_eq!(go.limits(true, ms(50)).time, Some(ms(100 + 150)));
+        assert_eq!(go.limits(false, ms(50)).time, Some(ms(200 + 300)));
+
+        // Never more than the time left, and never nothing
+        let go = GoOptions::parse(&["wtime", "100", "winc", "1000"]);
+        assert_eq!(go.limits(true, ms(50)).time, Some(ms(50)));
+        assert_eq!(go.limits(true, ms(500)).time, Some(ms(1)));
+    }
+
+    #[test]
+    fn uci_commands() {
+        use std::sync::{Arc, Mutex};
+        use crate::engine::uci::Uci;
+
+        let output = Arc::new(Mutex::new(Vec::<String>::new()));
+        let lines = Arc::clone(&output);
+        let mut uci = Uci::new(move |line: &str| lines.lock().unwrap().push(line.to_string()));
+        let last = |output: &Arc<Mutex<Vec<String>>>| output.lock().unwrap().last().cloned().unwrap_or_default();
+        let bestmoves = |output: &Arc<Mutex<Vec<String>>>| output.lock().unwrap().iter().filter(|line| line.starts_with("bestmove")).count();
+
+        assert!(uci.command("uci"));
+        assert_eq!(last(&output), "uciok");
+        uci.command("isready");
+        assert_eq!(last(&output), "readyok");
+        uci.command("xyzzy");
+        assert_eq!(last(&output), "info string Unknown command xyzzy");
+
+        // Options
+        uci.command("setoption name Move Overhead value 120");
+        assert_eq!(uci.move_overhead(), Duration::from_millis(120));
+        uci.command("setoption name Hash value lots");
+        assert_eq!(last(&output), "info string Invalid value for Hash: lots");
+        uci.command("setoption name Ponder value true");
+        assert_eq!(last(&output), "info string Unknown option Ponder");
+
+        // An invalid position leaves the position as it was
+        uci.command("position startpos moves e2e4");
+        let fen = uci.chess().to_fen();
+        uci.command("position startpos moves e2e4 e7e6 e4e6");
+        assert!(last(&output).starts_with("info string Invalid moves: "));
+        assert_eq!(uci.chess().to_fen(), fen);
+
+        // An infinite search sends bestmove only after stop
+        uci.command("go infinite");
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(bestmoves(&output), 0);
+        uci.command("stop");
+        assert_eq!(bestmoves(&output), 1);
+        assert!(last(&output).starts_with("bestmove "));
+
+        // Pondering goes on after ponderhit, and sends bestmove when the time of the go command is used
+        uci.command("go ponder wtime 3000 btime 3000");
+        std::thread::sleep(Duration::from_millis(50));
+        assert_eq!(bestmoves(&output), 1);
+        uci.command("ponderhit");
+        let start = std::time::Instant::now();
+        while bestmoves(&output) == 1 && start.elapsed() < Duration::from_secs(10) {
+            std::thread::sleep(Duration::from_millis(1));
+        }
+        assert_eq!(bestmoves(&output), 2);
+
+        assert!(!uci.command("quit"));
+        uci.stop_search();
+    }
 }
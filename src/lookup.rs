@@ -1,12 +1,14 @@
 
 pub mod tables {
 
+    use std::sync::OnceLock;
+
     type BitBoard = u64;
     
     /*
     Static Mask Rank 1 Precomputation to create arrays of precomutation
     */
-    static MASK_RANK_1: BitBoard = ((1 as BitBoard) << (8 * 1)) - 1;
+    static MASK_RANK_1: BitBoard = ((1 as BitBoard) << 8) - 1;
     
     /*
     Static Mask Rank Precomputation
@@ -18,13 +20,13 @@ pub mod tables {
     */
     pub static MASK_RANK: [BitBoard; 8] = [
         MASK_RANK_1,
-        MASK_RANK_1 << 8 * 1,
-        MASK_RANK_1 << 8 * 2,
-        MASK_RANK_1 << 8 * 3,
-        MASK_RANK_1 << 8 * 4,
-        MASK_RANK_1 << 8 * 5,
-        MASK_RANK_1 << 8 * 6,
-        MASK_RANK_1 << 8 * 7,
+        MASK_RANK_1 << 8,
+        MASK_RANK_1 << (8 * 2),
+        MASK_RANK_1 << (8 * 3),
+        MASK_RANK_1 << (8 * 4),
+        MASK_RANK_1 << (8 * 5),
+        MASK_RANK_1 << (8 * 6),
+        MASK_RANK_1 << (8 * 7),
     ];
     
     
@@ -37,19 +39,19 @@ pub mod tables {
     #[allow(dead_code)]
     pub static CLEAR_RANK: [BitBoard; 8] = [
         !MASK_RANK_1,
-        !(MASK_RANK_1 << 8 * 1),
-        !(MASK_RANK_1 << 8 * 2),
-        !(MASK_RANK_1 << 8 * 3),
-        !(MASK_RANK_1 << 8 * 4),
-        !(MASK_RANK_1 << 8 * 5),
-        !(MASK_RANK_1 << 8 * 6),
-        !(MASK_RANK_1 << 8 * 7)
+        !(MASK_RANK_1 << 8),
+        !(MASK_RANK_1 << (8 * 2)),
+        !(MASK_RANK_1 << (8 * 3)),
+        !(MASK_RANK_1 << (8 * 4)),
+        !(MASK_RANK_1 << (8 * 5)),
+        !(MASK_RANK_1 << (8 * 6)),
+        !(MASK_RANK_1 << (8 * 7))
     ];
     
     /*
     Static Mask File 1 Precomputation to create arrays of precomutation
     */
-    static MASK_FILE_1: BitBoard = (1 as BitBoard) << (8 * 7) | (1 as BitBoard) << (8 * 6) | (1 as BitBoard) << (8 * 5) | (1 as BitBoard) << (8 * 4) | (1 as BitBoard) << (8 * 3) | (1 as BitBoard) << (8 * 2) | (1 as BitBoard) << (8 * 1) | (1 as BitBoard) << (8 * 0);
+    static MASK_FILE_1: BitBoard = (1 as BitBoard) << (8 * 7) | (1 as BitBoard) << (8 * 6) | (1 as BitBoard) << (8 * 5) | (1 as BitBoard) << (8 * 4) | (1 as BitBoard) << (8 * 3) | (1 as BitBoard) << (8 * 2) | (1 as BitBoard) << 8 | (1 as BitBoard);
     
     /*
     Static Mask Rank Precomputation
@@ -76,6 +78,7 @@ pub mod tables {
     
     Ex. Used to remove white pieces from row 6 => white_pieces & CLEAR_FILE[5]
     */
+    #[allow(dead_code)]
     pub static CLEAR_FILE: [BitBoard; 8] = [
         !MASK_FILE_1,
         !(MASK_FILE_1 << 1),
@@ -86,6 +89,11 @@ pub mod tables {
         !(MASK_FILE_1 << 6),
         !(MASK_FILE_1 << 7)
     ];
+
+    /*
+    Bitmask of the dark squares (A1, C1, ..., B2, ...), the light squares are !DARK_SQUARES
+    */
+    pub static DARK_SQUARES: BitBoard = 0xAA55_AA55_AA55_AA55;
     
     /*
     Precomputed values of BitBoard for every square on the board
@@ -157,11 +165,315 @@ pub mod tables {
         (1 as BitBoard) << 63,
         (0 as BitBoard)
     ];
+
+    /*
+    Moves of a piece stepping once in each direction from a square, clipped at the edges of the board
+    */
+    const fn step_attacks(square: usize, steps: &[(i32, i32)]) -> BitBoard {
+        let mut attacks: BitBoard = 0;
+        let mut i = 0;
+        while i < steps.len() {
+            let r = (square / 8) as i32 + steps[i].0;
+            let f = (square % 8) as i32 + steps[i].1;
+            if r >= 0 && r < 8 && f >= 0 && f < 8 {
+                attacks |= (1 as BitBoard) << (r * 8 + f);
+            }
+            i += 1;
+        }
+        attacks
+    }
+
+    const fn step_attacks_table(steps: &[(i32, i32)]) -> [BitBoard; 64] {
+        let mut table: [BitBoard; 64] = [0; 64];
+        let mut square = 0;
+        while square < 64 {
+            table[square] = step_attacks(square, steps);
+            square += 1;
+        }
+        table
+    }
+
+    /*
+    Precomputed squares a king attacks from every square
+    */
+    pub static KING_ATTACKS: [BitBoard; 64] = step_attacks_table(&[(1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1)]);
+
+    /*
+    Precomputed squares a knight attacks from every square
+    */
+    pub static KNIGHT_ATTACKS: [BitBoard; 64] = step_attacks_table(&[(1, -2), (2, -1), (2, 1), (1, 2), (-1, 2), (-2, 1), (-2, -1), (-1, -2)]);
+
+    /*
+    Precomputed squares a pawn attacks from every square, PAWN_ATTACKS[0] for white pawns and PAWN_ATTACKS[1] for black pawns
+    */
+    pub static PAWN_ATTACKS: [[BitBoard; 64]; 2] = [
+        step_attacks_table(&[(1, -1), (1, 1)]),
+        step_attacks_table(&[(-1, -1), (-1, 1)])
+    ];
+
+    /*
+    Precomputed rays between two squares on the same rank, file or diagonal
+    BETWEEN[a][b] has the squares strictly between a and b, LINE[a][b] has the whole line through a and b (edge to edge)
+    Both are 0 if the squares are not on the same line
+
+    Ex. Used to find the squares that block a check => BETWEEN[king][checker]
+    */
+    const fn ray_tables() -> ([[BitBoard; 64]; 64], [[BitBoard; 64]; 64]) {
+        let directions: [(i32, i32); 8] = [(1, -1), (1, 0), (1, 1), (0, 1), (-1, 1), (-1, 0), (-1, -1), (0, -1)];
+        let mut between: [[BitBoard; 64]; 64] = [[0; 64]; 64];
+        let mut line: [[BitBoard; 64]; 64] = [[0; 64]; 64];
+
+        let mut a = 0;
+        while a < 64 {
+            let mut d = 0;
+            while d < 8 {
+                let (dr, df) = directions[d];
+
+                // The whole line through a in this direction and the opposite direction
+                let mut full: BitBoard = (1 as BitBoard) << a;
+                let mut sign = -1;
+                while sign <= 1 {
+                    let mut r = (a / 8) as i32 + sign * dr;
+                    let mut f = (a % 8) as i32 + sign * df;
+                    while r >= 0 && r < 8 && f >= 0 && f < 8 {
+                        full |= (1 as BitBoard) << (r * 8 + f);
+                        r += sign * dr;
+                        f += sign * df;
+                    }
+                    sign += 2;
+                }
+
+                // Walk towards every square b on the ray, storing the squares passed on the way
+                let mut passed: BitBoard = 0;
+                let mut r = (a / 8) as i32 + dr;
+                let mut f = (a % 8) as i32 + df;
+                while r >= 0 && r < 8 && f >= 0 && f < 8 {
+                    let b = (r * 8 + f) as usize;
+                    between[a][b] = passed;
+                    line[a][b] = full;
+                    passed |= (1 as BitBoard) << b;
+                    r += dr;
+                    f += df;
+                }
+                d += 1;
+            }
+            a += 1;
+        }
+
+        (between, line)
+    }
+
+    static RAY_TABLES: ([[BitBoard; 64]; 64], [[BitBoard; 64]; 64]) = ray_tables();
+
+    pub static BETWEEN: [[BitBoard; 64]; 64] = RAY_TABLES.0;
+
+    pub static LINE: [[BitBoard; 64]; 64] = RAY_TABLES.1;
+
+    /*
+    Random keys for Zobrist hashing of positions (https://www.chessprogramming.org/Zobrist_Hashing)
+    The hash of a position is every key of its pieces, castling rights, en passant file and side to move combined with xor
+
+    pieces[piece_type][square] in the order of PieceType, castling[rights] with the rights as bits (K = 1, Q = 2, k = 4, q = 8)
+    */
+    pub struct ZobristKeys {
+        pub pieces: [[BitBoard; 64]; 12],
+        pub castling: [BitBoard; 16],
+        pub en_passant: [BitBoard; 8],
+        pub black_to_move: BitBoard,
+    }
+
+    /*
+    Fills the keys with pseudo random numbers (splitmix64) with a fixed seed, so the hashes are the same every run
+    */
+    const fn zobrist_keys() -> ZobristKeys {
+        let mut keys = ZobristKeys {
+            pieces: [[0; 64]; 12],
+            castling: [0; 16],
+            en_passant: [0; 8],
+            black_to_move: 0,
+        };
+
+        let mut state: u64 = 0x0123_4567_89AB_CDEF;
+        let mut i = 0;
+        while i < 12 * 64 + 16 + 8 + 1 {
+            state = state.wrapping_add(0x9E37_79B9_7F4A_7C15);
+            let mut z = state;
+            z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+            z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+            z ^= z >> 31;
+
+            if i < 12 * 64 { keys.pieces[i / 64][i % 64] = z; }
+            else if i < 12 * 64 + 16 { keys.castling[i - 12 * 64] = z; }
+            else if i < 12 * 64 + 16 + 8 { keys.en_passant[i - 12 * 64 - 16] = z; }
+            else { keys.black_to_move = z; }
+            i += 1;
+        }
+
+        // No castling rights adds nothing to the hash
+        keys.castling[0] = 0;
+        keys
+    }
+
+    pub static ZOBRIST: ZobristKeys = zobrist_keys();
+
+    /*
+    Magic bitboards for the sliding pieces (https://www.chessprogramming.org/Magic_Bitboards)
+    The blockers on the rays of a square are multiplied by a magic number, and the highest bits of the product
+    give the index of the attacks in a table
+
+    The attack tables are filled the first time they are used
+    */
+    #[derive(Clone, Copy, Default)]
+    struct Magic {
+        mask: BitBoard,
+        magic: BitBoard,
+        shift: u32,
+        offset: usize,
+    }
+
+    impl Magic {
+        fn index(&self, occupied: BitBoard) -> usize {
+            self.offset + ((occupied & self.mask).wrapping_mul(self.magic) >> self.shift) as usize
+        }
+    }
+
+    struct SliderTables {
+        bishops: [Magic; 64],
+        rooks: [Magic; 64],
+        attacks: Vec<BitBoard>,
+    }
+
+    static SLIDER_TABLES: OnceLock<SliderTables> = OnceLock::new();
+
+    static BISHOP_DIRECTIONS: [(i32, i32); 4] = [(1, 1), (1, -1), (-1, -1), (-1, 1)];
+    static ROOK_DIRECTIONS: [(i32, i32); 4] = [(1, 0), (-1, 0), (0, 1), (0, -1)];
+
+    /*
+    Magic numbers of every square for bishops and rooks, the first sparse random number that works (three splitmix64 numbers and-ed together)
+    The magic_numbers test in lib.rs runs the search again from the same seed and compares
+    */
+    pub static BISHOP_MAGICS: [BitBoard; 64] = [
+        0x0408087004004013, 0x8020282100508020, 0x0008808102008400, 0x008C10520C180100,
+        0x1501104110102000, 0x0032021084050400, 0x4014040422880000, 0x0004104908201041,
+        0x0001440802142428, 0x0148083001020C20, 0x0502100082005231, 0x0040040400828400,
+        0x4185020210000080, 0x4008008820080A00, 0x5005820801880842, 0x8001844104100220,
+        0x00280021200408C8, 0x0004020888008400, 0x0040830104008180, 0x0006802802004000,
+        0x1402104C01200103, 0x0001000088A01000, 0x4004101200840400, 0x0A80300241141000,
+        0x84080A0004208800, 0x180108C410900100, 0x0080480214042408, 0x8004080114020508,
+        0x0901080401004000, 0x0208020030208400, 0x0802408100480820, 0x0042078000308809,
+        0x0058A00800100220, 0x0008080810044122, 0xC020404804100023, 0x0090A02020080080,
+        0x0114510040940040, 0x0830184110020100, 0x0008008494240204, 0x8011040028010100,
+        0x800202A084022000, 0x0404880108A01000, 0x00002023B0040804, 0x021308A018010101,
+        0x00C002120A000400, 0x1120204406400020, 0x0010029800400108, 0x011002006040C304,
+        0x8000829030120200, 0x0001010082210009, 0x0089009400880841, 0x1026202084240A00,
+        0x0003244008220400, 0x4020200841484100, 0x2884502202140000, 0x0008080800504004,
+        0x2032050100822000, 0x022801004104A040, 0x0138204040641000, 0x80200081A42A0801,
+        0x4001085240108380, 0x2300002021020C88, 0x0004100AC2880600, 0x004002280901009A,
+    ];
+
+    pub static ROOK_MAGICS: [BitBoard; 64] = [
+        0x0080002010804000, 0x0040100040002000, 0x1080081000802000, 0x8100040810002100,
+        0x0200020020100804, 0x0500040012280100, 0x04004A01009C1008, 0x2100005282092100,
+        0x2100800080304000, 0x0001002090400102, 0x0101002001001040, 0x4000800800100080,
+        0x0020800400080080, 0x010A001042000408, 0x0446000108040200, 0x80020001021040A4,
+        0x0100888000401020, 0x0A40018020004082, 0x12A0010020124300, 0x8081010020100009,
+        0x4001010010080004, 0x0400808004000200, 0x0080840008810210, 0x314052000B80C421,
+        0x4000400180006092, 0x0110004040002004, 0x4010100080200085, 0x03C0420200201008,
+        0x0148000404004020, 0xA002000280040080, 0x0200216400100882, 0xA002034200009124,
+        0x04C0008020800040, 0x0860200080804000, 0x8A05001041002008, 0x05E0801000800804,
+        0x0402280082800400, 0x1180200408011040, 0x00A8882104000210, 0x01162040A2000401,
+        0x2080008140028020, 0x0820004010014024, 0x2201004020090011, 0x2003001000090021,
+        0x0008008004008008, 0x0006000400090100, 0x0001501A08240081, 0x0020B10080420004,
+        0x00402440800D0100, 0x0060100028400040, 0x0000401080220200, 0x0002100008008280,
+        0x0488208041001002, 0x2022020080040080, 0x8482020810010400, 0x008014009C450200,
+        0x4090208010410202, 0x2046002100408016, 0x1404800811204202, 0x0051088420100101,
+        0x0181000208001005, 0x0011000400080201, 0x000810900A01480C, 0x00804412804108E2,
+    ];
+
+    /*
+    Walks every ray from a square until the edge of the board or the first blocker (the blocker is included)
+    */
+    fn ray_attacks(square: usize, occupied: BitBoard, directions: &[(i32, i32); 4]) -> BitBoard {
+        let mut attacks: BitBoard = 0;
+
+        for (dr, df) in directions {
+            let mut r = (square / 8) as i32 + dr;
+            let mut f = (square % 8) as i32 + df;
+            while (0..8).contains(&r) && (0..8).contains(&f) {
+                let b = PIECE[(r * 8 + f) as usize];
+                attacks |= b;
+                if occupied & b != 0 { break; }
+                r += dr;
+                f += df;
+            }
+        }
+
+        attacks
+    }
+
+    /*
+    Fills the attack table for every set of blockers on the rays of every square
+    The edges of the board are left out of the mask, a blocker there does not change the attacks
+    */
+    fn fill_attacks(directions: &[(i32, i32); 4], magic_numbers: &[BitBoard; 64], magics: &mut [Magic; 64], attacks: &mut Vec<BitBoard>) {
+        for square in 0..64 {
+            let edges = ((MASK_RANK[0] | MASK_RANK[7]) & !MASK_RANK[square / 8])
+                | ((MASK_FILE[0] | MASK_FILE[7]) & !MASK_FILE[square % 8]);
+            let mask = ray_attacks(square, 0, directions) & !edges;
+            let size = 1 << mask.count_ones();
+
+            let magic = Magic { mask, magic: magic_numbers[square], shift: 64 - mask.count_ones(), offset: attacks.len() };
+            attacks.resize(magic.offset + size, 0);
+
+            // Every subset of the mask (Carry-Rippler)
+            let mut subset: BitBoard = 0;
+            loop {
+                let index = magic.index(subset);
+                attacks[index] = ray_attacks(square, subset, directions);
+
+                subset = subset.wrapping_sub(mask) & mask;
+                if subset == 0 { break; }
+            }
+
+            magics[square] = magic;
+        }
+    }
+
+    fn slider_tables() -> &'static SliderTables {
+        SLIDER_TABLES.get_or_init(|| {
+            let mut tables = SliderTables {
+                bishops: [Magic::default(); 64],
+                rooks: [Magic::default(); 64],
+                attacks: Vec::new(),
+            };
+            fill_attacks(&BISHOP_DIRECTIONS, &BISHOP_MAGICS, &mut tables.bishops, &mut tables.attacks);
+            fill_attacks(&ROOK_DIRECTIONS, &ROOK_MAGICS, &mut tables.rooks, &mut tables.attacks);
+            tables
+        })
+    }
+
+    /*
+    Squares a bishop on a square (0-63) attacks, with the pieces on the board as blockers (blockers are included)
+    */
+    pub fn bishop_attacks(square: usize, occupied: BitBoard) -> BitBoard {
+        if square > 63 { return 0; }
+        let tables = slider_tables();
+        tables.attacks[tables.bishops[square].index(occupied)]
+    }
+
+    /*
+    Squares a rook on a square (0-63) attacks, with the pieces on the board as blockers (blockers are included)
+    */
+    pub fn rook_attacks(square: usize, occupied: BitBoard) -> BitBoard {
+        if square > 63 { return 0; }
+        let tables = slider_tables();
+        tables.attacks[tables.rooks[square].index(occupied)]
+    }
     
-    pub struct SQUARE();
+    pub struct Square();
 
     #[allow(dead_code)]
-    impl SQUARE {
+    impl Square {
         pub const A1: usize = 0;
         pub const B1: usize = 1;
         pub const C1: usize = 2;
@@ -234,7 +546,7 @@ Converts chess notation to a square on the board, on bad input it returns square
 */
 pub fn string_to_square(s: String) -> usize {
     let x = s.to_uppercase();
-    return match x.as_str() {
+    match x.as_str() {
         "A1"  => 0,
         "B1"  => 1,
         "C1"  => 2,
@@ -302,6 +614,16 @@ pub fn string_to_square(s: String) -> usize {
         _ => 64
     }
 }
+
+/*
+Converts a square on the board (0-63) to chess notation in lowercase, on bad input it returns "-"
+*/
+pub fn square_to_string(square: usize) -> String {
+    if square > 63 { return "-".to_string(); }
+    let file = (b'a' + (square % 8) as u8) as char;
+    let rank = (b'1' + (square / 8) as u8) as char;
+    format!("{}{}", file, rank)
+}
     
 
 }
@@ -0,0 +1,335 @@
+use std::fmt;
+
+use crate::{ChessBoard, MoveError, PieceType};
+use crate::lookup::tables::{MASK_RANK, PIECE, string_to_square, square_to_string};
+use crate::compute::patterns::squares;
+
+/// A move of a piece from one square to another square (0-63 inclusive)
+///
+/// Stores the piece to promote to (if the move is a promotion), and flags describing what kind of move it is
+///
+/// Moves are created by ChessBoard::legal_moves, or by Move::new and then played with ChessBoard::make_move
+///
+/// # Examples
+///
+/// ```
+/// use davbjor_chess::{ChessBoard, Move, PieceType};
+///
+/// let mut chess = ChessBoard::new();
+///
+/// // Find the move E2 -> E4 among the legal moves
+/// let m = chess.legal_moves().into_iter().find(|m| m.from == 12 && m.to == 28).unwrap();
+/// assert!(m.double_push);
+/// assert!(chess.make_move(m).is_ok());
+///
+/// // A move can also be created from its squares, the flags are filled in by make_move
+/// assert!(chess.make_move(Move::new(52, 36, None)).is_ok());
+/// assert_eq!(chess.piece_at(36), PieceType::BlackPawn);
+/// ```
+#[derive(Debug, PartialEq, Eq, Hash, Copy, Clone)]
+pub struct Move {
+    /// Square the piece moves from
+    pub from: usize,
+    /// Square the piece moves to
+    pub to: usize,
+    /// Piece the pawn promotes to (None if the move is not a promotion)
+    pub promotion: Option<PieceType>,
+    /// The move captures a piece (also true for en passant)
+    pub capture: bool,
+    /// The move is a pawn capturing en passant
+    pub en_passant: bool,
+    /// The move is a king castling (the rook is moved as well)
+    pub castling: bool,
+    /// The move is a pawn moving two squares forward
+    pub double_push: bool,
+}
+
+impl Move {
+    /// Creates a move from one square to another square, with all flags set to false
+    ///
+    /// The promotion piece should be given if a pawn moves to the last rank
+    pub fn new(from: usize, to: usize, promotion: Option<PieceType>) -> Self {
+        Move {
+            from,
+            to,
+            promotion,
+            capture: false,
+            en_passant: false,
+            castling: false,
+            double_push: false,
+        }
+    }
+
+    /// Checks if the move is a promotion
+    pub fn is_promotion(&self) -> bool {
+        self.promotion.is_some()
+    }
+
+    /// Writes the move in the coordinate notation of the UCI-protocol (e.g. "e2e4", "e1g1", "e7e8q")
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{Move, PieceType};
+    ///
+    /// assert_eq!(Move::new(12, 28, None).to_uci(), "e2e4");
+    /// assert_eq!(Move::new(52, 60, Some(PieceType::WhiteQueen)).to_uci(), "e7e8q");
+    /// ```
+    ///
+    pub fn to_uci(&self) -> String {
+        let mut uci = format!("{}{}", square_to_string(self.from), square_to_string(self.to));
+        match self.promotion {
+            Some(PieceType::WhiteQueen) | Some(PieceType::BlackQueen) => uci.push('q'),
+            Some(PieceType::WhiteRook) | Some(PieceType::BlackRook) => uci.push('r'),
+            Some(PieceType::WhiteBishop) | Some(PieceType::BlackBishop) => uci.push('b'),
+            Some(PieceType::WhiteKnight) | Some(PieceType::BlackKnight) => uci.push('n'),
+            _ => ()
+        }
+        uci
+    }
+}
+
+impl fmt::Display for Move {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.to_uci())
+    }
+}
+
+impl ChessBoard {
+    /// Returns a list of every legal move of the player whos turn it is
+    ///
+    /// Promotions are listed once for every piece the pawn can promote to (queen, rook, bishop, knight)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let chess = ChessBoard::new();
+    /// assert_eq!(chess.legal_moves().len(), 20);
+    /// ```
+    ///
+    pub fn legal_moves(&self) -> Vec<Move> {
+        self.generate_moves(true, true)
+    }
+
+    /// Returns a list of the legal captures (including en passant) and promotions of the player whos turn it is
+    ///
+    /// Together with legal_quiet_moves it gives every legal move, so a search can try captures before generating the rest
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let mut chess = ChessBoard::new();
+    /// chess.load("4k3/1P6/8/3p4/4P3/8/8/4K3 w - - 0 1".to_string());
+    ///
+    /// // exd5, and b8 promoting to one of four pieces
+    /// assert_eq!(chess.legal_captures().len(), 5);
+    /// assert_eq!(chess.legal_captures().len() + chess.legal_quiet_moves().len(), chess.legal_moves().len());
+    /// ```
+    ///
+    pub fn legal_captures(&self) -> Vec<Move> {
+        self.generate_moves(true, false)
+    }
+
+    /// Returns a list of the legal moves of the player whos turn it is that are not captures or promotions
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let chess = ChessBoard::new();
+    /// assert_eq!(chess.legal_quiet_moves().len(), 20);
+    /// assert!(chess.legal_captures().is_empty());
+    /// ```
+    ///
+    pub fn legal_quiet_moves(&self) -> Vec<Move> {
+        self.generate_moves(false, true)
+    }
+
+    /*
+    Generates the legal moves that are captures or promotions, quiet moves, or both
+    */
+    fn generate_moves(&self, captures: bool, quiets: bool) -> Vec<Move> {
+        let mut moves: Vec<Move> = vec![];
+        let own_pieces = if self.whites_turn { self.white_pieces } else { self.black_pieces };
+        let enemy_pieces = if self.whites_turn { self.black_pieces } else { self.white_pieces };
+        let last_rank = if self.whites_turn { MASK_RANK[7] } else { MASK_RANK[0] };
+
+        let legality = self.legality(self.whites_turn);
+
+        for from in squares(own_pieces) {
+            let piece_type = self.piece_at(from);
+            let mut targets = self.legal_targets(from, &legality);
+
+            // Pawns moving to the last rank or en passant are counted with the captures
+            let mut noisy = enemy_pieces;
+            if piece_type.is_pawn() { noisy |= self.en_passant_square | last_rank; }
+            if !captures { targets &= !noisy; }
+            if !quiets { targets &= noisy; }
+
+            for to in squares(targets) {
+                let mut m = Move::new(from, to, None);
+                m.en_passant = piece_type.is_pawn() && PIECE[to] == self.en_passant_square;
+                m.capture = enemy_pieces & PIECE[to] != 0 || m.en_passant;
+                m.castling = piece_type.is_king() && from.abs_diff(to) == 2;
+                m.double_push = piece_type.is_pawn() && from.abs_diff(to) == 16;
+
+                // Add one move for every piece the pawn can promote to
+                if piece_type.is_pawn() && last_rank & PIECE[to] != 0 {
+                    let promotions = if self.whites_turn {
+                        [PieceType::WhiteQueen, PieceType::WhiteRook, PieceType::WhiteBishop, PieceType::WhiteKnight]
+                    } else {
+                        [PieceType::BlackQueen, PieceType::BlackRook, PieceType::BlackBishop, PieceType::BlackKnight]
+                    };
+                    for p in promotions {
+                        moves.push(Move { promotion: Some(p), ..m });
+                    }
+                    continue;
+                }
+
+                moves.push(m);
+            }
+        }
+
+        moves
+    }
+
+    /// Reads a move in the coordinate notation of the UCI-protocol (e.g. "e2e4", "e1g1", "e7e8q") for the player whos turn it is
+    ///
+    /// Returns Ok(m) with the legal move (Move) matching the string
+    ///
+    /// Returns Err(e) if the string can't be read or the move is not legal, and gives the reason e (MoveError)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let mut chess = ChessBoard::new();
+    ///
+    /// let m = chess.parse_uci("e2e4").unwrap();
+    /// assert!(m.double_push);
+    /// assert!(chess.parse_uci("e2e5").is_err());
+    /// ```
+    ///
+    pub fn parse_uci(&self, uci: &str) -> Result<Move, MoveError> {
+        let uci = uci.trim();
+        if !uci.is_ascii() || (uci.len() != 4 && uci.len() != 5) {
+            return Err(MoveError::InvalidNotation(uci.to_string()));
+        }
+
+        let from = string_to_square(uci[0..2].to_string());
+        let to = string_to_square(uci[2..4].to_string());
+        if from == 64 || to == 64 {
+            return Err(MoveError::InvalidNotation(uci.to_string()));
+        }
+
+        let promotion = match uci[4..].to_ascii_lowercase().as_str() {
+            "" => None,
+            "q" => Some(if self.whites_turn { PieceType::WhiteQueen } else { PieceType::BlackQueen }),
+            "r" => Some(if self.whites_turn { PieceType::WhiteRook } else { PieceType::BlackRook }),
+            "b" => Some(if self.whites_turn { PieceType::WhiteBishop } else { PieceType::BlackBishop }),
+            "n" => Some(if self.whites_turn { PieceType::WhiteKnight } else { PieceType::BlackKnight }),
+            _ => return Err(MoveError::InvalidNotation(uci.to_string()))
+        };
+
+        let legal_moves = self.legal_moves();
+        if let Some(m) = legal_moves.iter().find(|m| m.from == from && m.to == to && m.promotion == promotion) {
+            return Ok(*m);
+        }
+        if promotion.is_none() && legal_moves.iter().any(|m| m.from == from && m.to == to) {
+            return Err(MoveError::PromotionRequired { from, to });
+        }
+
+        // The reason the piece can't make the move, or else the promotion piece is wrong
+        self.check_move(from, to)?;
+        Err(MoveError::InvalidPromotionPiece { piece: promotion.unwrap_or(PieceType::Empty) })
+    }
+
+    /// Makes a list of moves in the coordinate notation of the UCI-protocol, one after another
+    ///
+    /// Returns Ok(()) if every move was made
+    ///
+    /// Returns Err(e) without making any of the moves (the moves to redo with chess.redo() are kept) if one of them can't be read or is not legal, and gives the reason e (MoveError)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let mut chess = ChessBoard::new();
+    ///
+    /// assert!(chess.apply_uci_moves(&["e2e4", "e7e5", "g1f3"]).is_ok());
+    /// assert_eq!(chess.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    ///
+    /// // The last move is illegal, so no move is made
+    /// assert!(chess.apply_uci_moves(&["b8c6", "f3e5", "e8d7"]).is_err());
+    /// assert_eq!(chess.to_fen(), "rnbqkbnr/pppp1ppp/8/4p3/4P3/5N2/PPPP1PPP/RNBQKB1R b KQkq - 1 2");
+    /// ```
+    ///
+    pub fn apply_uci_moves(&mut self, moves: &[&str]) -> Result<(), MoveError> {
+        if moves.is_empty() { return Ok(()); }
+
+        // Making a move clears the moves to redo, they are given back if the moves are taken back
+        let redo_stack = std::mem::take(&mut self.redo_stack);
+
+        for (i, uci) in moves.iter().enumerate() {
+            let result = self.parse_uci(uci).and_then(|m| self.make_move(m));
+
+            if let Err(e) = result {
+                // Take back the moves already made
+                for _ in 0..i {
+                    self.unmake_move();
+                }
+                self.redo_stack = redo_stack;
+                return Err(e);
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Method to make a move (from ChessBoard::legal_moves or created with Move::new)
+    ///
+    /// Only the squares and the promotion piece of the move are used, the flags are found from the position
+    ///
+    /// Returns Ok(()) and makes the move if it is legal
+    ///
+    /// Returns Err(e) without making the move if it is illegal, or if a promotion is missing its piece, and gives the reason e (MoveError)
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard, Move, PieceType};
+    /// let mut chess = ChessBoard::new();
+    /// chess.load("8/P6k/8/8/8/8/8/K7 w - - 0 1".to_string());
+    ///
+    /// // Promotion without a piece is not made
+    /// assert!(chess.make_move(Move::new(48, 56, None)).is_err());
+    /// assert!(chess.make_move(Move::new(48, 56, Some(PieceType::WhiteQueen))).is_ok());
+    /// ```
+    ///
+    pub fn make_move(&mut self, m: Move) -> Result<(), MoveError> {
+        let piece_type = self.piece_at(m.from.min(64));
+        let is_promotion = (piece_type == PieceType::WhitePawn && m.to / 8 == 7) ||
+            (piece_type == PieceType::BlackPawn && m.to / 8 == 0);
+        if let Some(piece) = m.promotion.filter(|_| !is_promotion) {
+            return Err(MoveError::InvalidPromotionPiece { piece });
+        }
+
+        let result = match m.promotion {
+            Some(piece_type) => self.handle_promotion(m.from, m.to, piece_type),
+            None => self.move_piece(m.from, m.to)
+        };
+
+        match result {
+            Ok(true) => Ok(()),
+            Ok(false) => Err(MoveError::PromotionRequired { from: m.from, to: m.to }),
+            Err(e) => Err(e)
+        }
+    }
+}
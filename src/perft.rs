@@ -0,0 +1,70 @@
+use crate::{ChessBoard, Move};
+
+impl ChessBoard {
+    /// Counts every sequence of legal moves of a certain depth (number of plies) from the current position
+    ///
+    /// Used to test the move generation, by comparing with known numbers for a position (e.g. from https://www.chessprogramming.org/Perft_Results)
+    ///
+    /// The game is not changed, and checkmate, stalemate and draws are not detected along the way
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let chess = ChessBoard::new();
+    /// assert_eq!(chess.perft(1), 20);
+    /// assert_eq!(chess.perft(3), 8902);
+    /// ```
+    ///
+    pub fn perft(&self, depth: usize) -> u64 {
+        let mut chess = self.clone();
+        chess.perft_moves(depth)
+    }
+
+    /// Counts every sequence of legal moves of a certain depth (like chess.perft(depth)), split by the first move
+    ///
+    /// Returns a list of every legal move with the number of sequences starting with that move, useful to find which move a bug in the move generation is hiding behind
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let chess = ChessBoard::new();
+    /// let divided = chess.divide(2);
+    ///
+    /// assert_eq!(divided.len(), 20);
+    /// assert!(divided.iter().all(|(_, nodes)| *nodes == 20));
+    /// assert_eq!(divided.iter().map(|(_, nodes)| nodes).sum::<u64>(), chess.perft(2));
+    /// ```
+    ///
+    pub fn divide(&self, depth: usize) -> Vec<(Move, u64)> {
+        let mut chess = self.clone();
+        let mut divided: Vec<(Move, u64)> = vec![];
+        if depth == 0 { return divided; }
+
+        for m in chess.legal_moves() {
+            chess.make_move_unchecked(m);
+            divided.push((m, chess.perft_moves(depth - 1)));
+            chess.unmake_move();
+        }
+
+        divided
+    }
+
+    fn perft_moves(&mut self, depth: usize) -> u64 {
+        if depth == 0 { return 1; }
+
+        let moves = self.legal_moves();
+        if depth == 1 { return moves.len() as u64; }
+
+        let mut nodes = 0;
+        for m in moves {
+            self.make_move_unchecked(m);
+            nodes += self.perft_moves(depth - 1);
+            self.unmake_move();
+        }
+        nodes
+    }
+}
@@ -0,0 +1,610 @@
+/*
+Reading and writing of games in Portable Game Notation (PGN)
+
+A PGN-file contains one or many games, every game is a list of tag pairs followed by the movetext:
+
+[Event "Example"]
+[White "Player 1"]
+[Black "Player 2"]
+[Result "1-0"]
+
+1. e4 e5 2. Qh5 {comment} Nc6 (2... Nf6 $2 3. Qxe5+) 3. Bc4 Nf6 4. Qxf7# 1-0
+
+The moves are played through a ChessBoard, so every move is checked to be legal
+
+Played games are written as PGN by taking a GameRecord of the ChessBoard (chess.game_record())
+*/
+
+use std::fmt;
+use std::io::Read;
+
+use crate::{ChessBoard, DrawPolicy, FenError, GameResult, Move, MoveError};
+
+/// A move of a PGN-game, together with its annotations and the variations played instead of it
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnMove {
+    /// The move that was made
+    pub m: Move,
+    /// The move in standard algebraic notation as it was written (without annotation symbols like "!?")
+    pub san: String,
+    /// Numeric annotation glyphs ($1 = good move, $2 = mistake, ...), annotation symbols like "!?" are stored here as well
+    pub nags: Vec<u8>,
+    /// Comments written before the move (only at the start of the game or a variation)
+    pub comments_before: Vec<String>,
+    /// Comments written after the move
+    pub comments: Vec<String>,
+    /// Variations, every variation is a list of moves played instead of this move
+    pub variations: Vec<Vec<PgnMove>>,
+}
+
+/// A game read from a PGN-file
+///
+/// The moves of the game form a tree, where moves is the main line and every move can have variations
+///
+/// # Examples
+///
+/// ```
+/// use davbjor_chess::pgn::read_games;
+/// use davbjor_chess::GameResult;
+///
+/// let pgn = "[Event \"Example\"]\n[Result \"1-0\"]\n\n1. e4 e5 2. Qh5 Nc6 (2... Nf6 $2 3. Qxe5+) 3. Bc4 Nf6 4. Qxf7# 1-0";
+/// let games = read_games(pgn).unwrap();
+/// let game = &games[0];
+///
+/// assert_eq!(game.tag("Event"), Some("Example"));
+/// assert_eq!(game.result, GameResult::White);
+/// assert_eq!(game.moves.len(), 7);
+/// assert_eq!(game.moves[3].variations[0][0].san, "Nf6");
+/// assert_eq!(game.moves[3].variations[0][0].nags, vec![2]);
+/// assert_eq!(game.board().unwrap().game_result, GameResult::White);
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct PgnGame {
+    /// Tag pairs (name, value) in the order they were written
+    pub tags: Vec<(String, String)>,
+    /// The moves of the main line
+    pub moves: Vec<PgnMove>,
+    /// Comments that are not attached to any move
+    pub comments: Vec<String>,
+    /// Result token at the end of the movetext ("*" or no token gives GameResult::Ongoing)
+    pub result: GameResult,
+}
+
+/// Enum PgnError contains the reasons a PGN-file could not be read
+///
+/// The line (counted from 1) where the problem was found is stored in most errors
+#[derive(Debug, Clone, PartialEq)]
+pub enum PgnError {
+    /// The PGN could not be read from the reader
+    Io(String),
+    /// A tag pair is not written as [Name "value"]
+    InvalidTag { line: usize },
+    /// The FEN-tag of the game could not be read
+    ///
+    /// The line is None when the error is found in a PgnGame that was not read from text (by PgnGame::start_board or PgnGame::board)
+    InvalidFen { line: Option<usize>, error: FenError },
+    /// A move is not legal, or could not be read
+    ///
+    /// The line is None when the error is found in a PgnGame that was not read from text (by PgnGame::board)
+    IllegalMove { line: Option<usize>, san: String, error: MoveError },
+    /// A token that is not allowed at that place (e.g. a ")" without a variation)
+    UnexpectedToken { line: usize, token: String },
+    /// A comment was not closed by "}"
+    UnclosedComment { line: usize },
+    /// A variation was not closed by ")"
+    UnclosedVariation { line: usize },
+}
+
+impl fmt::Display for PgnError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            PgnError::Io(s) => write!(f, "Could not read PGN: {}", s),
+            PgnError::InvalidTag { line } => write!(f, "Invalid tag pair at line {}", line),
+            PgnError::InvalidFen { line: Some(line), error } => write!(f, "Invalid FEN at line {}: {}", line, error),
+            PgnError::InvalidFen { line: None, error } => write!(f, "Invalid FEN: {}", error),
+            PgnError::IllegalMove { line: Some(line), san, error } => write!(f, "Illegal move {} at line {}: {}", san, line, error),
+            PgnError::IllegalMove { line: None, san, error } => write!(f, "Illegal move {}: {}", san, error),
+            PgnError::UnexpectedToken { line, token } => write!(f, "Unexpected {} at line {}", token, line),
+            PgnError::UnclosedComment { line } => write!(f, "Comment at line {} is never closed", line),
+            PgnError::UnclosedVariation { line } => write!(f, "Variation at line {} is never closed", line),
+        }
+    }
+}
+
+impl std::error::Error for PgnError {}
+
+impl PgnGame {
+    /// Gives the value of a tag pair (e.g. "Event", "White", "Result"), or None if the game does not have the tag
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Gives the position the game starts from, the position of the FEN-tag or else the initial position of chess
+    pub fn start_board(&self) -> Result<ChessBoard, PgnError> {
+        match self.tag("FEN") {
+            Some(fen) => ChessBoard::from_fen(fen).map_err(|error| PgnError::InvalidFen { line: None, error }),
+            None => Ok(ChessBoard::new())
+        }
+    }
+
+    /// Gives the position at the end of the main line
+    ///
+    /// The moves are replayed without the draw rules of a draw policy (the game may have been played with any policy), the board that is given back has the default policy
+    pub fn board(&self) -> Result<ChessBoard, PgnError> {
+        let mut chess = self.start_board()?;
+        chess.draw_policy = DrawPolicy::None;
+        for pgn_move in self.moves.iter() {
+            chess.make_move(pgn_move.m).map_err(|error| PgnError::IllegalMove { line: None, san: pgn_move.san.clone(), error })?;
+        }
+        chess.draw_policy = DrawPolicy::default();
+        Ok(chess)
+    }
+}
+
+/// Record of a game played through a ChessBoard, that can be written as PGN
+///
+/// Contains every move made (by chess.move_piece(...), chess.handle_promotion(...) etc.) since the game was created or loaded
+///
+/// # Examples
+///
+/// ```
+/// use davbjor_chess::ChessBoard;
+///
+/// let mut chess = ChessBoard::new();
+/// chess.apply_uci_moves(&["f2f3", "e7e5", "g2g4", "d8h4"]).unwrap();
+///
+/// let mut record = chess.game_record();
+/// record.set_tag("White", "Player 1");
+/// record.set_tag("Black", "Player 2");
+///
+/// let pgn = record.to_pgn().unwrap();
+/// assert!(pgn.contains("[White \"Player 1\"]"));
+/// assert!(pgn.ends_with("1. f3 e5 2. g4 Qh4# 0-1\n"));
+/// ```
+#[derive(Debug, Clone, PartialEq)]
+pub struct GameRecord {
+    /// Tag pairs (name, value), starting with the seven tag roster (Event, Site, Date, Round, White, Black, Result)
+    pub tags: Vec<(String, String)>,
+    /// FEN-string of the position the game started from
+    pub start_fen: String,
+    /// Every move made in the game
+    pub moves: Vec<Move>,
+    /// Result of the game
+    pub result: GameResult,
+}
+
+impl GameRecord {
+    /// Gives the value of a tag pair, or None if the record does not have the tag
+    pub fn tag(&self, name: &str) -> Option<&str> {
+        self.tags.iter().find(|(n, _)| n == name).map(|(_, v)| v.as_str())
+    }
+
+    /// Sets the value of a tag pair (e.g. "Event", "Site", "Date", "White", "Black"), adding the tag if the record does not have it
+    ///
+    /// The Result, SetUp and FEN tags are always written from the game itself
+    pub fn set_tag(&mut self, name: &str, value: &str) {
+        match self.tags.iter_mut().find(|(n, _)| n == name) {
+            Some(tag) => tag.1 = value.to_string(),
+            None => self.tags.push((name.to_string(), value.to_string()))
+        }
+    }
+
+    /// Writes the game as PGN, with the tag pairs followed by the moves in standard algebraic notation (wrapped at 80 columns)
+    ///
+    /// The moves are replayed without the draw rules of a draw policy, so a game played past a repetition (with DrawPolicy::None) is written in full
+    ///
+    /// Returns Err(e) if the start position can't be read or a move can't be made, and gives the reason e (PgnError)
+    pub fn to_pgn(&self) -> Result<String, PgnError> {
+        let result = match self.result {
+            GameResult::White => "1-0",
+            GameResult::Black => "0-1",
+            GameResult::Draw => "1/2-1/2",
+            GameResult::Ongoing => "*"
+        };
+
+        let mut pgn = String::new();
+        for (name, value) in self.tags.iter() {
+            if name == "Result" || name == "SetUp" || name == "FEN" { continue; }
+            pgn.push_str(&format!("[{} \"{}\"]\n", name, escape(value)));
+            // Result follows the other tags of the seven tag roster
+            if name == "Black" {
+                pgn.push_str(&format!("[Result \"{}\"]\n", result));
+            }
+        }
+        if self.tag("Black").is_none() {
+            pgn.push_str(&format!("[Result \"{}\"]\n", result));
+        }
+        if self.start_fen != START_FEN {
+            pgn.push_str("[SetUp \"1\"]\n");
+            pgn.push_str(&format!("[FEN \"{}\"]\n", self.start_fen));
+        }
+        pgn.push('\n');
+
+        // Moves in standard algebraic notation, with move numbers
+        let mut words: Vec<String> = vec![];
+        let mut chess = ChessBoard::from_fen(&self.start_fen).map_err(|error| PgnError::InvalidFen { line: None, error })?;
+        chess.draw_policy = DrawPolicy::None;
+        for (i, m) in self.moves.iter().enumerate() {
+            if chess.whites_turn {
+                words.push(format!("{}.", chess.fullmove));
+            }
+            else if i == 0 {
+                words.push(format!("{}...", chess.fullmove));
+            }
+            let san = chess.to_san(*m);
+            chess.make_move(*m).map_err(|error| PgnError::IllegalMove { line: None, san: san.clone(), error })?;
+            words.push(san);
+        }
+        words.push(result.to_string());
+
+        // Wrap lines at 80 columns
+        let mut line = String::new();
+        for word in words {
+            if !line.is_empty() && line.len() + 1 + word.len() > 80 {
+                pgn.push_str(&line);
+                pgn.push('\n');
+                line.clear();
+            }
+            if !line.is_empty() { line.push(' '); }
+            line.push_str(&word);
+        }
+        pgn.push_str(&line);
+        pgn.push('\n');
+
+        Ok(pgn)
+    }
+}
+
+static START_FEN: &str = "rnbqkbnr/pppppppp/8/8/8/8/PPPPPPPP/RNBQKBNR w KQkq - 0 1";
+
+/*
+Escapes quotes and backslashes of a tag value
+*/
+fn escape(value: &str) -> String {
+    value.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+impl ChessBoard {
+    /// Gives a record of every move made since the game was created or loaded, which can be written as PGN
+    ///
+    /// The tags of the seven tag roster are set to "?" (Date to "????.??.??") and can be changed with record.set_tag(...)
+    ///
+    pub fn game_record(&self) -> GameRecord {
+        // Take back every move to find the starting position
+        let mut start = self.clone();
+        let mut moves: Vec<Move> = vec![];
+        while let Some(m) = start.unmake_move() {
+            moves.push(m);
+        }
+        moves.reverse();
+
+        GameRecord {
+            tags: vec![
+                ("Event".to_string(), "?".to_string()),
+                ("Site".to_string(), "?".to_string()),
+                ("Date".to_string(), "????.??.??".to_string()),
+                ("Round".to_string(), "?".to_string()),
+                ("White".to_string(), "?".to_string()),
+                ("Black".to_string(), "?".to_string()),
+            ],
+            start_fen: start.to_fen(),
+            moves,
+            result: self.game_result,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq)]
+enum Token {
+    Tag(String, String),
+    Comment(String),
+    Nag(u8),
+    Open,
+    Close,
+    Result(GameResult),
+    Symbol(String),
+}
+
+/// Reads every game of a PGN-file
+///
+/// Returns Err(e) if any of the games can't be read or contains an illegal move, where e (PgnError) tells what was wrong and at which line
+///
+/// Games with a FEN-tag start from that position
+///
+/// # Examples
+///
+/// ```
+/// use davbjor_chess::pgn::read_games;
+///
+/// let pgn = "[FEN \"4k3/8/8/8/8/8/8/R3K3 w Q - 0 1\"]\n[SetUp \"1\"]\n\n1. O-O-O Ke7 *\n\n1. d4 d5 *";
+/// let games = read_games(pgn).unwrap();
+///
+/// assert_eq!(games.len(), 2);
+/// assert_eq!(games[0].board().unwrap().to_fen(), "8/4k3/8/8/8/8/8/2KR4 w - - 2 2");
+/// assert_eq!(games[1].moves.len(), 2);
+/// ```
+///
+pub fn read_games(pgn: &str) -> Result<Vec<PgnGame>, PgnError> {
+    let tokens = tokenize(pgn)?;
+    let mut games: Vec<PgnGame> = vec![];
+    let mut i = 0;
+
+    while i < tokens.len() {
+        let mut game = PgnGame {
+            tags: vec![],
+            moves: vec![],
+            comments: vec![],
+            result: GameResult::Ongoing,
+        };
+
+        // Tag pairs
+        let mut fen_line = 0;
+        while i < tokens.len() {
+            match &tokens[i] {
+                (line, Token::Tag(name, value)) => {
+                    if name == "FEN" { fen_line = *line; }
+                    game.tags.push((name.clone(), value.clone()));
+                },
+                _ => break
+            }
+            i += 1;
+        }
+
+        let mut chess = match game.tag("FEN") {
+            Some(fen) => ChessBoard::from_fen(fen).map_err(|error| PgnError::InvalidFen { line: Some(fen_line), error })?,
+            None => ChessBoard::new()
+        };
+        // The result tag tells how the game ended, the moves are not stopped by the draw rules of a draw policy
+        chess.draw_policy = DrawPolicy::None;
+
+        // Movetext
+        let (moves, comments) = read_line(&tokens, &mut i, chess, false)?;
+        game.moves = moves;
+        game.comments.extend(comments);
+
+        let mut has_result = false;
+        if let Some((_, Token::Result(result))) = tokens.get(i) {
+            game.result = *result;
+            has_result = true;
+            i += 1;
+        }
+
+        // Comments between two games belong to the game before
+        if game.tags.is_empty() && game.moves.is_empty() && !has_result {
+            if let Some(last) = games.last_mut() {
+                last.comments.extend(game.comments);
+                continue;
+            }
+        }
+
+        games.push(game);
+    }
+
+    Ok(games)
+}
+
+/// Reads every game of a PGN-file from a reader (e.g. a std::fs::File)
+///
+/// Works like davbjor_chess::pgn::read_games
+///
+pub fn read_games_from<R: Read>(mut reader: R) -> Result<Vec<PgnGame>, PgnError> {
+    let mut pgn = String::new();
+    reader.read_to_string(&mut pgn).map_err(|e| PgnError::Io(e.to_string()))?;
+    read_games(&pgn)
+}
+
+/*
+Reads the moves of a line (the main line or a variation) until the end of the line
+Returns the moves and the comments that came after the last move if the line had no moves
+*/
+fn read_line(tokens: &[(usize, Token)], i: &mut usize, mut chess: ChessBoard, variation: bool) -> Result<(Vec<PgnMove>, Vec<String>), PgnError> {
+    let mut moves: Vec<PgnMove> = vec![];
+    let mut comments: Vec<String> = vec![];
+    let start_line = tokens.get(*i).map_or(0, |(line, _)| *line);
+
+    while *i < tokens.len() {
+        let (line, token) = &tokens[*i];
+        match token {
+            Token::Symbol(san) => {
+                let m = chess.parse_san(san).and_then(|m| chess.make_move(m).map(|_| m))
+                    .map_err(|error| PgnError::IllegalMove { line: Some(*line), san: san.clone(), error })?;
+                moves.push(PgnMove {
+                    m,
+                    san: san.clone(),
+                    nags: vec![],
+                    comments_before: std::mem::take(&mut comments),
+                    comments: vec![],
+                    variations: vec![],
+                });
+            },
+            Token::Comment(c) => {
+                match moves.last_mut() {
+                    Some(last) => last.comments.push(c.clone()),
+                    None => comments.push(c.clone())
+                }
+            },
+            Token::Nag(n) => {
+                match moves.last_mut() {
+                    Some(last) => last.nags.push(*n),
+                    None => return Err(PgnError::UnexpectedToken { line: *line, token: format!("${}", n) })
+                }
+            },
+            Token::Open => {
+                if moves.is_empty() {
+                    return Err(PgnError::UnexpectedToken { line: *line, token: "(".to_string() });
+                }
+                // The variation is played instead of the last move
+                let mut before = chess.clone();
+                before.unmake_move();
+                *i += 1;
+                let (variation_moves, _) = read_line(tokens, i, before, true)?;
+                if let Some(last) = moves.last_mut() {
+                    last.variations.push(variation_moves);
+                }
+                continue;
+            },
+            Token::Close => {
+                if !variation {
+                    return Err(PgnError::UnexpectedToken { line: *line, token: ")".to_string() });
+                }
+                *i += 1;
+                return Ok((moves, comments));
+            },
+            Token::Result(_) | Token::Tag(_, _) => break
+        }
+        *i += 1;
+    }
+
+    if variation {
+        return Err(PgnError::UnclosedVariation { line: start_line });
+    }
+
+    Ok((moves, comments))
+}
+
+/*
+Splits a PGN-file into tokens, together with the line they were found at
+*/
+fn tokenize(pgn: &str) -> Result<Vec<(usize, Token)>, PgnError> {
+    let chars: Vec<char> = pgn.chars().collect();
+    let mut tokens: Vec<(usize, Token)> = vec![];
+    let mut line = 1;
+    let mut i = 0;
+
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\n' => { line += 1; i += 1; },
+            _ if c.is_whitespace() => { i += 1; },
+            // Escape mechanism, the rest of the line is ignored
+            '%' if i == 0 || chars[i - 1] == '\n' => {
+                while i < chars.len() && chars[i] != '\n' { i += 1; }
+            },
+            // Line comment
+            ';' => {
+                let start = i + 1;
+                while i < chars.len() && chars[i] != '\n' { i += 1; }
+                let comment: String = chars[start..i].iter().collect();
+                tokens.push((line, Token::Comment(comment.trim().to_string())));
+            },
+            '{' => {
+                let start_line = line;
+                let start = i + 1;
+                while i < chars.len() && chars[i] != '}' {
+                    if chars[i] == '\n' { line += 1; }
+                    i += 1;
+                }
+                if i >= chars.len() {
+                    return Err(PgnError::UnclosedComment { line: start_line });
+                }
+                let comment: String = chars[start..i].iter().collect();
+                tokens.push((start_line, Token::Comment(comment.trim().to_string())));
+                i += 1;
+            },
+            '[' => {
+                let (name, value) = read_tag(&chars, &mut i).ok_or(PgnError::InvalidTag { line })?;
+                tokens.push((line, Token::Tag(name, value)));
+            },
+            '(' => { tokens.push((line, Token::Open)); i += 1; },
+            ')' => { tokens.push((line, Token::Close)); i += 1; },
+            '$' => {
+                let start = i + 1;
+                i += 1;
+                while i < chars.len() && chars[i].is_ascii_digit() { i += 1; }
+                let number: String = chars[start..i].iter().collect();
+                let nag = number.parse::<u8>().map_err(|_| PgnError::UnexpectedToken { line, token: format!("${}", number) })?;
+                tokens.push((line, Token::Nag(nag)));
+            },
+            _ => {
+                let start = i;
+                while i < chars.len() && !chars[i].is_whitespace() && !"{}()[];$".contains(chars[i]) { i += 1; }
+                let symbol: String = chars[start..i].iter().collect();
+                read_symbol(&symbol, line, &mut tokens)?;
+            }
+        }
+    }
+
+    Ok(tokens)
+}
+
+/*
+Reads a tag pair [Name "value"] starting at the '[', values can contain \" and \\
+*/
+fn read_tag(chars: &[char], i: &mut usize) -> Option<(String, String)> {
+    *i += 1;
+    while *i < chars.len() && chars[*i] == ' ' { *i += 1; }
+
+    let start = *i;
+    while *i < chars.len() && (chars[*i].is_alphanumeric() || chars[*i] == '_') { *i += 1; }
+    let name: String = chars[start..*i].iter().collect();
+    if name.is_empty() { return None; }
+
+    while *i < chars.len() && chars[*i] == ' ' { *i += 1; }
+    if chars.get(*i) != Some(&'"') { return None; }
+    *i += 1;
+
+    let mut value = String::new();
+    loop {
+        match chars.get(*i)? {
+            '"' => break,
+            '\\' => {
+                *i += 1;
+                value.push(*chars.get(*i)?);
+            },
+            '\n' => return None,
+            c => value.push(*c)
+        }
+        *i += 1;
+    }
+    *i += 1;
+
+    while *i < chars.len() && chars[*i] == ' ' { *i += 1; }
+    if chars.get(*i) != Some(&']') { return None; }
+    *i += 1;
+
+    Some((name, value))
+}
+
+/*
+Reads a symbol of the movetext: a result, a move number or a move (with optional annotation symbols like "!?")
+*/
+fn read_symbol(symbol: &str, line: usize, tokens: &mut Vec<(usize, Token)>) -> Result<(), PgnError> {
+    let result = match symbol {
+        "1-0" => Some(GameResult::White),
+        "0-1" => Some(GameResult::Black),
+        "1/2-1/2" => Some(GameResult::Draw),
+        "*" => Some(GameResult::Ongoing),
+        _ => None
+    };
+    if let Some(result) = result {
+        tokens.push((line, Token::Result(result)));
+        return Ok(());
+    }
+
+    // Remove move numbers ("12." or "12...") in front of the move, castling written with zeros is kept
+    let san = match symbol.trim_start_matches(|c: char| c.is_ascii_digit()) {
+        rest if rest.is_empty() || rest.starts_with('.') => rest.trim_start_matches('.'),
+        _ => symbol
+    };
+    if san.is_empty() { return Ok(()); }
+
+    // Annotation symbols are stored as numeric annotation glyphs
+    let annotation_start = san.find(['!', '?']).unwrap_or(san.len());
+    let nag = match &san[annotation_start..] {
+        "" => None,
+        "!" => Some(1),
+        "?" => Some(2),
+        "!!" => Some(3),
+        "??" => Some(4),
+        "!?" => Some(5),
+        "?!" => Some(6),
+        other => return Err(PgnError::UnexpectedToken { line, token: other.to_string() })
+    };
+
+    tokens.push((line, Token::Symbol(san[..annotation_start].to_string())));
+    if let Some(nag) = nag {
+        tokens.push((line, Token::Nag(nag)));
+    }
+
+    Ok(())
+}
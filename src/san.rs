@@ -0,0 +1,230 @@
+use crate::{ChessBoard, Move, MoveError, PieceType};
+use crate::lookup::tables::{PIECE, string_to_square, square_to_string};
+
+/*
+Letter of a piece in standard algebraic notation (pawns have no letter)
+*/
+fn piece_letter(piece_type: PieceType) -> Option<char> {
+    match piece_type {
+        PieceType::WhiteKnight | PieceType::BlackKnight => Some('N'),
+        PieceType::WhiteBishop | PieceType::BlackBishop => Some('B'),
+        PieceType::WhiteRook | PieceType::BlackRook => Some('R'),
+        PieceType::WhiteQueen | PieceType::BlackQueen => Some('Q'),
+        PieceType::WhiteKing | PieceType::BlackKing => Some('K'),
+        _ => None
+    }
+}
+
+/*
+Piece type of a letter in standard algebraic notation, for the player whos turn it is
+*/
+fn letter_piece(letter: char, white: bool) -> Option<PieceType> {
+    let piece_type = match letter.to_ascii_uppercase() {
+        'P' => PieceType::WhitePawn,
+        'N' => PieceType::WhiteKnight,
+        'B' => PieceType::WhiteBishop,
+        'R' => PieceType::WhiteRook,
+        'Q' => PieceType::WhiteQueen,
+        'K' => PieceType::WhiteKing,
+        _ => return None
+    };
+    if white { return Some(piece_type); }
+
+    match piece_type {
+        PieceType::WhitePawn => Some(PieceType::BlackPawn),
+        PieceType::WhiteKnight => Some(PieceType::BlackKnight),
+        PieceType::WhiteBishop => Some(PieceType::BlackBishop),
+        PieceType::WhiteRook => Some(PieceType::BlackRook),
+        PieceType::WhiteQueen => Some(PieceType::BlackQueen),
+        _ => Some(PieceType::BlackKing)
+    }
+}
+
+impl ChessBoard {
+    /// Writes a move in standard algebraic notation (SAN), as used in PGN-files
+    ///
+    /// The move should be legal in the current position (from ChessBoard::legal_moves)
+    ///
+    /// Adds disambiguation by file, rank or both when needed, and "+" for check or "#" for checkmate
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard, Move, PieceType};
+    ///
+    /// let mut chess = ChessBoard::new();
+    /// assert_eq!(chess.to_san(Move::new(6, 21, None)), "Nf3");
+    ///
+    /// chess.load("4k3/1P6/8/8/8/8/8/R3K2R w KQ - 0 1".to_string());
+    /// assert_eq!(chess.to_san(Move::new(4, 2, None)), "O-O-O");
+    /// assert_eq!(chess.to_san(Move::new(49, 57, Some(PieceType::WhiteQueen))), "b8=Q+");
+    /// assert_eq!(chess.to_san(Move::new(0, 56, None)), "Ra8+");
+    /// ```
+    ///
+    pub fn to_san(&self, m: Move) -> String {
+        if m.from > 63 || m.to > 63 { return "-".to_string(); }
+
+        let piece_type = self.piece_at(m.from);
+        let mut san = String::new();
+
+        if piece_type.is_king() && m.from.abs_diff(m.to) == 2 {
+            // Castling
+            if m.to % 8 == 6 { san.push_str("O-O"); } else { san.push_str("O-O-O"); }
+        }
+        else {
+            let en_passant = piece_type.is_pawn() && PIECE[m.to] == self.en_passant_square;
+            let capture = self.all_pieces & PIECE[m.to] != 0 || en_passant;
+            let destination = square_to_string(m.to);
+
+            match piece_letter(piece_type) {
+                None => {
+                    // Pawns show the file they came from when capturing
+                    if capture {
+                        san.push(square_to_string(m.from).chars().next().unwrap_or('-'));
+                        san.push('x');
+                    }
+                    san.push_str(&destination);
+                    if let Some(promotion) = m.promotion.and_then(piece_letter) {
+                        san.push('=');
+                        san.push(promotion);
+                    }
+                },
+                Some(letter) => {
+                    san.push(letter);
+
+                    // Other pieces of the same type that can move to the same square
+                    let others: Vec<usize> = self.legal_moves().iter()
+                        .filter(|o| o.to == m.to && o.from != m.from && self.piece_at(o.from) == piece_type)
+                        .map(|o| o.from)
+                        .collect();
+                    if !others.is_empty() {
+                        let from = square_to_string(m.from);
+                        let same_file = others.iter().any(|o| o % 8 == m.from % 8);
+                        let same_rank = others.iter().any(|o| o / 8 == m.from / 8);
+                        if !same_file {
+                            san.push_str(&from[0..1]);
+                        }
+                        else if !same_rank {
+                            san.push_str(&from[1..2]);
+                        }
+                        else {
+                            san.push_str(&from);
+                        }
+                    }
+
+                    if capture { san.push('x'); }
+                    san.push_str(&destination);
+                }
+            }
+        }
+
+        // Check or checkmate, found by making the move on a copy of the position (not of the whole game)
+        let is_promotion = (piece_type == PieceType::WhitePawn && m.to / 8 == 7) ||
+            (piece_type == PieceType::BlackPawn && m.to / 8 == 0);
+        if self.check_move(m.from, m.to).is_ok() && is_promotion == m.promotion.is_some() {
+            let mut next = self.position_copy();
+            next.make_move_unchecked(m);
+            if next.side_to_move_in_check() {
+                san.push(if next.has_legal_move(next.whites_turn) { '+' } else { '#' });
+            }
+        }
+
+        san
+    }
+
+    /// Reads a move in standard algebraic notation (SAN) for the player whos turn it is
+    ///
+    /// Returns Ok(m) with the legal move (Move) the notation describes
+    ///
+    /// Returns Err(e) if the notation can't be read, or does not match exactly one legal move, and gives the reason e (MoveError)
+    ///
+    /// The check and checkmate suffix is optional, and castling can be written with zeros ("0-0") as well
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let mut chess = ChessBoard::new();
+    ///
+    /// let m = chess.parse_san("Nf3").unwrap();
+    /// assert_eq!((m.from, m.to), (6, 21));
+    /// chess.make_move(m).unwrap();
+    ///
+    /// assert!(chess.parse_san("e4").is_err());
+    /// assert!(chess.parse_san("e5").is_ok());
+    /// ```
+    ///
+    pub fn parse_san(&self, san: &str) -> Result<Move, MoveError> {
+        // Remove check, checkmate and annotation suffixes
+        let text = san.trim().trim_end_matches(['+', '#', '!', '?']);
+        if text.is_empty() { return Err(MoveError::InvalidNotation(san.to_string())); }
+
+        let legal_moves = self.legal_moves();
+
+        // Castling
+        let castling_side = match text {
+            "O-O" | "0-0" => Some(6),
+            "O-O-O" | "0-0-0" => Some(2),
+            _ => None
+        };
+        if let Some(file) = castling_side {
+            return legal_moves.into_iter()
+                .find(|m| m.castling && m.to % 8 == file)
+                .ok_or(MoveError::NoMatchingMove(san.to_string()));
+        }
+
+        let mut chars: Vec<char> = text.chars().collect();
+
+        // Promotion piece, either written as "e8=Q" or "e8Q"
+        let mut promotion: Option<PieceType> = None;
+        let last = chars[chars.len() - 1];
+        if chars.len() >= 3 && "NBRQnbrq".contains(last) && (chars[chars.len() - 2] == '=' || chars[chars.len() - 2].is_ascii_digit()) {
+            promotion = letter_piece(last, self.whites_turn);
+            chars.pop();
+            if chars[chars.len() - 1] == '=' { chars.pop(); }
+        }
+
+        // Destination square
+        if chars.len() < 2 { return Err(MoveError::InvalidNotation(san.to_string())); }
+        let destination: String = chars[chars.len() - 2..].iter().collect();
+        let to = string_to_square(destination.clone());
+        if to == 64 || destination.chars().next().is_some_and(|c| c.is_uppercase()) {
+            return Err(MoveError::InvalidNotation(san.to_string()));
+        }
+        chars.truncate(chars.len() - 2);
+
+        // Piece letter, pawns have none
+        let mut piece_type = if self.whites_turn { PieceType::WhitePawn } else { PieceType::BlackPawn };
+        if !chars.is_empty() && "PNBRQK".contains(chars[0]) {
+            piece_type = letter_piece(chars[0], self.whites_turn).unwrap_or(piece_type);
+            chars.remove(0);
+        }
+
+        // Disambiguation by file and/or rank, and the optional capture sign
+        let mut from_file: Option<usize> = None;
+        let mut from_rank: Option<usize> = None;
+        for c in chars {
+            match c {
+                'a'..='h' => from_file = Some(c as usize - 'a' as usize),
+                '1'..='8' => from_rank = Some(c as usize - '1' as usize),
+                'x' | 'X' | ':' | '-' => (),
+                _ => return Err(MoveError::InvalidNotation(san.to_string()))
+            }
+        }
+
+        let candidates: Vec<Move> = legal_moves.into_iter()
+            .filter(|m| m.to == to && self.piece_at(m.from) == piece_type)
+            .filter(|m| from_file.is_none_or(|f| m.from % 8 == f))
+            .filter(|m| from_rank.is_none_or(|r| m.from / 8 == r))
+            .filter(|m| m.promotion == promotion || (promotion.is_none() && m.promotion.is_some()))
+            .collect();
+
+        match candidates.len() {
+            0 => Err(MoveError::NoMatchingMove(san.to_string())),
+            1 => Ok(candidates[0]),
+            _ if candidates.iter().all(|m| m.promotion.is_some() && m.from == candidates[0].from) => Err(MoveError::PromotionRequired { from: candidates[0].from, to }),
+            _ => Err(MoveError::AmbiguousMove(san.to_string()))
+        }
+    }
+}
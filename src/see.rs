@@ -0,0 +1,149 @@
+use crate::{ChessBoard, Move, PieceType};
+use crate::lookup::tables::{PIECE, KING_ATTACKS, KNIGHT_ATTACKS, PAWN_ATTACKS, bishop_attacks, rook_attacks};
+
+type BitBoard = u64;
+
+/*
+Values of the pieces in exchanges (pawn, knight, bishop, rook, queen, king)
+*/
+static SEE_VALUES: [i32; 6] = [100, 300, 300, 500, 900, 20000];
+
+/*
+Longest possible exchange on one square (every piece captures once)
+*/
+const MAX_EXCHANGE: usize = 32;
+
+impl ChessBoard {
+    /// Static exchange evaluation, the material won (or lost if negative) by a move when both players keep capturing on its square with their least valuable piece
+    ///
+    /// A player stops capturing when it would lose material. Pinned pieces are counted as attackers, and checks are not looked at
+    ///
+    /// Useful to order captures in a search, or to find pieces that are hanging or not defended well enough
+    ///
+    /// Returns 0 if there is no piece on the from square
+    ///
+    /// # Examples
+    ///
+    /// ```
+    /// use davbjor_chess::{ChessBoard};
+    ///
+    /// let mut chess = ChessBoard::new();
+    ///
+    /// // The pawn on e5 is only defended by the rook, so it is won
+    /// chess.load("1k1r4/1pp4p/p7/4p3/8/P5P1/1PP4P/2K1R3 w - - 0 1".to_string());
+    /// let m = chess.parse_uci("e1e5").unwrap();
+    /// assert_eq!(chess.see(m), 100);
+    ///
+    /// // The knight is lost for a pawn
+    /// chess.load("1k1r3q/1ppn3p/p4b2/4p3/8/P2N2P1/1PP1R1BP/2K1Q3 w - - 0 1".to_string());
+    /// let m = chess.parse_uci("d3e5").unwrap();
+    /// assert_eq!(chess.see(m), -200);
+    /// ```
+    ///
+    pub fn see(&self, m: Move) -> i32 {
+        let (from, to) = (m.from.min(64), m.to.min(64));
+        let piece_type = self.piece_at(from);
+        if piece_type == PieceType::Empty || to > 63 { return 0; }
+
+        let mut occupied = self.all_pieces ^ PIECE[from];
+        let mut gain = [0; MAX_EXCHANGE];
+
+        // Captured piece, a pawn moving diagonally to an empty square captures en passant
+        let captured = self.piece_at(to);
+        if captured != PieceType::Empty {
+            gain[0] = value(captured);
+        }
+        else if piece_type.is_pawn() && from % 8 != to % 8 {
+            gain[0] = SEE_VALUES[0];
+            occupied &= !PIECE[if to > from { to - 8 } else { to + 8 }];
+        }
+
+        // Piece standing on the square after the move
+        let mut on_square = value(piece_type);
+        if let Some(promotion) = m.promotion {
+            gain[0] += value(promotion) - SEE_VALUES[0];
+            on_square = value(promotion);
+        }
+
+        let mut attackers = self.attackers_to(to, occupied);
+        let mut white = !piece_type.is_white();
+        let mut depth = 0;
+
+        loop {
+            let own = attackers & occupied & if white { self.white_pieces } else { self.black_pieces };
+            if own == 0 { break; }
+
+            let (attacker, kind) = self.least_valuable(own, white);
+
+            // The king can't capture on a square the other player still attacks
+            let other = attackers & occupied & !PIECE[attacker] & if white { self.black_pieces } else { self.white_pieces };
+            if kind == 5 && other != 0 { break; }
+
+            if depth + 1 >= MAX_EXCHANGE { break; }
+            depth += 1;
+            gain[depth] = on_square - gain[depth - 1];
+
+            // Sliders behind the capturing piece can now capture too
+            occupied ^= PIECE[attacker];
+            attackers |= self.slider_attackers_to(to, occupied);
+            on_square = SEE_VALUES[kind];
+            white = !white;
+        }
+
+        // Every player can choose to stop capturing instead
+        while depth > 0 {
+            gain[depth - 1] = -(-gain[depth - 1]).max(gain[depth]);
+            depth -= 1;
+        }
+
+        gain[0]
+    }
+
+    /*
+    Every piece (of both colors) attacking a square, with the given squares occupied
+    */
+    fn attackers_to(&self, square: usize, occupied: BitBoard) -> BitBoard {
+        (PAWN_ATTACKS[1][square] & self.white_pawns)
+            | (PAWN_ATTACKS[0][square] & self.black_pawns)
+            | (KNIGHT_ATTACKS[square] & (self.white_knights | self.black_knights))
+            | (KING_ATTACKS[square] & (self.white_kings | self.black_kings))
+            | self.slider_attackers_to(square, occupied)
+    }
+
+    fn slider_attackers_to(&self, square: usize, occupied: BitBoard) -> BitBoard {
+        let diagonal = self.white_bishops | self.black_bishops | self.white_queens | self.black_queens;
+        let straight = self.white_rooks | self.black_rooks | self.white_queens | self.black_queens;
+        (bishop_attacks(square, occupied) & diagonal) | (rook_attacks(square, occupied) & straight)
+    }
+
+    /*
+    Square and kind (index of SEE_VALUES) of the least valuable piece among the attackers of one color
+    */
+    fn least_valuable(&self, attackers: BitBoard, white: bool) -> (usize, usize) {
+        let pieces = if white {
+            [self.white_pawns, self.white_knights, self.white_bishops, self.white_rooks, self.white_queens, self.white_kings]
+        } else {
+            [self.black_pawns, self.black_knights, self.black_bishops, self.black_rooks, self.black_queens, self.black_kings]
+        };
+
+        for (kind, bitboard) in pieces.iter().enumerate() {
+            let candidates = attackers & bitboard;
+            if candidates != 0 {
+                return (candidates.trailing_zeros() as usize, kind);
+            }
+        }
+        (64, 5)
+    }
+}
+
+fn value(piece_type: PieceType) -> i32 {
+    match piece_type {
+        PieceType::WhitePawn | PieceType::BlackPawn => SEE_VALUES[0],
+        PieceType::WhiteKnight | PieceType::BlackKnight => SEE_VALUES[1],
+        PieceType::WhiteBishop | PieceType::BlackBishop => SEE_VALUES[2],
+        PieceType::WhiteRook | PieceType::BlackRook => SEE_VALUES[3],
+        PieceType::WhiteQueen | PieceType::BlackQueen => SEE_VALUES[4],
+        PieceType::WhiteKing | PieceType::BlackKing => SEE_VALUES[5],
+        PieceType::Empty => 0
+    }
+}
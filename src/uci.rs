@@ -0,0 +1,25 @@
+// Chess engine speaking the Universal Chess Interface (UCI) over stdin / stdout
+// The commands are handled by davbjor_chess::engine::uci
+
+use davbjor_chess::engine::uci::Uci;
+use std::io::{self, BufRead, Write};
+
+fn main() {
+    let mut uci = Uci::new(send);
+
+    for line in io::stdin().lock().lines() {
+        let line = match line {
+            Ok(line) => line,
+            Err(_) => break
+        };
+        if !uci.command(&line) { break; }
+    }
+
+    uci.stop_search();
+}
+
+fn send(message: &str) {
+    let mut stdout = io::stdout().lock();
+    let _ = writeln!(stdout, "{}", message);
+    let _ = stdout.flush();
+}